@@ -0,0 +1,430 @@
+//! Clash / Mihomo YAML configs as proxy lists: the `proxies:` entries become [`ProxyConfig`]s.
+//!
+//! VLESS and Trojan entries are rewritten as share links and go through [`parse_proxy_url`], so
+//! they are checked exactly like a link in a plain list. Shadowsocks entries are built directly
+//! because their password is literal, never base64.
+
+use crate::parser::{ProxyConfig, ProxyParseError, ShadowsocksConfig, parse_proxy_url};
+use anyhow::{Context, Result, anyhow};
+use percent_encoding::{NON_ALPHANUMERIC, utf8_percent_encode};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use url::Url;
+
+#[derive(Debug, Deserialize)]
+struct ClashConfig {
+    #[serde(default)]
+    proxies: Vec<serde_yaml::Value>,
+}
+
+/// The fields of one `proxies:` entry herscat understands; everything else is ignored.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+struct ClashProxy {
+    name: Option<String>,
+    #[serde(rename = "type")]
+    kind: String,
+    server: String,
+    port: u16,
+    uuid: Option<String>,
+    password: Option<String>,
+    cipher: Option<String>,
+    network: Option<String>,
+    tls: bool,
+    servername: Option<String>,
+    sni: Option<String>,
+    flow: Option<String>,
+    encryption: Option<String>,
+    client_fingerprint: Option<String>,
+    skip_cert_verify: bool,
+    alpn: Vec<String>,
+    udp_over_tcp: bool,
+    plugin: Option<String>,
+    reality_opts: Option<RealityOpts>,
+    ws_opts: Option<WsOpts>,
+    grpc_opts: Option<GrpcOpts>,
+    h2_opts: Option<H2Opts>,
+    http_opts: Option<HttpOpts>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+struct RealityOpts {
+    public_key: Option<String>,
+    short_id: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct WsOpts {
+    path: Option<String>,
+    headers: HashMap<String, String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+struct GrpcOpts {
+    grpc_service_name: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct H2Opts {
+    host: Vec<String>,
+    path: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct HttpOpts {
+    path: Vec<String>,
+    headers: HashMap<String, Vec<String>>,
+}
+
+type Query = Vec<(&'static str, String)>;
+
+impl ClashProxy {
+    fn to_proxy_config(&self) -> Result<ProxyConfig, ProxyParseError> {
+        if self.server.is_empty() {
+            return Err(missing("server"));
+        }
+        if self.port == 0 {
+            return Err(ProxyParseError::InvalidPort(self.port));
+        }
+
+        match self.kind.as_str() {
+            "vless" => {
+                let uuid = self.uuid.as_deref().ok_or_else(|| missing("uuid"))?;
+                let mut query = vec![(
+                    "encryption",
+                    self.encryption
+                        .clone()
+                        .unwrap_or_else(|| "none".to_string()),
+                )];
+                let security = if self.reality_opts.is_some() {
+                    "reality"
+                } else if self.tls {
+                    "tls"
+                } else {
+                    "none"
+                };
+                self.security_params(security, &mut query);
+                self.transport_params(&mut query);
+                self.parse_share_link("vless", uuid, query)
+            }
+            "trojan" => {
+                let password = self
+                    .password
+                    .as_deref()
+                    .ok_or_else(|| missing("password"))?;
+                let security = if self.reality_opts.is_some() {
+                    "reality"
+                } else {
+                    "tls"
+                };
+                let mut query = Vec::new();
+                self.security_params(security, &mut query);
+                self.transport_params(&mut query);
+                self.parse_share_link("trojan", password, query)
+            }
+            "ss" => self.shadowsocks(),
+            other => Err(ProxyParseError::UnsupportedProtocol(other.to_string())),
+        }
+    }
+
+    fn security_params(&self, security: &str, query: &mut Query) {
+        query.push(("security", security.to_string()));
+        if let Some(reality) = &self.reality_opts {
+            if let Some(key) = &reality.public_key {
+                query.push(("pbk", key.clone()));
+            }
+            query.push(("sid", reality.short_id.clone()));
+        }
+        if let Some(sni) = self.sni.as_ref().or(self.servername.as_ref()) {
+            query.push(("sni", sni.clone()));
+        }
+        if let Some(fp) = &self.client_fingerprint {
+            query.push(("fp", fp.clone()));
+        }
+        if let Some(flow) = self.flow.as_ref().filter(|flow| !flow.is_empty()) {
+            query.push(("flow", flow.clone()));
+        }
+        if self.skip_cert_verify {
+            query.push(("allowInsecure", "true".to_string()));
+        }
+        if !self.alpn.is_empty() {
+            query.push(("alpn", self.alpn.join(",")));
+        }
+    }
+
+    /// Clash's `http` network is HTTP header obfuscation over raw TCP.
+    fn transport_params(&self, query: &mut Query) {
+        let network = self.network.as_deref().unwrap_or("tcp");
+        match network {
+            "ws" => {
+                query.push(("type", "ws".to_string()));
+                if let Some(opts) = &self.ws_opts {
+                    if let Some(path) = &opts.path {
+                        query.push(("path", path.clone()));
+                    }
+                    if let Some(host) = header(&opts.headers, "host") {
+                        query.push(("host", host.clone()));
+                    }
+                }
+            }
+            "grpc" => {
+                query.push(("type", "grpc".to_string()));
+                if let Some(name) = self
+                    .grpc_opts
+                    .as_ref()
+                    .and_then(|opts| opts.grpc_service_name.as_ref())
+                {
+                    query.push(("serviceName", name.clone()));
+                }
+            }
+            "h2" => {
+                query.push(("type", "h2".to_string()));
+                if let Some(opts) = &self.h2_opts {
+                    if let Some(path) = &opts.path {
+                        query.push(("path", path.clone()));
+                    }
+                    if !opts.host.is_empty() {
+                        query.push(("host", opts.host.join(",")));
+                    }
+                }
+            }
+            "http" => {
+                query.push(("type", "tcp".to_string()));
+                query.push(("headerType", "http".to_string()));
+                if let Some(opts) = &self.http_opts {
+                    if let Some(path) = opts.path.first() {
+                        query.push(("path", path.clone()));
+                    }
+                    if let Some(host) = header(&opts.headers, "host").and_then(|h| h.first()) {
+                        query.push(("host", host.clone()));
+                    }
+                }
+            }
+            other => query.push(("type", other.to_string())),
+        }
+    }
+
+    /// Parses `scheme://user@server:port?query`. The link parsers keep the user part as written,
+    /// so the percent-encoded uuid or password and the name are put back verbatim afterwards.
+    fn parse_share_link(
+        &self,
+        scheme: &str,
+        user: &str,
+        query: Query,
+    ) -> Result<ProxyConfig, ProxyParseError> {
+        let host = if self.server.contains(':') {
+            format!("[{}]", self.server)
+        } else {
+            self.server.clone()
+        };
+        let encoded = utf8_percent_encode(user, NON_ALPHANUMERIC);
+        let mut url = Url::parse(&format!("{scheme}://{encoded}@{host}:{}", self.port)).map_err(
+            |source| ProxyParseError::InvalidUrl {
+                protocol: "Clash",
+                source,
+            },
+        )?;
+        url.query_pairs_mut().extend_pairs(query);
+        let mut config = parse_proxy_url(url.as_str())?;
+        match &mut config {
+            ProxyConfig::Vless(vless) => {
+                vless.id = user.to_string();
+                vless.name = self.name.clone();
+            }
+            ProxyConfig::Trojan(trojan) => {
+                trojan.password = user.to_string();
+                trojan.name = self.name.clone();
+            }
+            _ => {}
+        }
+        Ok(config)
+    }
+
+    fn shadowsocks(&self) -> Result<ProxyConfig, ProxyParseError> {
+        if self.plugin.is_some() {
+            return Err(ProxyParseError::InvalidParameter {
+                name: "plugin",
+                reason: "is not supported by xray-core",
+            });
+        }
+        let mut settings = HashMap::new();
+        if self.udp_over_tcp {
+            settings.insert("uot".to_string(), "true".to_string());
+        }
+        let config = ShadowsocksConfig {
+            name: self.name.clone(),
+            label: None,
+            method: self.cipher.clone().ok_or_else(|| missing("cipher"))?,
+            password: self.password.clone().ok_or_else(|| missing("password"))?,
+            server: self.server.clone(),
+            port: self.port,
+            settings,
+        };
+        config.validate()?;
+        Ok(ProxyConfig::Shadowsocks(config))
+    }
+}
+
+fn missing(field: &'static str) -> ProxyParseError {
+    ProxyParseError::MissingField {
+        protocol: "Clash",
+        field,
+    }
+}
+
+/// Header names are case-insensitive, and Clash configs spell `Host` either way.
+fn header<'a, T>(headers: &'a HashMap<String, T>, name: &str) -> Option<&'a T> {
+    headers
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value)
+}
+
+/// Reads the `proxies:` list of a Clash config. Entries of other types (vmess, hysteria2, ...)
+/// or with unusable fields are skipped with a warning, like bad lines of a plain list.
+pub fn parse_clash_config(content: &str) -> Result<Vec<ProxyConfig>> {
+    let config: ClashConfig =
+        serde_yaml::from_str(content).context("Failed to parse Clash YAML")?;
+    let total = config.proxies.len();
+    let mut configs = Vec::new();
+    let mut failures: BTreeMap<String, usize> = BTreeMap::new();
+
+    for (idx, entry) in config.proxies.into_iter().enumerate() {
+        let result = serde_yaml::from_value::<ClashProxy>(entry)
+            .map_err(|e| ("malformed entry".to_string(), e.to_string()))
+            .and_then(|proxy| {
+                proxy
+                    .to_proxy_config()
+                    .map_err(|e| (e.category(), format!("{} ({e})", proxy.kind)))
+            });
+        match result {
+            Ok(cfg) => configs.push(cfg),
+            Err((category, reason)) => {
+                log::warn!("Skipping Clash proxy #{}: {}", idx + 1, reason);
+                *failures.entry(category).or_default() += 1;
+            }
+        }
+    }
+
+    let failed: usize = failures.values().sum();
+    let mut summary = format!(
+        "{total} proxies: {} parsed, {failed} skipped",
+        configs.len()
+    );
+    if !failures.is_empty() {
+        let breakdown: Vec<String> = failures
+            .iter()
+            .map(|(category, count)| format!("{category}: {count}"))
+            .collect();
+        summary += &format!(" ({})", breakdown.join(", "));
+    }
+
+    if configs.is_empty() {
+        return Err(anyhow!("No usable proxies in the Clash config ({summary})"));
+    }
+    log::info!("Clash config: {summary}");
+    Ok(configs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"
+port: 7890
+mode: rule
+proxies:
+  - name: "DE reality"
+    type: vless
+    server: de.example.com
+    port: 443
+    uuid: 11111111-1111-1111-1111-111111111111
+    network: tcp
+    tls: true
+    udp: true
+    flow: xtls-rprx-vision
+    servername: www.microsoft.com
+    client-fingerprint: chrome
+    reality-opts:
+      public-key: pubkey
+      short-id: ab12
+  - name: NL ss
+    type: ss
+    server: 203.0.113.7
+    port: 8388
+    cipher: chacha20-ietf-poly1305
+    password: "p@ss:word"
+  - name: vmess node
+    type: vmess
+    server: vm.example.com
+    port: 443
+    uuid: 22222222-2222-2222-2222-222222222222
+    alterId: 0
+    cipher: auto
+rules:
+  - MATCH,DIRECT
+"#;
+
+    #[test]
+    fn test_parse_clash_vless_and_ss() {
+        let configs = parse_clash_config(CONFIG).unwrap();
+        assert_eq!(configs.len(), 2);
+
+        let ProxyConfig::Vless(vless) = &configs[0] else {
+            panic!("expected a VLESS proxy");
+        };
+        assert_eq!(vless.name.as_deref(), Some("DE reality"));
+        assert_eq!(vless.host, "de.example.com");
+        assert_eq!(vless.id, "11111111-1111-1111-1111-111111111111");
+        assert_eq!(vless.security, "reality");
+        assert_eq!(vless.public_key.as_deref(), Some("pubkey"));
+        assert_eq!(vless.short_id.as_deref(), Some("ab12"));
+        assert_eq!(vless.sni.as_deref(), Some("www.microsoft.com"));
+        assert_eq!(vless.flow.as_deref(), Some("xtls-rprx-vision"));
+        assert_eq!(vless.fingerprint.as_deref(), Some("chrome"));
+
+        let ProxyConfig::Shadowsocks(ss) = &configs[1] else {
+            panic!("expected a Shadowsocks proxy");
+        };
+        assert_eq!(configs[1].display_name().as_deref(), Some("NL ss"));
+        assert_eq!(ss.server, "203.0.113.7");
+        assert_eq!(ss.port, 8388);
+        assert_eq!(ss.method, "chacha20-ietf-poly1305");
+        assert_eq!(ss.password, "p@ss:word");
+    }
+
+    #[test]
+    fn test_clash_ws_transport_and_empty_list() {
+        let config = r#"
+proxies:
+  - name: trojan ws
+    type: trojan
+    server: "2001:db8::1"
+    port: 443
+    password: "s3cr3t@pass/word"
+    sni: cdn.example.com
+    network: ws
+    ws-opts:
+      path: /ws
+      headers:
+        host: cdn.example.com
+"#;
+        let configs = parse_clash_config(config).unwrap();
+        let ProxyConfig::Trojan(trojan) = &configs[0] else {
+            panic!("expected a Trojan proxy");
+        };
+        assert_eq!(trojan.server, "[2001:db8::1]");
+        assert_eq!(trojan.password, "s3cr3t@pass/word");
+        assert_eq!(trojan.network.as_deref(), Some("ws"));
+        assert_eq!(trojan.path.as_deref(), Some("/ws"));
+        assert_eq!(trojan.host.as_deref(), Some("cdn.example.com"));
+
+        let err = parse_clash_config("proxies: []\n").unwrap_err();
+        assert!(err.to_string().contains("0 proxies"));
+    }
+}
@@ -1,5 +1,13 @@
-use clap::{Parser, Subcommand, ValueEnum};
+use crate::config::{DEFAULT_FINGERPRINT, UpstreamProxy};
+use crate::stressor::{DEFAULT_EMA_ALPHA, MAX_UDP_PACKET_SIZE};
+use anyhow::Context;
+use clap::parser::ValueSource;
+use clap::{ArgMatches, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
 use clap_complete::Shell;
+use serde::{Deserialize, Deserializer};
+use std::fs;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
 
 #[derive(Subcommand, Debug, Clone)]
 pub enum Commands {
@@ -8,17 +16,282 @@ pub enum Commands {
         #[arg(value_enum)]
         shell: Shell,
     },
+    /// Print the built-in default download targets
+    Targets,
+    /// Download a small file once through every proxy and report PASS/FAIL per proxy
+    Check {
+        /// URL fetched through each proxy
+        #[arg(long, value_name = "URL", default_value = DEFAULT_CHECK_URL)]
+        target: String,
+
+        /// Seconds each proxy gets to finish the download
+        #[arg(long, value_name = "SECONDS", default_value_t = 15)]
+        timeout: u64,
+    },
 }
 
-#[derive(ValueEnum, Debug, Clone, Copy)]
+/// Small download used by `herscat check` unless `--target` is given.
+pub const DEFAULT_CHECK_URL: &str = "https://speed.cloudflare.com/__down?bytes=100000";
+
+#[derive(ValueEnum, Deserialize, Debug, Clone, Copy)]
 #[value(rename_all = "kebab-case")]
+#[serde(rename_all = "kebab-case")]
 pub enum Mode {
     /// Download large files over HTTP(S) through proxies
     Download,
     /// Send continuous TCP payloads through proxies
     TcpFlood,
+    /// Repeatedly open and close TCP connections through proxies without sending payload
+    TcpConnect,
     /// Send continuous UDP payloads through proxies
     UdpFlood,
+    /// Only open and close TCP connections through proxies to measure connect latency
+    Connect,
+    /// Replay the HTTP request sequence from --replay-file through proxies
+    Replay,
+}
+
+impl Mode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Mode::Download => "download",
+            Mode::TcpFlood => "tcp-flood",
+            Mode::TcpConnect => "tcp-connect",
+            Mode::UdpFlood => "udp-flood",
+            Mode::Connect => "connect",
+            Mode::Replay => "replay",
+        }
+    }
+
+    /// `--concurrency` used when neither the command line nor `--config` sets it.
+    pub fn default_concurrency(&self) -> usize {
+        match self {
+            Mode::Download => 200,
+            Mode::Replay | Mode::TcpFlood | Mode::TcpConnect => 50,
+            Mode::UdpFlood | Mode::Connect => 20,
+        }
+    }
+
+    /// Modes that push sustained load at their targets and are kept off private ranges
+    /// unless `--allow-private` is given.
+    pub fn is_flood(&self) -> bool {
+        matches!(self, Mode::TcpFlood | Mode::TcpConnect | Mode::UdpFlood)
+    }
+
+    /// Whether the mode talks to `host:port` targets through the SOCKS5 inbound.
+    pub fn uses_socket_targets(&self) -> bool {
+        !matches!(self, Mode::Download | Mode::Replay)
+    }
+}
+
+#[derive(ValueEnum, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[value(rename_all = "kebab-case")]
+#[serde(rename_all = "kebab-case")]
+pub enum InstanceMode {
+    /// Cycle through the proxy list until --instances are started
+    Cycle,
+    /// Start exactly one instance per distinct proxy (ignores --instances)
+    OnePerProxy,
+    /// Shuffle the proxy list before cycling through it
+    Shuffle,
+}
+
+#[derive(ValueEnum, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[value(rename_all = "kebab-case")]
+#[serde(rename_all = "kebab-case")]
+pub enum ListFormat {
+    /// Clash YAML for `.yaml`/`.yml` files, one link per line otherwise
+    #[default]
+    Auto,
+    /// One vless/trojan/ss link per line
+    Urls,
+    /// Clash / Mihomo YAML config with a `proxies:` list
+    Clash,
+}
+
+impl ListFormat {
+    /// Whether the list at `path` is read as a Clash config.
+    pub fn is_clash(self, path: &str) -> bool {
+        match self {
+            ListFormat::Auto => std::path::Path::new(path)
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .is_some_and(|ext| {
+                    ext.eq_ignore_ascii_case("yaml") || ext.eq_ignore_ascii_case("yml")
+                }),
+            ListFormat::Urls => false,
+            ListFormat::Clash => true,
+        }
+    }
+}
+
+#[derive(ValueEnum, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[value(rename_all = "kebab-case")]
+#[serde(rename_all = "kebab-case")]
+pub enum InboundProtocol {
+    /// SOCKS5 inbound (required by flood and connect modes)
+    #[default]
+    Socks,
+    /// HTTP proxy inbound
+    Http,
+}
+
+impl InboundProtocol {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            InboundProtocol::Socks => "socks",
+            InboundProtocol::Http => "http",
+        }
+    }
+}
+
+#[derive(ValueEnum, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[value(rename_all = "kebab-case")]
+#[serde(rename_all = "kebab-case")]
+pub enum PayloadPattern {
+    /// Random bytes, generated once per run
+    #[default]
+    Random,
+    /// All zero bytes
+    Zero,
+    /// 0, 1, 2, ..., 255 repeating
+    Incrementing,
+    /// A repeating printable ASCII string
+    Text,
+}
+
+#[derive(ValueEnum, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[value(rename_all = "kebab-case")]
+#[serde(rename_all = "kebab-case")]
+pub enum AcceptEncoding {
+    /// Ask for uncompressed bodies
+    #[default]
+    Identity,
+    /// Ask for gzip and count the compressed bytes
+    Gzip,
+    /// Ask for deflate and count the compressed bytes
+    Deflate,
+    /// Ask for brotli and count the compressed bytes
+    Br,
+    /// Offer gzip, deflate and br and decompress; counts decompressed bytes
+    Auto,
+}
+
+impl AcceptEncoding {
+    /// `Accept-Encoding` header sent with every download, or `None` to let the client offer
+    /// the encodings it can decode.
+    pub fn header_value(&self) -> Option<&'static str> {
+        match self {
+            AcceptEncoding::Identity => Some("identity"),
+            AcceptEncoding::Gzip => Some("gzip"),
+            AcceptEncoding::Deflate => Some("deflate"),
+            AcceptEncoding::Br => Some("br"),
+            AcceptEncoding::Auto => None,
+        }
+    }
+
+    /// Whether response bodies are decompressed before their bytes are counted.
+    pub fn decompresses(&self) -> bool {
+        matches!(self, AcceptEncoding::Auto)
+    }
+}
+
+#[derive(ValueEnum, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[value(rename_all = "kebab-case")]
+#[serde(rename_all = "kebab-case")]
+pub enum XrayLogLevel {
+    /// Everything, including per-connection handshake details
+    Debug,
+    /// Connection-level events
+    Info,
+    /// Problems that do not stop the instance
+    #[default]
+    Warning,
+    /// Only errors
+    Error,
+    /// Disable xray-core logging
+    None,
+}
+
+impl XrayLogLevel {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            XrayLogLevel::Debug => "debug",
+            XrayLogLevel::Info => "info",
+            XrayLogLevel::Warning => "warning",
+            XrayLogLevel::Error => "error",
+            XrayLogLevel::None => "none",
+        }
+    }
+}
+
+/// `--server-family`: address family xray-core dials the proxy server over.
+#[derive(ValueEnum, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[value(rename_all = "kebab-case")]
+#[serde(rename_all = "kebab-case")]
+pub enum ServerFamily {
+    /// Leave resolution to xray-core's defaults (no dns block)
+    #[default]
+    Auto,
+    /// Resolve the proxy server to IPv4 addresses only
+    Ipv4,
+    /// Resolve the proxy server to IPv6 addresses only
+    Ipv6,
+}
+
+impl ServerFamily {
+    /// Outbound `domainStrategy`, also the dns block's `queryStrategy` unless `Auto`.
+    pub fn domain_strategy(&self) -> &'static str {
+        match self {
+            ServerFamily::Auto => "AsIs",
+            ServerFamily::Ipv4 => "UseIPv4",
+            ServerFamily::Ipv6 => "UseIPv6",
+        }
+    }
+}
+
+/// `--concurrency-mode`: how `--concurrency` relates to the number of xray-core instances.
+#[derive(ValueEnum, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[value(rename_all = "kebab-case")]
+#[serde(rename_all = "kebab-case")]
+pub enum ConcurrencyMode {
+    /// Every instance gets --concurrency workers, so the total grows with the instance count
+    #[default]
+    PerInstance,
+    /// --concurrency workers in total, split as evenly as possible across the instances
+    Total,
+}
+
+impl ConcurrencyMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ConcurrencyMode::PerInstance => "per-instance",
+            ConcurrencyMode::Total => "total",
+        }
+    }
+
+    /// Workers for instance `idx` of `instances`; under `Total` the first `concurrency %
+    /// instances` instances get one extra so the sum is exactly `concurrency`.
+    pub fn instance_workers(&self, concurrency: usize, instances: usize, idx: usize) -> usize {
+        match self {
+            ConcurrencyMode::PerInstance => concurrency,
+            ConcurrencyMode::Total => {
+                let instances = instances.max(1);
+                concurrency / instances + usize::from(idx < concurrency % instances)
+            }
+        }
+    }
+}
+
+#[derive(ValueEnum, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[value(rename_all = "kebab-case")]
+#[serde(rename_all = "kebab-case")]
+pub enum LogFormat {
+    /// Human-readable env_logger output
+    #[default]
+    Text,
+    /// One JSON object per line with timestamp, level, module and message
+    Json,
 }
 
 #[derive(Parser, Debug, Clone)]
@@ -29,6 +302,10 @@ pub enum Mode {
     long_about = "herscat - Launch multiple xray-core instances and run hundreds of parallel HTTP streams via SOCKS5 proxies for load/stress testing of proxy setups."
 )]
 pub struct Args {
+    /// Load run settings from a TOML or JSON file (explicit flags take precedence)
+    #[arg(long = "config", value_name = "FILE")]
+    pub config: Option<PathBuf>,
+
     /// Proxy URL to use for connection (supports vless/trojan/ss)
     #[arg(short = 'u', long, value_name = "PROXY_URL")]
     pub url: Option<String>,
@@ -37,26 +314,214 @@ pub struct Args {
     #[arg(short = 'l', long, value_name = "FILE")]
     pub list: Option<String>,
 
+    /// How to read --list; auto treats .yaml/.yml files as Clash configs
+    #[arg(long = "list-format", alias = "format", value_enum, default_value_t = ListFormat::Auto)]
+    pub list_format: ListFormat,
+
+    /// Only use proxies whose #name contains this text (case-insensitive)
+    #[arg(long = "filter-name", value_name = "TEXT")]
+    pub filter_name: Option<String>,
+
+    /// Skip proxies whose #name contains this text (case-insensitive)
+    #[arg(long = "exclude-name", value_name = "TEXT")]
+    pub exclude_name: Option<String>,
+
+    /// Use only the proxy at this 0-based index of the parsed list (for debugging a single node)
+    #[arg(long = "only-proxy", value_name = "INDEX")]
+    pub only_proxy: Option<usize>,
+
     /// Duration to run the test in seconds (0 = infinite)
     #[arg(short = 'd', long, default_value_t = 0)]
     pub duration: u64,
 
+    /// Hard stop after this many seconds regardless of mode, limits or --duration
+    #[arg(long = "deadline", value_name = "SECONDS")]
+    pub deadline: Option<u64>,
+
+    /// After --duration, stop starting new requests and let in-flight ones finish for this many seconds
+    #[arg(long = "ramp-down", value_name = "SECONDS")]
+    pub ramp_down: Option<u64>,
+
+    /// Stop after this many successful events (downloads, packets or connects)
+    #[arg(long = "max-requests", value_name = "COUNT")]
+    pub max_requests: Option<u64>,
+
+    /// Stop once this much data has been transferred (e.g. 500MB, 2GB)
+    #[arg(long = "max-bytes", value_name = "SIZE", value_parser = parse_byte_size)]
+    pub max_bytes: Option<u64>,
+
+    /// Exclude the first SECONDS of the run from totals and averages
+    #[arg(long = "warmup", value_name = "SECONDS")]
+    pub warmup: Option<u64>,
+
+    /// Connect every worker before any load is sent, then start them all together
+    #[arg(long = "connect-only-first")]
+    pub connect_only_first: bool,
+
     /// Number of xray-core instances to launch
     #[arg(short = 'x', long = "instances", default_value_t = 5)]
     pub xray_instances: usize,
 
+    /// How proxies are assigned to xray-core instances
+    #[arg(long = "instance-mode", value_enum, default_value_t = InstanceMode::Cycle)]
+    pub instance_mode: InstanceMode,
+
+    /// Explicit comma-separated listen ports, one instance each (overrides --base-port/--instances)
+    #[arg(long = "ports", value_name = "PORTS", value_delimiter = ',')]
+    pub ports: Option<Vec<u16>>,
+
+    /// Inbound protocol exposed by each xray-core instance
+    #[arg(long = "inbound", value_enum, default_value_t = InboundProtocol::Socks)]
+    pub inbound: InboundProtocol,
+
+    /// Require this username on the local SOCKS5 inbounds (needs --socks-pass)
+    #[arg(long = "socks-user", value_name = "USER")]
+    pub socks_user: Option<String>,
+
+    /// Require this password on the local SOCKS5 inbounds (needs --socks-user)
+    #[arg(long = "socks-pass", value_name = "PASS")]
+    pub socks_pass: Option<String>,
+
+    /// Mux concurrency for VLESS/Trojan outbounds (0 = disabled)
+    #[arg(long = "mux", value_name = "N", default_value_t = 0)]
+    pub mux: u16,
+
+    /// TLS/REALITY server name for every outbound, overriding per-proxy SNI
+    #[arg(long = "sni", value_name = "NAME")]
+    pub sni: Option<String>,
+
+    /// Dial every proxy server through this SOCKS5 or HTTP(S) proxy, e.g. socks5://host:1080
+    #[arg(long = "upstream-proxy", value_name = "URL")]
+    pub upstream_proxy: Option<UpstreamProxy>,
+
+    /// uTLS fingerprint for TLS/REALITY outbounds whose link has no fp parameter
+    #[arg(long = "default-fingerprint", value_name = "NAME", default_value = DEFAULT_FINGERPRINT)]
+    pub default_fingerprint: String,
+
+    /// Address family xray-core dials proxy servers over (not targets): auto|ipv4|ipv6
+    #[arg(long = "server-family", value_enum, value_name = "FAMILY", default_value_t = ServerFamily::Auto)]
+    pub server_family: ServerFamily,
+
+    /// Log level written into the generated xray-core configs
+    #[arg(long = "xray-loglevel", value_enum, default_value_t = XrayLogLevel::Warning)]
+    pub xray_loglevel: XrayLogLevel,
+
+    /// Address the local xray inbounds listen on (default 127.0.0.1)
+    #[arg(long = "listen", value_name = "IP")]
+    pub listen: Option<IpAddr>,
+
     /// Base port for SOCKS5 proxies (incremented for each instance)
     #[arg(short = 'p', long = "base-port", default_value_t = 10808)]
     pub base_port: u16,
 
-    /// Total concurrency (number of simultaneous downloads across all instances)
-    #[arg(short = 'c', long = "concurrency", default_value_t = 200)]
+    /// Abort instead of continuing when some xray-core instances fail to start
+    #[arg(long = "require-all-instances")]
+    pub require_all_instances: bool,
+
+    /// Also restart instances whose SOCKS5 inbound stops answering a greeting, not only crashed ones
+    #[arg(long = "monitor-probe", action = clap::ArgAction::SetTrue)]
+    pub monitor_probe: bool,
+
+    /// Leave crashed xray-core instances down and stop the workers of their ports
+    #[arg(long = "no-restart", action = clap::ArgAction::SetTrue)]
+    pub no_restart: bool,
+
+    /// Raise the soft open file limit to N (capped at the hard limit) before starting
+    #[arg(long = "max-open-files", value_name = "N")]
+    pub max_open_files: Option<u64>,
+
+    /// Pause in milliseconds between launching consecutive xray-core instances
+    #[arg(long = "launch-stagger", value_name = "MS", default_value_t = 100)]
+    pub launch_stagger: u64,
+
+    /// Workers per xray instance, or in total with --concurrency-mode total (simultaneous
+    /// downloads, floods or connects each)
+    /// [default: 200 download, 50 replay/tcp-flood/tcp-connect, 20 udp-flood/connect]
+    #[arg(
+        short = 'c',
+        long = "concurrency",
+        default_value_t = 200,
+        hide_default_value = true
+    )]
     pub concurrency: usize,
 
+    /// Whether --concurrency counts workers per xray instance or in total across all of them
+    #[arg(long = "concurrency-mode", value_enum, default_value_t = ConcurrencyMode::PerInstance)]
+    pub concurrency_mode: ConcurrencyMode,
+
+    /// Start with few workers and ramp up towards --concurrency while failures stay low
+    #[arg(long = "auto-concurrency", action = clap::ArgAction::SetTrue)]
+    pub auto_concurrency: bool,
+
+    /// Failure percentage per stats interval above which --auto-concurrency backs off
+    #[arg(long = "auto-threshold", value_name = "PERCENT", default_value_t = 5.0)]
+    pub auto_threshold: f64,
+
+    /// Recycle all xray-core instances when throughput stays below this many Mbps
+    #[arg(long = "stall-restart", value_name = "MBPS")]
+    pub stall_restart: Option<f64>,
+
+    /// Consecutive stats intervals below --stall-restart before instances are recycled
+    #[arg(long = "stall-intervals", value_name = "N", default_value_t = 3)]
+    pub stall_intervals: u32,
+
+    /// Maximum idle pooled HTTP connections kept per host in download mode (0 = no reuse)
+    #[arg(long = "pool-size", value_name = "N", default_value_t = 10)]
+    pub pool_size: usize,
+
+    /// Overlapping downloads per download worker, each on its own connection
+    #[arg(long = "requests-in-flight", value_name = "N", default_value_t = 1)]
+    pub requests_in_flight: usize,
+
+    /// Request at most this many bytes per download via a Range header (e.g. 10MB)
+    #[arg(long = "chunk-bytes", value_name = "SIZE", value_parser = parse_byte_size)]
+    pub chunk_bytes: Option<u64>,
+
+    /// Retry a failed download up to N times with a short backoff before counting it as failed
+    #[arg(long = "retries", value_name = "N", default_value_t = 0)]
+    pub retries: u32,
+
+    /// Verify TLS certificates of HTTPS download targets (accepted blindly by default)
+    #[arg(long = "verify-certs", action = clap::ArgAction::SetTrue)]
+    pub verify_certs: bool,
+
+    /// Time a separate TLS handshake through the proxy every 20th https download (needs --inbound socks)
+    #[arg(long = "tls-timing", action = clap::ArgAction::SetTrue)]
+    pub tls_timing: bool,
+
+    /// Do not follow HTTP redirects in download mode; a 3xx response counts as the result
+    #[arg(long = "no-redirects", action = clap::ArgAction::SetTrue)]
+    pub no_redirects: bool,
+
+    /// Count non-2xx HTTP responses as failed events instead of successes
+    #[arg(long = "fail-on-status", action = clap::ArgAction::SetTrue)]
+    pub fail_on_status: bool,
+
+    /// Count downloads whose body length differs from Content-Length as partial
+    #[arg(long = "check-length", action = clap::ArgAction::SetTrue)]
+    pub check_length: bool,
+
+    /// Accept-Encoding of download requests; only `auto` counts decompressed instead of wire bytes
+    #[arg(
+        long = "accept-encoding",
+        value_name = "ENCODING",
+        value_enum,
+        default_value_t = AcceptEncoding::Identity
+    )]
+    pub accept_encoding: AcceptEncoding,
+
     /// Custom target URLs for stress testing (comma-separated)
     #[arg(short = 't', long = "targets", value_name = "URLS")]
     pub custom_targets: Option<String>,
 
+    /// File with one target per line (blank lines and # comments ignored)
+    #[arg(long = "targets-file", value_name = "FILE")]
+    pub targets_file: Option<PathBuf>,
+
+    /// JSON-lines file of HTTP requests to loop through in replay mode
+    #[arg(long = "replay-file", value_name = "FILE")]
+    pub replay_file: Option<PathBuf>,
+
     /// Operation mode to run the stressor with
     #[arg(short = 'm', long = "mode", value_enum, default_value_t = Mode::Download)]
     pub mode: Mode,
@@ -70,10 +535,66 @@ pub struct Args {
     )]
     pub packet_size: u32,
 
-    /// Packet rate in packets per second per task (TCP/UDP modes)
+    /// Byte pattern used to fill tcp/udp flood payloads
+    #[arg(long = "payload-pattern", value_enum, default_value_t = PayloadPattern::Random)]
+    pub payload_pattern: PayloadPattern,
+
+    /// File whose bytes tcp-flood sends instead of a generated payload, reading an answer after each
+    #[arg(long = "tcp-template", value_name = "FILE")]
+    pub tcp_template: Option<PathBuf>,
+
+    /// End of each --tcp-template answer, with \r \n \t \0 \\ \xHH escapes (e.g. "\r\n\r\n")
+    #[arg(long = "tcp-read-until", value_name = "DELIM")]
+    pub tcp_read_until: Option<String>,
+
+    /// Size of each --tcp-template answer (e.g. 512, 4KB)
+    #[arg(long = "tcp-read-bytes", value_name = "SIZE", value_parser = parse_byte_size)]
+    pub tcp_read_bytes: Option<u64>,
+
+    /// Packet rate in packets per second per task (TCP/UDP modes, connect attempts in connect mode)
     #[arg(short = 'r', long = "packet-rate", value_name = "PPS")]
     pub packet_rate: Option<u32>,
 
+    /// Aggregate packet rate of the whole run, split evenly across all workers (instead of --packet-rate)
+    #[arg(long = "total-pps", value_name = "PPS")]
+    pub total_pps: Option<u32>,
+
+    /// Minimum random think time between send iterations in milliseconds
+    #[arg(long = "jitter-min", value_name = "MS")]
+    pub jitter_min: Option<u64>,
+
+    /// Maximum random think time between send iterations in milliseconds
+    #[arg(long = "jitter-max", value_name = "MS")]
+    pub jitter_max: Option<u64>,
+
+    /// Redo the full SOCKS5 UDP associate handshake after every UDP flood packet
+    #[arg(long = "udp-reassociate", action = clap::ArgAction::SetTrue)]
+    pub udp_reassociate: bool,
+
+    /// Allow flood targets on loopback, private and link-local addresses
+    #[arg(long = "allow-private", action = clap::ArgAction::SetTrue)]
+    pub allow_private: bool,
+
+    /// Give every worker its own shuffled copy of the target list and walk it in order
+    #[arg(long = "shuffle-targets", action = clap::ArgAction::SetTrue)]
+    pub shuffle_targets: bool,
+
+    /// Seed every worker's random choices (targets, User-Agents, jitter, payload) for reproducible runs
+    #[arg(long = "seed", value_name = "SEED")]
+    pub seed: Option<u64>,
+
+    /// Bind every UDP flood association to this local port (SO_REUSEPORT) instead of an ephemeral one
+    #[arg(long = "udp-local-port", value_name = "PORT")]
+    pub udp_local_port: Option<u16>,
+
+    /// Local IP xray-core sends proxy traffic from (all modes)
+    #[arg(long = "local-address", value_name = "IP")]
+    pub local_address: Option<IpAddr>,
+
+    /// Hold each tcp-connect connection open for this many milliseconds before closing it
+    #[arg(long = "hold-ms", value_name = "MS")]
+    pub hold_ms: Option<u64>,
+
     /// Number of packets to send before reconnecting (0 = keep connection open)
     #[arg(
         short = 'P',
@@ -83,6 +604,18 @@ pub struct Args {
     )]
     pub packets_per_connection: u32,
 
+    /// Reconnect each tcp-flood connection after this many seconds, whatever its packet count
+    #[arg(long = "conn-lifetime", value_name = "SECONDS")]
+    pub conn_lifetime: Option<u64>,
+
+    /// Exit with a non-zero status if the failure rate exceeds this percentage
+    #[arg(long = "fail-over", value_name = "PERCENT")]
+    pub fail_over: Option<f64>,
+
+    /// Skip the banner and progress output and only log errors; the final statistics still print
+    #[arg(short = 'q', long = "quiet", action = clap::ArgAction::SetTrue)]
+    pub quiet: bool,
+
     /// Enable verbose logging
     #[arg(short = 'v', long = "verbose", action = clap::ArgAction::SetTrue)]
     pub verbose: bool,
@@ -91,15 +624,353 @@ pub struct Args {
     #[arg(long = "debug", action = clap::ArgAction::SetTrue)]
     pub debug: bool,
 
+    /// Log output format
+    #[arg(long = "log-format", value_enum, default_value_t = LogFormat::Text)]
+    pub log_format: LogFormat,
+
     /// Statistics reporting interval in seconds
     #[arg(short = 'i', long = "stats-interval", default_value_t = 5)]
     pub stats_interval: u64,
 
+    /// Smoothing of the periodic rates: weight of the newest interval, 1.0 = no smoothing
+    #[arg(long = "ema-alpha", value_name = "ALPHA", default_value_t = DEFAULT_EMA_ALPHA)]
+    pub ema_alpha: f64,
+
+    /// Append throughput samples to a CSV file every stats interval
+    #[arg(long = "csv", value_name = "FILE")]
+    pub csv: Option<PathBuf>,
+
+    /// Carry lifetime totals across restarts: loaded at startup, rewritten every stats interval and at exit
+    #[arg(long = "state-file", value_name = "FILE")]
+    pub state_file: Option<PathBuf>,
+
+    /// Write a JSON report with the run config and final statistics when the test ends
+    #[arg(long = "report", value_name = "FILE")]
+    pub report: Option<PathBuf>,
+
+    /// Embed every instance's generated xray config in the --report file
+    #[arg(long = "report-configs", action = clap::ArgAction::SetTrue)]
+    pub report_configs: bool,
+
+    /// Show a live full-screen dashboard instead of the periodic statistics lines
+    #[arg(long = "tui", action = clap::ArgAction::SetTrue)]
+    pub tui: bool,
+
     #[command(subcommand)]
     pub cmd: Option<Commands>,
 }
 
+/// Parses a human-readable size such as `512KB`, `100MB` or `1.5GB` into bytes.
+/// Suffixes are case-insensitive and use 1024-based units; a bare number means bytes.
+pub fn parse_byte_size(value: &str) -> Result<u64, String> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+
+    let number: f64 = number
+        .parse()
+        .map_err(|_| format!("invalid size '{value}', expected e.g. 100MB or 2GB"))?;
+    let multiplier: u64 = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kb" | "kib" => 1024,
+        "m" | "mb" | "mib" => 1024 * 1024,
+        "g" | "gb" | "gib" => 1024 * 1024 * 1024,
+        "t" | "tb" | "tib" => 1024 * 1024 * 1024 * 1024,
+        other => return Err(format!("unknown size unit '{other}' in '{value}'")),
+    };
+
+    Ok((number * multiplier as f64).round() as u64)
+}
+
+fn deserialize_byte_size<'de, D>(deserializer: D) -> Result<Option<u64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum RawSize {
+        Bytes(u64),
+        Text(String),
+    }
+
+    match Option::<RawSize>::deserialize(deserializer)? {
+        None => Ok(None),
+        Some(RawSize::Bytes(bytes)) => Ok(Some(bytes)),
+        Some(RawSize::Text(text)) => parse_byte_size(&text)
+            .map(Some)
+            .map_err(serde::de::Error::custom),
+    }
+}
+
+/// Run settings loaded from `--config`. Keys use the long flag names.
+#[derive(Deserialize, Debug, Default)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct RunConfig {
+    pub url: Option<String>,
+    pub list: Option<String>,
+    pub list_format: Option<ListFormat>,
+    pub filter_name: Option<String>,
+    pub exclude_name: Option<String>,
+    pub only_proxy: Option<usize>,
+    pub duration: Option<u64>,
+    pub deadline: Option<u64>,
+    pub ramp_down: Option<u64>,
+    pub max_requests: Option<u64>,
+    #[serde(deserialize_with = "deserialize_byte_size")]
+    pub max_bytes: Option<u64>,
+    pub warmup: Option<u64>,
+    pub connect_only_first: Option<bool>,
+    pub instances: Option<usize>,
+    pub instance_mode: Option<InstanceMode>,
+    pub ports: Option<Vec<u16>>,
+    pub inbound: Option<InboundProtocol>,
+    pub socks_user: Option<String>,
+    pub socks_pass: Option<String>,
+    pub mux: Option<u16>,
+    pub sni: Option<String>,
+    pub upstream_proxy: Option<UpstreamProxy>,
+    pub default_fingerprint: Option<String>,
+    pub server_family: Option<ServerFamily>,
+    pub listen: Option<IpAddr>,
+    pub xray_loglevel: Option<XrayLogLevel>,
+    pub base_port: Option<u16>,
+    pub launch_stagger: Option<u64>,
+    pub require_all_instances: Option<bool>,
+    pub monitor_probe: Option<bool>,
+    pub no_restart: Option<bool>,
+    pub max_open_files: Option<u64>,
+    pub concurrency: Option<usize>,
+    pub concurrency_mode: Option<ConcurrencyMode>,
+    pub auto_concurrency: Option<bool>,
+    pub auto_threshold: Option<f64>,
+    pub stall_restart: Option<f64>,
+    pub stall_intervals: Option<u32>,
+    pub pool_size: Option<usize>,
+    pub requests_in_flight: Option<usize>,
+    pub verify_certs: Option<bool>,
+    pub tls_timing: Option<bool>,
+    pub no_redirects: Option<bool>,
+    pub fail_on_status: Option<bool>,
+    pub check_length: Option<bool>,
+    pub accept_encoding: Option<AcceptEncoding>,
+    #[serde(deserialize_with = "deserialize_byte_size")]
+    pub chunk_bytes: Option<u64>,
+    pub retries: Option<u32>,
+    pub targets: Option<String>,
+    pub targets_file: Option<PathBuf>,
+    pub replay_file: Option<PathBuf>,
+    pub mode: Option<Mode>,
+    pub packet_size: Option<u32>,
+    pub payload_pattern: Option<PayloadPattern>,
+    pub tcp_template: Option<PathBuf>,
+    pub tcp_read_until: Option<String>,
+    #[serde(deserialize_with = "deserialize_byte_size")]
+    pub tcp_read_bytes: Option<u64>,
+    pub packet_rate: Option<u32>,
+    pub total_pps: Option<u32>,
+    pub jitter_min: Option<u64>,
+    pub jitter_max: Option<u64>,
+    pub packets_per_conn: Option<u32>,
+    pub conn_lifetime: Option<u64>,
+    pub hold_ms: Option<u64>,
+    pub udp_reassociate: Option<bool>,
+    pub allow_private: Option<bool>,
+    pub shuffle_targets: Option<bool>,
+    pub seed: Option<u64>,
+    pub udp_local_port: Option<u16>,
+    pub local_address: Option<IpAddr>,
+    pub fail_over: Option<f64>,
+    pub quiet: Option<bool>,
+    pub verbose: Option<bool>,
+    pub log_format: Option<LogFormat>,
+    pub debug: Option<bool>,
+    pub stats_interval: Option<u64>,
+    pub ema_alpha: Option<f64>,
+    pub csv: Option<PathBuf>,
+    pub state_file: Option<PathBuf>,
+    pub report: Option<PathBuf>,
+    pub report_configs: Option<bool>,
+    pub tui: Option<bool>,
+}
+
+impl RunConfig {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read run config {}", path.display()))?;
+
+        let is_json = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("json"));
+        if is_json {
+            serde_json::from_str(&content)
+                .with_context(|| format!("Failed to parse JSON run config {}", path.display()))
+        } else {
+            toml::from_str(&content)
+                .with_context(|| format!("Failed to parse TOML run config {}", path.display()))
+        }
+    }
+}
+
 impl Args {
+    /// Parses the command line and merges the `--config` file, if any, underneath it.
+    pub fn load() -> anyhow::Result<Self> {
+        let matches = Self::command().get_matches();
+        let run_config = match matches.get_one::<PathBuf>("config") {
+            Some(path) => Some(RunConfig::load(path)?),
+            None => None,
+        };
+        Self::from_parts(&matches, run_config)
+    }
+
+    /// Default log filter when `RUST_LOG` is unset; an explicit `--verbose`/`--debug` outranks
+    /// `--quiet`.
+    pub fn log_level(&self) -> &'static str {
+        match (self.debug, self.verbose, self.quiet) {
+            (true, _, _) => "debug",
+            (false, true, _) => "info",
+            (false, false, true) => "error",
+            _ => "warn",
+        }
+    }
+
+    fn from_parts(matches: &ArgMatches, run_config: Option<RunConfig>) -> anyhow::Result<Self> {
+        let mut args = Self::from_arg_matches(matches)?;
+        let mut concurrency_set = !matches!(
+            matches.value_source("concurrency"),
+            Some(ValueSource::DefaultValue) | None
+        );
+
+        if let Some(run_config) = run_config {
+            concurrency_set |= run_config.concurrency.is_some();
+            args.merge_run_config(run_config, matches);
+        }
+
+        // Mode-aware default: 200 workers per instance is fine for downloads but far too many
+        // UDP associations once multiplied by the instance count.
+        if !concurrency_set {
+            args.concurrency = args.mode.default_concurrency();
+        }
+
+        Ok(args)
+    }
+
+    /// Copies file values into fields that were not given explicitly on the command line.
+    pub fn merge_run_config(&mut self, file: RunConfig, matches: &ArgMatches) {
+        let from_cli = |id: &str| matches.value_source(id) == Some(ValueSource::CommandLine);
+
+        macro_rules! merge {
+            ($($src:ident => $dst:ident),* $(,)?) => {
+                $(
+                    if let Some(value) = file.$src
+                        && !from_cli(stringify!($dst))
+                    {
+                        self.$dst = value;
+                    }
+                )*
+            };
+        }
+
+        macro_rules! merge_opt {
+            ($($src:ident => $dst:ident),* $(,)?) => {
+                $(
+                    if file.$src.is_some() && !from_cli(stringify!($dst)) {
+                        self.$dst = file.$src;
+                    }
+                )*
+            };
+        }
+
+        merge_opt!(
+            url => url,
+            list => list,
+            filter_name => filter_name,
+            exclude_name => exclude_name,
+            only_proxy => only_proxy,
+            deadline => deadline,
+            ramp_down => ramp_down,
+            max_requests => max_requests,
+            max_bytes => max_bytes,
+            chunk_bytes => chunk_bytes,
+            warmup => warmup,
+            ports => ports,
+            targets => custom_targets,
+            targets_file => targets_file,
+            replay_file => replay_file,
+            tcp_template => tcp_template,
+            tcp_read_until => tcp_read_until,
+            tcp_read_bytes => tcp_read_bytes,
+            packet_rate => packet_rate,
+            total_pps => total_pps,
+            seed => seed,
+            jitter_min => jitter_min,
+            jitter_max => jitter_max,
+            hold_ms => hold_ms,
+            conn_lifetime => conn_lifetime,
+            udp_local_port => udp_local_port,
+            local_address => local_address,
+            fail_over => fail_over,
+            socks_user => socks_user,
+            socks_pass => socks_pass,
+            sni => sni,
+            upstream_proxy => upstream_proxy,
+            stall_restart => stall_restart,
+            listen => listen,
+            max_open_files => max_open_files,
+            csv => csv,
+            state_file => state_file,
+            report => report,
+        );
+
+        merge!(
+            list_format => list_format,
+            duration => duration,
+            instances => xray_instances,
+            instance_mode => instance_mode,
+            inbound => inbound,
+            mux => mux,
+            xray_loglevel => xray_loglevel,
+            default_fingerprint => default_fingerprint,
+            server_family => server_family,
+            base_port => base_port,
+            launch_stagger => launch_stagger,
+            require_all_instances => require_all_instances,
+            monitor_probe => monitor_probe,
+            no_restart => no_restart,
+            concurrency => concurrency,
+            concurrency_mode => concurrency_mode,
+            auto_concurrency => auto_concurrency,
+            auto_threshold => auto_threshold,
+            connect_only_first => connect_only_first,
+            stall_intervals => stall_intervals,
+            pool_size => pool_size,
+            requests_in_flight => requests_in_flight,
+            retries => retries,
+            verify_certs => verify_certs,
+            tls_timing => tls_timing,
+            no_redirects => no_redirects,
+            fail_on_status => fail_on_status,
+            check_length => check_length,
+            accept_encoding => accept_encoding,
+            mode => mode,
+            packet_size => packet_size,
+            payload_pattern => payload_pattern,
+            packets_per_conn => packets_per_connection,
+            udp_reassociate => udp_reassociate,
+            allow_private => allow_private,
+            shuffle_targets => shuffle_targets,
+            quiet => quiet,
+            verbose => verbose,
+            log_format => log_format,
+            debug => debug,
+            stats_interval => stats_interval,
+            ema_alpha => ema_alpha,
+            report_configs => report_configs,
+            tui => tui,
+        );
+    }
+
     pub fn validate(&self) -> anyhow::Result<()> {
         if self.url.is_none() && self.list.is_none() {
             return Err(anyhow::anyhow!("Either --url or --list must be provided"));
@@ -115,26 +986,572 @@ impl Args {
             return Err(anyhow::anyhow!("Xray instances must be greater than 0"));
         }
 
+        if let Some(ports) = &self.ports {
+            if ports.is_empty() {
+                return Err(anyhow::anyhow!("--ports requires at least one port"));
+            }
+            if ports.contains(&0) {
+                return Err(anyhow::anyhow!("--ports entries must be greater than 0"));
+            }
+        }
+
         if self.concurrency == 0 {
             return Err(anyhow::anyhow!("Concurrency must be greater than 0"));
         }
 
+        if self.max_requests == Some(0) {
+            return Err(anyhow::anyhow!(
+                "Max requests must be greater than 0 when provided"
+            ));
+        }
+
+        if self.max_bytes == Some(0) {
+            return Err(anyhow::anyhow!(
+                "Max bytes must be greater than 0 when provided"
+            ));
+        }
+
+        if self.max_bytes.is_some() && matches!(self.mode, Mode::Connect | Mode::TcpConnect) {
+            return Err(anyhow::anyhow!(
+                "--max-bytes has no effect in {:?} mode, which transfers no data",
+                self.mode
+            ));
+        }
+
+        if self.requests_in_flight == 0 {
+            return Err(anyhow::anyhow!("--requests-in-flight must be at least 1"));
+        }
+
+        if self.requests_in_flight > 1 && !matches!(self.mode, Mode::Download) {
+            return Err(anyhow::anyhow!(
+                "--requests-in-flight only applies to download mode"
+            ));
+        }
+
+        if self.chunk_bytes == Some(0) {
+            return Err(anyhow::anyhow!(
+                "Chunk bytes must be greater than 0 when provided"
+            ));
+        }
+
+        if self.chunk_bytes.is_some() && !matches!(self.mode, Mode::Download) {
+            return Err(anyhow::anyhow!(
+                "--chunk-bytes only applies to download mode"
+            ));
+        }
+
+        if self.deadline == Some(0) {
+            return Err(anyhow::anyhow!(
+                "Deadline must be greater than 0 when provided"
+            ));
+        }
+
+        if self.ramp_down.is_some() && self.duration == 0 {
+            return Err(anyhow::anyhow!("--ramp-down requires --duration"));
+        }
+
+        if self.conn_lifetime == Some(0) {
+            return Err(anyhow::anyhow!("--conn-lifetime must be greater than 0"));
+        }
+
+        if self.conn_lifetime.is_some() && !matches!(self.mode, Mode::TcpFlood) {
+            return Err(anyhow::anyhow!(
+                "--conn-lifetime only applies to tcp-flood mode"
+            ));
+        }
+
+        if self.hold_ms.is_some() && !matches!(self.mode, Mode::TcpConnect) {
+            return Err(anyhow::anyhow!(
+                "--hold-ms only applies to tcp-connect mode"
+            ));
+        }
+
+        if self.udp_local_port.is_some() && !matches!(self.mode, Mode::UdpFlood) {
+            return Err(anyhow::anyhow!(
+                "--udp-local-port only applies to udp-flood mode"
+            ));
+        }
+
+        if self.udp_reassociate {
+            if !matches!(self.mode, Mode::UdpFlood) {
+                return Err(anyhow::anyhow!(
+                    "--udp-reassociate only applies to udp-flood mode"
+                ));
+            }
+            if self.packets_per_connection > 0 {
+                return Err(anyhow::anyhow!(
+                    "--udp-reassociate already sends one packet per association, drop --packets-per-conn"
+                ));
+            }
+        }
+
+        if self.tls_timing && !matches!(self.mode, Mode::Download) {
+            return Err(anyhow::anyhow!(
+                "--tls-timing only applies to download mode"
+            ));
+        }
+
+        if self.monitor_probe && self.inbound != InboundProtocol::Socks {
+            return Err(anyhow::anyhow!("--monitor-probe requires --inbound socks"));
+        }
+
+        if self.no_restart {
+            let restarting = if self.monitor_probe {
+                Some("--monitor-probe")
+            } else if self.stall_restart.is_some() {
+                Some("--stall-restart")
+            } else {
+                None
+            };
+            if let Some(flag) = restarting {
+                return Err(anyhow::anyhow!(
+                    "--no-restart cannot be combined with {flag}"
+                ));
+            }
+        }
+
+        if self.tls_timing && self.inbound != InboundProtocol::Socks {
+            return Err(anyhow::anyhow!("--tls-timing requires --inbound socks"));
+        }
+
+        if self.report_configs && self.report.is_none() {
+            return Err(anyhow::anyhow!("--report-configs requires --report"));
+        }
+
+        if self.sni.as_deref().is_some_and(|sni| sni.trim().is_empty()) {
+            return Err(anyhow::anyhow!("--sni must not be empty"));
+        }
+
+        if self.default_fingerprint.trim().is_empty() {
+            return Err(anyhow::anyhow!("--default-fingerprint must not be empty"));
+        }
+
+        if self.stats_interval == 0 {
+            return Err(anyhow::anyhow!("Stats interval must be greater than 0"));
+        }
+
+        if !(self.ema_alpha > 0.0 && self.ema_alpha <= 1.0) {
+            return Err(anyhow::anyhow!(
+                "--ema-alpha must be greater than 0 and at most 1 (1 disables smoothing)"
+            ));
+        }
+
+        if let Some(warmup) = self.warmup
+            && self.duration > 0
+            && warmup >= self.duration
+        {
+            return Err(anyhow::anyhow!(
+                "--warmup ({warmup}s) must be shorter than --duration ({}s)",
+                self.duration
+            ));
+        }
+
+        if self.tcp_template.is_some() && !matches!(self.mode, Mode::TcpFlood) {
+            return Err(anyhow::anyhow!("--tcp-template requires --mode tcp-flood"));
+        }
+
+        if self.tcp_template.is_none()
+            && (self.tcp_read_until.is_some() || self.tcp_read_bytes.is_some())
+        {
+            return Err(anyhow::anyhow!(
+                "--tcp-read-until and --tcp-read-bytes require --tcp-template"
+            ));
+        }
+
+        if self.tcp_read_until.is_some() && self.tcp_read_bytes.is_some() {
+            return Err(anyhow::anyhow!(
+                "Cannot specify both --tcp-read-until and --tcp-read-bytes, choose one"
+            ));
+        }
+
+        if self.tcp_read_bytes == Some(0) {
+            return Err(anyhow::anyhow!("--tcp-read-bytes must be greater than 0"));
+        }
+
+        if self.connect_only_first && matches!(self.mode, Mode::Connect) {
+            return Err(anyhow::anyhow!(
+                "--connect-only-first does not apply to connect mode, which measures connection setup"
+            ));
+        }
+
+        if let Some(threshold) = self.fail_over
+            && !(0.0..=100.0).contains(&threshold)
+        {
+            return Err(anyhow::anyhow!(
+                "--fail-over must be a percentage between 0 and 100"
+            ));
+        }
+
+        if let Some(mbps) = self.stall_restart {
+            if !(mbps.is_finite() && mbps > 0.0) {
+                return Err(anyhow::anyhow!(
+                    "--stall-restart must be a positive Mbps value"
+                ));
+            }
+            if matches!(self.mode, Mode::Connect | Mode::TcpConnect) {
+                return Err(anyhow::anyhow!(
+                    "--stall-restart needs a mode that moves data, not {}",
+                    self.mode.as_str()
+                ));
+            }
+        }
+
+        if self.stall_intervals == 0 {
+            return Err(anyhow::anyhow!("--stall-intervals must be greater than 0"));
+        }
+
+        if !(0.0..=100.0).contains(&self.auto_threshold) {
+            return Err(anyhow::anyhow!(
+                "--auto-threshold must be a percentage between 0 and 100"
+            ));
+        }
+
         if self.packet_size == 0 {
             return Err(anyhow::anyhow!("Packet size must be greater than 0"));
         }
 
+        if matches!(self.mode, Mode::UdpFlood) && self.packet_size > MAX_UDP_PACKET_SIZE {
+            return Err(anyhow::anyhow!(
+                "--packet-size {} does not fit in a SOCKS5 UDP datagram; udp-flood allows at most {} bytes",
+                self.packet_size,
+                MAX_UDP_PACKET_SIZE
+            ));
+        }
+
         if let Some(rate) = self.packet_rate && rate == 0 {
             return Err(anyhow::anyhow!(
                 "Packet rate must be greater than 0 when provided"
             ));
         }
 
-        if matches!(self.mode, Mode::TcpFlood | Mode::UdpFlood) && self.custom_targets.is_none() {
+        if self.total_pps == Some(0) {
+            return Err(anyhow::anyhow!("--total-pps must be greater than 0"));
+        }
+
+        if self.packet_rate.is_some() && self.total_pps.is_some() {
+            return Err(anyhow::anyhow!(
+                "--packet-rate and --total-pps cannot be combined"
+            ));
+        }
+
+        if let (Some(min), Some(max)) = (self.jitter_min, self.jitter_max)
+            && min > max
+        {
             return Err(anyhow::anyhow!(
-                "Flood modes require explicit --targets (comma-separated host:port entries)"
+                "--jitter-min ({min}) must not be greater than --jitter-max ({max})"
+            ));
+        }
+
+        if self.custom_targets.is_some() && self.targets_file.is_some() {
+            return Err(anyhow::anyhow!(
+                "Cannot specify both --targets and --targets-file, choose one"
+            ));
+        }
+
+        if matches!(self.mode, Mode::Replay) {
+            if self.replay_file.is_none() {
+                return Err(anyhow::anyhow!("Replay mode requires --replay-file"));
+            }
+            if self.custom_targets.is_some() || self.targets_file.is_some() {
+                return Err(anyhow::anyhow!(
+                    "Replay mode takes its requests from --replay-file, not --targets"
+                ));
+            }
+        } else if self.replay_file.is_some() {
+            return Err(anyhow::anyhow!("--replay-file requires --mode replay"));
+        }
+
+        if self.mode.uses_socket_targets()
+            && self.custom_targets.is_none()
+            && self.targets_file.is_none()
+        {
+            return Err(anyhow::anyhow!(
+                "Flood and connect modes require explicit --targets or --targets-file (host:port entries)"
+            ));
+        }
+
+        if self.socks_user.is_some() != self.socks_pass.is_some() {
+            return Err(anyhow::anyhow!(
+                "--socks-user and --socks-pass must be provided together"
+            ));
+        }
+
+        if self.socks_user.is_some() && self.inbound != InboundProtocol::Socks {
+            return Err(anyhow::anyhow!(
+                "--socks-user/--socks-pass require --inbound socks"
+            ));
+        }
+
+        if let Some(listen) = self.listen
+            && !listen.is_loopback()
+            && self.socks_user.is_none()
+        {
+            return Err(anyhow::anyhow!(
+                "--listen {listen} makes the inbounds reachable from other machines; set \
+                 --socks-user/--socks-pass so they are not an open proxy"
+            ));
+        }
+
+        if self.inbound == InboundProtocol::Http && self.mode.uses_socket_targets() {
+            return Err(anyhow::anyhow!(
+                "--inbound http is only supported in download mode; {:?} requires SOCKS5",
+                self.mode
             ));
         }
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn merged(cli: &[&str], file: &str) -> Args {
+        let matches = Args::command().try_get_matches_from(cli).unwrap();
+        let run_config: RunConfig = toml::from_str(file).unwrap();
+        Args::from_parts(&matches, Some(run_config)).unwrap()
+    }
+
+    #[test]
+    fn test_run_config_fills_unset_flags() {
+        let args = merged(
+            &["herscat"],
+            r#"
+                url = "vless://uuid@example.com:443"
+                mode = "tcp-flood"
+                targets = "example.com:443"
+                instances = 3
+                ports = [10800, 10810]
+            "#,
+        );
+
+        assert_eq!(args.url.as_deref(), Some("vless://uuid@example.com:443"));
+        assert!(matches!(args.mode, Mode::TcpFlood));
+        assert_eq!(args.custom_targets.as_deref(), Some("example.com:443"));
+        assert_eq!(args.xray_instances, 3);
+        assert_eq!(args.ports, Some(vec![10800, 10810]));
+        assert_eq!(args.concurrency, 50);
+    }
+
+    #[test]
+    fn test_concurrency_default_depends_on_mode() {
+        assert_eq!(merged(&["herscat"], "").concurrency, 200);
+        assert_eq!(merged(&["herscat", "-m", "udp-flood"], "").concurrency, 20);
+        let explicit = merged(&["herscat", "-m", "udp-flood", "-c", "200"], "");
+        assert_eq!(explicit.concurrency, 200);
+        let from_file = merged(&["herscat"], "mode = \"udp-flood\"\nconcurrency = 5");
+        assert_eq!(from_file.concurrency, 5);
+    }
+
+    #[test]
+    fn test_cli_flags_override_run_config() {
+        let args = merged(
+            &["herscat", "--concurrency", "50", "-m", "connect"],
+            r#"
+                concurrency = 500
+                mode = "udp-flood"
+                duration = 60
+            "#,
+        );
+
+        assert_eq!(args.concurrency, 50);
+        assert!(matches!(args.mode, Mode::Connect));
+        assert_eq!(args.duration, 60);
+    }
+
+    #[test]
+    fn test_explicit_verbosity_outranks_quiet() {
+        let level = |flags: &[&str]| {
+            let argv = ["herscat", "--url", "vless://x"].iter().chain(flags);
+            Args::try_parse_from(argv).unwrap().log_level()
+        };
+        assert_eq!(level(&[]), "warn");
+        assert_eq!(level(&["-q"]), "error");
+        assert_eq!(level(&["-q", "-v"]), "info");
+        assert_eq!(level(&["-q", "--debug"]), "debug");
+    }
+
+    #[test]
+    fn test_parse_byte_size() {
+        assert_eq!(parse_byte_size("4096"), Ok(4096));
+        assert_eq!(parse_byte_size("100MB"), Ok(100 * 1024 * 1024));
+        assert_eq!(parse_byte_size("2gb"), Ok(2 * 1024 * 1024 * 1024));
+        assert_eq!(parse_byte_size("1.5 KiB"), Ok(1536));
+        assert!(parse_byte_size("10 parsecs").is_err());
+        assert!(parse_byte_size("MB").is_err());
+
+        let run_config: RunConfig = toml::from_str(r#"max-bytes = "1GB""#).unwrap();
+        assert_eq!(run_config.max_bytes, Some(1024 * 1024 * 1024));
+    }
+
+    #[test]
+    fn test_run_config_rejects_unknown_keys() {
+        assert!(toml::from_str::<RunConfig>("concurency = 10").is_err());
+    }
+
+    #[test]
+    fn test_udp_packet_size_limit_is_mode_aware() {
+        let parse = |extra: &[&str]| {
+            let mut argv = vec!["herscat", "--url", "vless://x", "--targets", "1.1.1.1:53"];
+            argv.extend_from_slice(extra);
+            Args::try_parse_from(argv).unwrap()
+        };
+
+        let too_large = parse(&["--mode", "udp-flood", "--packet-size", "65500"]);
+        let err = too_large.validate().unwrap_err().to_string();
+        assert!(err.contains(&MAX_UDP_PACKET_SIZE.to_string()), "{err}");
+
+        let max = MAX_UDP_PACKET_SIZE.to_string();
+        let at_limit = parse(&["--mode", "udp-flood", "--packet-size", &max]);
+        assert!(at_limit.validate().is_ok());
+        let tcp = parse(&["--mode", "tcp-flood", "--packet-size", "65500"]);
+        assert!(tcp.validate().is_ok());
+    }
+
+    #[test]
+    fn test_ema_alpha_range() {
+        let valid = |alpha: &str| {
+            let argv = ["herscat", "--url", "vless://x", "--ema-alpha", alpha];
+            Args::try_parse_from(argv).unwrap().validate().is_ok()
+        };
+        assert!(valid("1.0"));
+        assert!(valid("0.05"));
+        assert!(!valid("0"));
+        assert!(!valid("1.5"));
+        assert!(!valid("NaN"));
+    }
+
+    #[test]
+    fn test_hold_ms_needs_tcp_connect_mode() {
+        let validate = |mode: &str| {
+            Args::try_parse_from([
+                "herscat",
+                "--url",
+                "vless://x",
+                "--mode",
+                mode,
+                "--targets",
+                "example.com:443",
+                "--hold-ms",
+                "500",
+            ])
+            .unwrap()
+            .validate()
+        };
+        assert!(validate("tcp-connect").is_ok());
+        let err = validate("tcp-flood").unwrap_err();
+        assert!(err.to_string().contains("tcp-connect"));
+    }
+
+    #[test]
+    fn test_udp_options_need_udp_flood_mode() {
+        let validate = |extra: &[&str]| {
+            let mut argv = vec![
+                "herscat",
+                "--url",
+                "vless://x",
+                "--targets",
+                "example.com:53",
+            ];
+            argv.extend_from_slice(extra);
+            Args::try_parse_from(argv).unwrap().validate()
+        };
+        assert!(validate(&["--mode", "udp-flood", "--udp-reassociate"]).is_ok());
+        let err = validate(&["--mode", "tcp-flood", "--udp-reassociate"]).unwrap_err();
+        assert!(err.to_string().contains("udp-flood"));
+        let err = validate(&["--mode", "udp-flood", "--udp-reassociate", "-P", "10"]).unwrap_err();
+        assert!(err.to_string().contains("--packets-per-conn"));
+        assert!(validate(&["--mode", "udp-flood", "--udp-local-port", "40000"]).is_ok());
+        let err = validate(&["--mode", "connect", "--udp-local-port", "40000"]).unwrap_err();
+        assert!(err.to_string().contains("--udp-local-port"));
+    }
+
+    #[test]
+    fn test_non_loopback_listen_needs_auth() {
+        let validate = |extra: &[&str]| {
+            let mut argv = vec!["herscat", "--url", "vless://x"];
+            argv.extend_from_slice(extra);
+            Args::try_parse_from(argv).unwrap().validate()
+        };
+        assert!(validate(&["--listen", "127.0.0.2"]).is_ok());
+        assert!(validate(&["--listen", "::1"]).is_ok());
+        let err = validate(&["--listen", "0.0.0.0"]).unwrap_err();
+        assert!(err.to_string().contains("--socks-user"));
+        let auth = ["--socks-user", "cat", "--socks-pass", "meow"];
+        assert!(validate(&[&["--listen", "0.0.0.0"], &auth[..]].concat()).is_ok());
+    }
+
+    #[test]
+    fn test_max_bytes_needs_a_mode_that_moves_data() {
+        let validate = |extra: &[&str]| {
+            let mut argv = vec!["herscat", "--url", "vless://x", "--max-bytes", "1GB"];
+            argv.extend_from_slice(extra);
+            Args::try_parse_from(argv).unwrap().validate()
+        };
+        assert!(validate(&[]).is_ok());
+        assert!(validate(&["--mode", "tcp-flood", "--targets", "example.com:80"]).is_ok());
+        for mode in ["connect", "tcp-connect"] {
+            let err = validate(&["--mode", mode, "--targets", "example.com:80"]).unwrap_err();
+            assert!(err.to_string().contains("--max-bytes"));
+        }
+    }
+
+    #[test]
+    fn test_requests_in_flight_needs_download_mode() {
+        let validate = |extra: &[&str]| {
+            let mut argv = vec!["herscat", "--url", "vless://x"];
+            argv.extend_from_slice(extra);
+            Args::try_parse_from(argv).unwrap().validate()
+        };
+        assert!(validate(&["--requests-in-flight", "4"]).is_ok());
+        assert!(validate(&["--requests-in-flight", "0"]).is_err());
+        let err = validate(&["--mode", "replay", "--requests-in-flight", "4"]).unwrap_err();
+        assert!(err.to_string().contains("download mode"));
+    }
+
+    #[test]
+    fn test_chunk_bytes_needs_download_mode() {
+        let validate = |extra: &[&str]| {
+            let mut argv = vec!["herscat", "--url", "vless://x", "--chunk-bytes", "10MB"];
+            argv.extend_from_slice(extra);
+            Args::try_parse_from(argv).unwrap().validate()
+        };
+        assert!(validate(&[]).is_ok());
+        let err = validate(&["--mode", "tcp-flood", "--targets", "example.com:80"]).unwrap_err();
+        assert!(err.to_string().contains("--chunk-bytes"));
+    }
+
+    #[test]
+    fn test_connect_only_first_skips_connect_mode() {
+        let parse = |mode: &str| {
+            Args::try_parse_from([
+                "herscat",
+                "--url",
+                "vless://x",
+                "--mode",
+                mode,
+                "--targets",
+                "example.com:443",
+                "--connect-only-first",
+            ])
+            .unwrap()
+        };
+        assert!(parse("tcp-flood").validate().is_ok());
+        let err = parse("connect").validate().unwrap_err();
+        assert!(err.to_string().contains("connect mode"));
+    }
+
+    #[test]
+    fn test_no_restart_rejects_restarting_options() {
+        let validate = |extra: &[&str]| {
+            let mut argv = vec!["herscat", "--url", "vless://x", "--no-restart"];
+            argv.extend_from_slice(extra);
+            Args::try_parse_from(argv).unwrap().validate()
+        };
+        assert!(validate(&[]).is_ok());
+        let err = validate(&["--monitor-probe"]).unwrap_err();
+        assert!(err.to_string().contains("--monitor-probe"));
+        assert!(validate(&["--stall-restart", "10"]).is_err());
+    }
+}
@@ -0,0 +1,122 @@
+//! Pre-flight check of `RLIMIT_NOFILE` against the sockets a run is going to open.
+
+use crate::cli::Mode;
+use anyhow::Result;
+#[cfg(unix)]
+use anyhow::{Context, anyhow};
+#[cfg(unix)]
+use rlimit::Resource;
+
+/// File descriptors the process needs regardless of concurrency (stdio, log files, xray pipes).
+const BASELINE_FDS: u64 = 64;
+
+/// Rough upper bound of descriptors herscat and each xray-core child will hold at once.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OpenFilesEstimate {
+    /// herscat itself: one socket per worker (two for UDP associations) plus idle pooled connections
+    pub herscat: u64,
+    /// One xray-core instance: an inbound and an outbound socket per worker
+    pub per_instance: u64,
+}
+
+impl OpenFilesEstimate {
+    pub fn new(mode: Mode, instances: usize, concurrency: usize, pool_size: usize) -> Self {
+        let per_worker = match mode {
+            Mode::UdpFlood => 2,
+            _ => 1,
+        };
+        let pooled = match mode {
+            Mode::Download | Mode::Replay => pool_size as u64,
+            _ => 0,
+        };
+        let instances = instances as u64;
+        let concurrency = concurrency as u64;
+        Self {
+            herscat: instances * (concurrency * per_worker + pooled) + BASELINE_FDS,
+            per_instance: concurrency * 2 + BASELINE_FDS,
+        }
+    }
+
+    /// xray-core inherits the limit, so the larger of the two needs has to fit.
+    pub fn required(&self) -> u64 {
+        self.herscat.max(self.per_instance)
+    }
+
+    /// `required` rounded up to the next multiple of 1024, as suggested for `ulimit -n`.
+    pub fn suggested(&self) -> u64 {
+        self.required().div_ceil(1024) * 1024
+    }
+}
+
+/// Compares the soft `RLIMIT_NOFILE` with `estimate`, first raising it to `raise_to`
+/// (capped at the hard limit) when `--max-open-files` is given.
+///
+/// Warns when the limit is below the estimate and fails when it cannot even hold one socket per
+/// worker, since such a run only produces "too many open files" errors.
+#[cfg(unix)]
+pub fn check_open_files(
+    estimate: OpenFilesEstimate,
+    workers: u64,
+    raise_to: Option<u64>,
+) -> Result<()> {
+    let (mut soft, hard) =
+        rlimit::getrlimit(Resource::NOFILE).context("Failed to read the open file limit")?;
+
+    if let Some(target) = raise_to
+        && target > soft
+    {
+        let target = target.min(hard);
+        rlimit::setrlimit(Resource::NOFILE, target, hard)
+            .with_context(|| format!("Failed to raise the open file limit to {target}"))?;
+        log::info!("Raised the open file limit from {soft} to {target} (hard limit {hard})");
+        soft = target;
+    }
+
+    let required = estimate.required();
+    if soft >= required {
+        log::debug!("Open file limit {soft} covers the estimated {required} descriptors");
+        return Ok(());
+    }
+
+    let suggested = estimate.suggested();
+    if soft < workers {
+        return Err(anyhow!(
+            "Open file limit {soft} is below the {workers} workers of this run; \
+             raise it with `ulimit -n {suggested}` or --max-open-files {suggested}"
+        ));
+    }
+    log::warn!(
+        "Open file limit {soft} is below the estimated {required} descriptors for this run; \
+         expect \"too many open files\" errors or raise it with `ulimit -n {suggested}` \
+         or --max-open-files {suggested}"
+    );
+    Ok(())
+}
+
+/// There is no `RLIMIT_NOFILE` outside unix, so there is nothing to check or raise.
+#[cfg(not(unix))]
+pub fn check_open_files(
+    _estimate: OpenFilesEstimate,
+    _workers: u64,
+    _raise_to: Option<u64>,
+) -> Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_open_files_estimate_depends_on_mode() {
+        let download = OpenFilesEstimate::new(Mode::Download, 5, 200, 10);
+        assert_eq!(download.herscat, 5 * 210 + BASELINE_FDS);
+        assert_eq!(download.per_instance, 400 + BASELINE_FDS);
+        assert_eq!(download.required(), 1114);
+        assert_eq!(download.suggested(), 2048);
+
+        let udp = OpenFilesEstimate::new(Mode::UdpFlood, 5, 20, 10);
+        assert_eq!(udp.herscat, 5 * 40 + BASELINE_FDS);
+        assert_eq!(udp.suggested(), 1024);
+    }
+}
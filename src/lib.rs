@@ -0,0 +1,33 @@
+//! herscat as a library: launch xray-core instances from proxy links and drive
+//! the download/flood stressors programmatically.
+//!
+//! The `herscat` binary is a thin CLI wrapper over this crate.
+
+pub mod clash;
+pub mod cli;
+pub mod config;
+pub mod fdlimit;
+pub mod parser;
+pub mod process;
+pub mod report;
+pub mod stressor;
+pub mod tui;
+
+pub use clash::parse_clash_config;
+pub use cli::Mode;
+pub use config::ConfigOptions;
+pub use parser::{ProxyConfig, ProxyParseError, filter_by_name, parse_proxy_list, parse_proxy_url};
+pub use process::{PortAllocation, ProcessManager};
+pub use stressor::{StressConfig, StressRunner, StressStats, load_targets_file, resolve_targets};
+
+/// Runs a stress test to completion and returns the final statistics.
+///
+/// `config.proxy_ports` must point at already running SOCKS5 inbounds, e.g. the
+/// ports returned by [`ProcessManager::start_instances`]. The call returns once
+/// the configured duration or request limit is reached; without either bound it
+/// runs until the surrounding task is cancelled.
+pub async fn run_stress_test(config: StressConfig) -> anyhow::Result<StressStats> {
+    let runner = StressRunner::new(config)?;
+    runner.run().await?;
+    Ok(runner.get_current_stats())
+}
@@ -1,45 +1,72 @@
-mod cli;
-mod config;
-mod parser;
-mod process;
-mod stressor;
-
 use anyhow::{Context, Result};
-use clap::{CommandFactory, Parser};
+use clap::CommandFactory;
 use clap_complete::{Generator, generate};
 use colored::*;
+use futures::future::join_all;
 use std::fs;
-use std::time::Duration;
+use std::io::{IsTerminal, Write};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
 use tokio::signal;
-
-use cli::{Args, Commands};
-use parser::{ProxyConfig, parse_proxy_list, parse_proxy_url};
-use process::ProcessManager;
-use stressor::{StressConfig, StressRunner, resolve_targets};
+use url::Url;
+
+use herscat::clash::parse_clash_config;
+use herscat::cli::{Args, Commands, ConcurrencyMode, InstanceMode, LogFormat, Mode};
+use herscat::config::{ConfigOptions, InboundAuth, KNOWN_FINGERPRINTS, is_known_fingerprint};
+use herscat::fdlimit::{OpenFilesEstimate, check_open_files};
+use herscat::parser::{
+    ProxyConfig, filter_by_name, parse_proxy_list, parse_proxy_url, select_by_index,
+};
+use herscat::process::{PortAllocation, ProcessManager};
+use herscat::report::{ReportSpec, RunInfo, RunOutcome};
+use herscat::stressor::{
+    AutoConcurrency, DEFAULT_HTTP_TARGETS, FailureKind, GIBIBYTE, LifetimeStats, MEBIBYTE,
+    StallRestart, StressConfig, StressRunner, TcpTemplate, check_target_ranges, fetch_once,
+    jitter_range, load_replay_file, load_targets_file, resolve_targets,
+};
+use herscat::tui::{Dashboard, LogBuffer};
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    let args = Args::parse();
+    let args = Args::load()?;
 
-    if let Some(cmd) = args.cmd {
+    if let Some(cmd) = &args.cmd {
         match cmd {
             Commands::Completions { shell } => {
-                print_completions(shell, &mut Args::command());
+                print_completions(*shell, &mut Args::command());
+                return Ok(());
+            }
+            Commands::Targets => {
+                let mut stdout = std::io::stdout().lock();
+                for target in DEFAULT_HTTP_TARGETS {
+                    // Stop quietly when piped into e.g. `head`.
+                    if writeln!(stdout, "{target}").is_err() {
+                        break;
+                    }
+                }
                 return Ok(());
             }
+            Commands::Check { .. } => {}
         }
     }
 
-    let log_level = match (args.debug, args.verbose) {
-        (true, _) => "debug",
-        (false, true) => "info",
-        _ => "warn",
-    };
+    let log_level = args.log_level();
 
-    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or(log_level)).init();
+    let logs = LogBuffer::default();
+    init_logger(log_level, args.log_format, args.tui.then(|| logs.clone()));
     args.validate().context("Invalid command line arguments")?;
+    if args.tui && args.cmd.is_none() && !std::io::stdout().is_terminal() {
+        return Err(anyhow::anyhow!("--tui needs stdout to be a terminal"));
+    }
 
-    print_banner();
+    if !args.quiet {
+        print_banner();
+    }
+
+    if let Some(Commands::Check { target, timeout }) = &args.cmd {
+        return run_check(&args, target, Duration::from_secs(*timeout)).await;
+    }
 
     let proxy_configs = load_proxy_configs(&args)
         .await
@@ -61,9 +88,68 @@ async fn main() -> Result<()> {
             .count()
     );
 
-    let process_manager = ProcessManager::new().context("Failed to initialize process manager")?;
+    let targets = match &args.targets_file {
+        Some(path) => load_targets_file(path, args.mode),
+        None => resolve_targets(args.mode, args.custom_targets.as_deref()),
+    }
+    .context("Failed to prepare targets for selected mode")?;
+    let replay = match &args.replay_file {
+        Some(path) => load_replay_file(path).context("Failed to load replay requests")?,
+        None => Vec::new(),
+    };
+    let tcp_template = args
+        .tcp_template
+        .as_deref()
+        .map(|path| TcpTemplate::load(path, args.tcp_read_until.as_deref(), args.tcp_read_bytes))
+        .transpose()
+        .context("Failed to load --tcp-template")?;
+    if args.mode.is_flood() {
+        check_target_ranges(&targets, args.allow_private)
+            .await
+            .context("Unsafe flood targets")?;
+    }
+
+    let instances = match (&args.ports, args.instance_mode) {
+        (Some(ports), _) => ports.len(),
+        (None, InstanceMode::OnePerProxy) => proxy_configs.len(),
+        (None, _) => args.xray_instances,
+    };
+    let instance_workers = args
+        .concurrency_mode
+        .instance_workers(args.concurrency, instances, 0);
+    // Each overlapping download of a worker holds its own connection.
+    let instance_connections = instance_workers * args.requests_in_flight;
+    let estimate =
+        OpenFilesEstimate::new(args.mode, instances, instance_connections, args.pool_size);
+    check_open_files(
+        estimate,
+        (instances * instance_workers) as u64,
+        args.max_open_files,
+    )
+    .context("Open file limit is too low")?;
+
+    let inbound_auth = inbound_auth(&args);
+    let config_options = config_options(&args);
+    let proxy_host = config_options.proxy_host();
+    if let Some(upstream) = &config_options.upstream_proxy {
+        log::info!("Every outbound dials its server through upstream proxy {upstream}");
+    }
+    let process_manager = ProcessManager::new(config_options)
+        .context("Failed to initialize process manager")?
+        .with_launch_stagger(Duration::from_millis(args.launch_stagger))
+        .with_require_all(args.require_all_instances)
+        .with_monitor_probe(args.monitor_probe)
+        .with_restart(!args.no_restart)
+        .with_seed(args.seed);
+    let port_allocation = match &args.ports {
+        Some(ports) => PortAllocation::Explicit(ports.clone()),
+        None => PortAllocation::Sequential {
+            base_port: args.base_port,
+            instances: args.xray_instances,
+        },
+    };
     let proxy_ports = process_manager
-        .start_instances(&proxy_configs, args.base_port, args.xray_instances)
+        .start_instances(&proxy_configs, &port_allocation, args.instance_mode)
         .await
         .context("Failed to start xray-core instances")?;
 
@@ -79,48 +165,142 @@ async fn main() -> Result<()> {
         proxy_ports
     );
 
+    if args.concurrency_mode == ConcurrencyMode::Total && args.concurrency < proxy_ports.len() {
+        log::warn!(
+            "--concurrency {} is split across {} instances, so {} of them get no workers",
+            args.concurrency,
+            proxy_ports.len(),
+            proxy_ports.len() - args.concurrency
+        );
+    }
+
     process_manager.start_monitor(Duration::from_secs(2));
 
     tokio::time::sleep(Duration::from_secs(3)).await;
     log::info!("Monitor started, proceeding with stress test...");
 
-    let targets = resolve_targets(args.mode, args.custom_targets.as_deref())
-        .context("Failed to prepare targets for selected mode")?;
-
     let stress_config = StressConfig {
         mode: args.mode,
         targets,
+        replay,
         concurrency: args.concurrency,
+        concurrency_mode: args.concurrency_mode,
+        ema_alpha: args.ema_alpha,
+        pool_size: args.pool_size,
+        requests_in_flight: args.requests_in_flight,
+        verify_certs: args.verify_certs,
+        tls_timing: args.tls_timing,
+        follow_redirects: !args.no_redirects,
+        fail_on_status: args.fail_on_status,
+        chunk_bytes: args.chunk_bytes,
+        retries: args.retries,
+        check_length: args.check_length,
+        accept_encoding: args.accept_encoding,
         duration: (args.duration > 0).then(|| Duration::from_secs(args.duration)),
+        deadline: args.deadline.map(Duration::from_secs),
+        ramp_down: args.ramp_down.map(Duration::from_secs),
+        max_requests: args.max_requests,
+        max_bytes: args.max_bytes,
+        warmup: args.warmup.filter(|&s| s > 0).map(Duration::from_secs),
+        connect_only_first: args.connect_only_first,
         proxy_ports: proxy_ports.clone(),
+        proxy_host,
+        inbound: args.inbound,
+        inbound_auth,
         packet_size: args.packet_size as usize,
+        payload_pattern: args.payload_pattern,
+        tcp_template,
         packet_rate: args.packet_rate,
+        total_pps: args.total_pps,
         packets_per_connection: (args.packets_per_connection > 0)
             .then_some(args.packets_per_connection),
+        conn_lifetime: args.conn_lifetime.map(Duration::from_secs),
+        hold: args.hold_ms.map(Duration::from_millis),
+        udp_reassociate: args.udp_reassociate,
+        udp_local_port: args.udp_local_port,
+        local_address: args.local_address,
+        jitter: jitter_range(args.jitter_min, args.jitter_max),
+        shuffle_targets: args.shuffle_targets,
+        seed: args.seed,
+        auto_concurrency: args.auto_concurrency.then(|| AutoConcurrency {
+            max_failure_rate: args.auto_threshold,
+            interval: Duration::from_secs(args.stats_interval),
+        }),
+        stall_restart: args.stall_restart.map(|min_mbps| StallRestart {
+            min_mbps,
+            intervals: args.stall_intervals,
+        }),
+        csv_log: args.csv.clone(),
+        state_file: args.state_file.clone(),
+        progress_bar: std::io::stdout().is_terminal()
+            && !args.tui
+            && !args.quiet
+            && args.log_format != LogFormat::Json,
     };
 
     let stress_runner =
         StressRunner::new(stress_config.clone()).context("Failed to initialize stress runner")?;
+    if let Some(lifetime) = stress_runner.lifetime_stats()
+        && lifetime.runs > 1
+    {
+        log::info!(
+            "Resuming lifetime totals from {} earlier run(s) ({:.2} GiB)",
+            lifetime.runs - 1,
+            lifetime.bytes_transferred as f64 / GIBIBYTE
+        );
+    }
 
     stress_runner
         .start_stats_reporter(Duration::from_secs(args.stats_interval))
-        .await;
+        .await
+        .context("Failed to start statistics reporter")?;
+
+    if stress_config.stall_restart.is_some() {
+        let process_manager = process_manager.clone();
+        let stress_runner = stress_runner.clone();
+        tokio::spawn(async move {
+            loop {
+                stress_runner.stalled().await;
+                let restarted = process_manager.recycle_all().await;
+                log::warn!("Stall recycle restarted {restarted} xray-core instances");
+            }
+        });
+    }
 
-    let process_manager_clone = process_manager.clone();
+    if args.no_restart {
+        let process_manager = process_manager.clone();
+        let stress_runner = stress_runner.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(Duration::from_secs(2)).await;
+                stress_runner.retain_ports(&process_manager.live_ports().await);
+            }
+        });
+    }
+
+    let report = args.report.clone().map(|path| {
+        let workers = stress_runner.effective_worker_count();
+        let info = RunInfo::new(&stress_config, workers, proxy_configs.len());
+        ReportSpec::new(path, info, args.report_configs)
+    });
+
+    // The handler only stops the workers; stats, state and the report are written once below.
+    let interrupted = Arc::new(AtomicBool::new(false));
     let stress_runner_clone = stress_runner.clone();
+    let interrupted_clone = Arc::clone(&interrupted);
+    let quiet = args.quiet;
 
     tokio::spawn(async move {
         match signal::ctrl_c().await {
             Ok(()) => {
-                println!(
-                    "\n{}",
-                    "Received Ctrl+C, shutting down gracefully...".yellow()
-                );
-                print_stats(&stress_runner_clone);
-                if let Err(e) = process_manager_clone.terminate_all().await {
-                    log::error!("Error during shutdown: {e}");
+                if !quiet {
+                    println!(
+                        "\n{}",
+                        "Received Ctrl+C, shutting down gracefully...".yellow()
+                    );
                 }
-                std::process::exit(0);
+                interrupted_clone.store(true, Ordering::Relaxed);
+                stress_runner_clone.shutdown();
             }
             Err(err) => {
                 log::error!("Unable to listen for shutdown signal: {err}");
@@ -128,54 +308,239 @@ async fn main() -> Result<()> {
         }
     });
 
-    println!(
-        "\n{} Starting stress test with total concurrency = {} across {} xray instances",
-        "[herscat]".red().bold(),
-        args.concurrency.to_string().cyan(),
-        proxy_ports.len().to_string().cyan(),
-    );
+    if !args.quiet {
+        print_run_plan(&stress_runner, &stress_config);
+    }
 
-    if let Some(duration) = stress_config.duration {
-        println!(
-            "{} Test will run for {} seconds",
-            "[herscat]".red().bold(),
-            duration.as_secs().to_string().cyan()
-        );
+    let dashboard = args
+        .tui
+        .then(|| Dashboard::spawn(stress_runner.clone(), process_manager.clone(), logs))
+        .transpose()?;
+    let result = stress_runner.run().await;
+    if let Some(dashboard) = dashboard {
+        dashboard.close().await?;
+    }
+    if let Err(err) = result {
+        stress_runner.save_state();
+        if let Some(report) = &report {
+            report
+                .write(RunOutcome::Failed, &stress_runner, &process_manager)
+                .await;
+        }
+        return Err(err.context("Stress test failed"));
+    }
+
+    let interrupted = interrupted.load(Ordering::Relaxed);
+    let outcome = if interrupted {
+        RunOutcome::Interrupted
     } else {
+        RunOutcome::Completed
+    };
+    print_stats(&stress_runner);
+    stress_runner.save_state();
+    if let Some(report) = &report {
+        report
+            .write(outcome, &stress_runner, &process_manager)
+            .await;
+    }
+
+    process_manager
+        .terminate_all()
+        .await
+        .context("Failed to cleanup xray processes")?;
+
+    if interrupted {
+        return Ok(());
+    }
+
+    if let Some(threshold) = args.fail_over {
+        let failure_rate = stress_runner.get_current_stats().failure_rate();
+        if failure_rate > threshold {
+            println!(
+                "\n{} Failure rate {}% exceeds the --fail-over threshold of {}%",
+                "[herscat]".red().bold(),
+                format!("{failure_rate:.2}").red(),
+                format!("{threshold:.2}").cyan()
+            );
+            std::process::exit(1);
+        }
+    }
+
+    if !args.quiet {
         println!(
-            "{} Test will run indefinitely (Ctrl+C to stop)",
+            "\n{} Test completed successfully!",
             "[herscat]".red().bold()
         );
     }
 
-    stress_runner.run().await.context("Stress test failed")?;
+    Ok(())
+}
 
-    print_stats(&stress_runner);
+fn inbound_auth(args: &Args) -> Option<InboundAuth> {
+    match (&args.socks_user, &args.socks_pass) {
+        (Some(user), Some(pass)) => Some(InboundAuth {
+            user: user.clone(),
+            pass: pass.clone(),
+        }),
+        _ => None,
+    }
+}
 
-    process_manager
-        .terminate_all()
+fn config_options(args: &Args) -> ConfigOptions {
+    if !is_known_fingerprint(&args.default_fingerprint) {
+        log::warn!(
+            "--default-fingerprint {} is not a uTLS fingerprint known to xray-core ({})",
+            args.default_fingerprint,
+            KNOWN_FINGERPRINTS.join(", ")
+        );
+    }
+
+    ConfigOptions {
+        mux_concurrency: args.mux,
+        inbound: args.inbound,
+        log_level: args.xray_loglevel,
+        auth: inbound_auth(args),
+        udp: matches!(args.mode, Mode::UdpFlood),
+        sni: args.sni.clone(),
+        listen: args.listen,
+        upstream_proxy: args.upstream_proxy.clone(),
+        default_fingerprint: Some(args.default_fingerprint.clone()),
+        server_family: args.server_family,
+        send_through: args.local_address,
+    }
+}
+
+/// `herscat check`: one instance and one small download per proxy, then a PASS/FAIL table.
+async fn run_check(args: &Args, target: &str, timeout: Duration) -> Result<()> {
+    let url = Url::parse(target).with_context(|| format!("Invalid check target {target}"))?;
+    let proxy_configs = load_proxy_configs(args)
         .await
-        .context("Failed to cleanup xray processes")?;
+        .context("Failed to load proxy configurations")?;
+    let config_options = config_options(args);
+    let proxy_host = config_options.proxy_host();
+    let process_manager = ProcessManager::new(config_options)
+        .context("Failed to initialize process manager")?
+        .with_launch_stagger(Duration::from_millis(args.launch_stagger));
+
+    // One instance per list entry, so every result maps back to its proxy.
+    let mut ports = Vec::with_capacity(proxy_configs.len());
+    let mut base_port = args.base_port;
+    for (idx, proxy) in proxy_configs.iter().enumerate() {
+        let allocation = PortAllocation::Sequential {
+            base_port,
+            instances: 1,
+        };
+        let single = std::slice::from_ref(proxy);
+        let started = process_manager
+            .start_instances(single, &allocation, InstanceMode::Cycle)
+            .await;
+        match started {
+            Ok(started) => {
+                base_port = started[0].saturating_add(1);
+                ports.push(Some(started[0]));
+            }
+            Err(e) => {
+                log::warn!("Failed to start xray-core for proxy {idx}: {e:#}");
+                ports.push(None);
+            }
+        }
+    }
+
+    tokio::time::sleep(Duration::from_secs(3)).await;
+
+    let checks = ports.iter().map(|port| {
+        let url = &url;
+        async move {
+            let port = (*port).ok_or("xray failed to start")?;
+            let config = StressConfig {
+                proxy_ports: vec![port],
+                proxy_host,
+                inbound: args.inbound,
+                inbound_auth: inbound_auth(args),
+                verify_certs: args.verify_certs,
+                follow_redirects: !args.no_redirects,
+                accept_encoding: args.accept_encoding,
+                local_address: args.local_address,
+                ..StressConfig::default()
+            };
+            let started = Instant::now();
+            match tokio::time::timeout(timeout, fetch_once(&config, url)).await {
+                Ok(Ok(bytes)) => Ok((started.elapsed(), bytes)),
+                Ok(Err(kind)) => Err(kind.as_str()),
+                Err(_) => Err(FailureKind::Timeout.as_str()),
+            }
+        }
+    });
+    let results = join_all(checks).await;
+
+    if let Err(e) = process_manager.terminate_all().await {
+        log::error!("Error during shutdown: {e}");
+    }
 
+    println!("\n{} Check results for {}:", "[herscat]".red().bold(), url);
+    let mut failed = 0;
+    for (idx, (proxy, result)) in proxy_configs.iter().zip(&results).enumerate() {
+        let name = proxy
+            .display_name()
+            .unwrap_or_else(|| "unnamed".to_string());
+        match result {
+            Ok((latency, bytes)) => println!(
+                "  {} {:>4} {} | {} ms | {} bytes",
+                "PASS".green(),
+                idx,
+                name,
+                latency.as_millis().to_string().cyan(),
+                bytes
+            ),
+            Err(reason) => {
+                failed += 1;
+                println!("  {} {:>4} {} | {}", "FAIL".red(), idx, name, reason.red());
+            }
+        }
+    }
     println!(
-        "\n{} Test completed successfully!",
-        "[herscat]".red().bold()
+        "  {} of {} proxies passed",
+        (results.len() - failed).to_string().green(),
+        results.len()
     );
 
+    if failed > 0 {
+        return Err(anyhow::anyhow!(
+            "{failed} of {} proxies failed the check",
+            results.len()
+        ));
+    }
     Ok(())
 }
 
 async fn load_proxy_configs(args: &Args) -> Result<Vec<ProxyConfig>> {
-    if let Some(ref url) = args.url {
+    let mut configs = if let Some(ref url) = args.url {
         let cfg = parse_proxy_url(url).context("Failed to parse proxy URL")?;
-        Ok(vec![cfg])
+        vec![cfg]
     } else if let Some(ref list_file) = args.list {
         let content = fs::read_to_string(list_file)
             .with_context(|| format!("Failed to read proxy list file: {list_file}"))?;
-        parse_proxy_list(&content).context("Failed to parse proxy list")
+        if args.list_format.is_clash(list_file) {
+            parse_clash_config(&content).context("Failed to parse Clash config")?
+        } else {
+            parse_proxy_list(&content).context("Failed to parse proxy list")?
+        }
     } else {
         unreachable!("Either url or list should be provided (validated earlier)")
+    };
+
+    if let Some(index) = args.only_proxy {
+        configs = select_by_index(configs, index)?;
     }
+
+    if args.filter_name.is_none() && args.exclude_name.is_none() {
+        return Ok(configs);
+    }
+    filter_by_name(
+        configs,
+        args.filter_name.as_deref(),
+        args.exclude_name.as_deref(),
+    )
 }
 
 fn print_completions<G: Generator>(generator: G, cmd: &mut clap::Command) {
@@ -187,39 +552,232 @@ fn print_completions<G: Generator>(generator: G, cmd: &mut clap::Command) {
     );
 }
 
+/// Log target of the one-line `RESULT` summary, enabled at info level whatever the verbosity.
+const RESULT_LOG_TARGET: &str = "herscat::result";
+
+/// With `--tui`, records go through `logs` so the dashboard can show them while it is open.
+fn init_logger(default_level: &str, format: LogFormat, logs: Option<LogBuffer>) {
+    let mut builder =
+        env_logger::Builder::from_env(env_logger::Env::default().default_filter_or(default_level));
+    builder.filter_module(RESULT_LOG_TARGET, log::LevelFilter::Info);
+    if let Some(logs) = logs {
+        builder
+            .target(env_logger::Target::Pipe(Box::new(logs)))
+            .write_style(env_logger::WriteStyle::Never);
+    }
+    if format == LogFormat::Json {
+        builder.format(|buf, record| {
+            let line = serde_json::json!({
+                "timestamp": buf.timestamp_millis().to_string(),
+                "level": record.level().as_str(),
+                "module": record.module_path().unwrap_or_else(|| record.target()),
+                "message": record.args().to_string(),
+            });
+            writeln!(buf, "{line}")
+        });
+    }
+    builder.init();
+}
+
+/// The `[herscat]` lines describing the run before it starts; skipped with `--quiet`.
+fn print_run_plan(stress_runner: &StressRunner, stress_config: &StressConfig) {
+    let instances = stress_config.proxy_ports.len();
+    let most = stress_config.max_instance_workers();
+    // `--concurrency-mode total` can leave the last instances one worker short.
+    let per_instance = if stress_config.instance_workers(instances - 1) == most {
+        format!("{} per instance", most.to_string().cyan())
+    } else {
+        format!("up to {} per instance", most.to_string().cyan())
+    };
+    println!(
+        "\n{} Starting stress test with {} workers ({}) across {} xray instances",
+        "[herscat]".red().bold(),
+        stress_runner.effective_worker_count().to_string().cyan(),
+        per_instance,
+        instances.to_string().cyan(),
+    );
+    if stress_config.requests_in_flight > 1 {
+        println!(
+            "{} Each worker keeps {} downloads in flight, up to {} at once",
+            "[herscat]".red().bold(),
+            stress_config.requests_in_flight.to_string().cyan(),
+            (stress_runner.effective_worker_count() * stress_config.requests_in_flight)
+                .to_string()
+                .cyan()
+        );
+    }
+    if stress_config.auto_concurrency.is_some() {
+        println!(
+            "{} Auto-concurrency starts with {} of them active",
+            "[herscat]".red().bold(),
+            stress_runner.active_worker_count().to_string().cyan()
+        );
+    }
+    if stress_config.connect_only_first {
+        println!(
+            "{} Load starts once every worker has connected (--connect-only-first)",
+            "[herscat]".red().bold()
+        );
+    }
+    if let Some(total) = stress_config.total_pps
+        && let Some(interval) = stress_config.send_interval()
+    {
+        println!(
+            "{} --total-pps {} across {} workers: one send every {} ms per worker",
+            "[herscat]".red().bold(),
+            total.to_string().cyan(),
+            stress_runner.effective_worker_count(),
+            format!("{:.3}", interval.as_secs_f64() * 1000.0).cyan()
+        );
+    }
+
+    if let Some(duration) = stress_config.duration {
+        println!(
+            "{} Test will run for {} seconds",
+            "[herscat]".red().bold(),
+            duration.as_secs().to_string().cyan()
+        );
+    } else if stress_config.max_requests.is_none()
+        && stress_config.max_bytes.is_none()
+        && stress_config.deadline.is_none()
+    {
+        println!(
+            "{} Test will run indefinitely (Ctrl+C to stop)",
+            "[herscat]".red().bold()
+        );
+    }
+
+    if let Some(deadline) = stress_config.deadline {
+        println!(
+            "{} Test will be stopped after at most {} seconds",
+            "[herscat]".red().bold(),
+            deadline.as_secs().to_string().cyan()
+        );
+    }
+
+    if let Some(limit) = stress_config.max_requests {
+        println!(
+            "{} Test will stop after {} successful events",
+            "[herscat]".red().bold(),
+            limit.to_string().cyan()
+        );
+    }
+
+    if let Some(limit) = stress_config.max_bytes {
+        println!(
+            "{} Test will stop after {} MiB transferred",
+            "[herscat]".red().bold(),
+            format!("{:.2}", limit as f64 / MEBIBYTE).cyan()
+        );
+    }
+}
+
 fn print_stats(stress_runner: &StressRunner) {
     let final_stats = stress_runner.get_current_stats();
+    log::info!(
+        target: RESULT_LOG_TARGET,
+        "{}",
+        final_stats.result_line(stress_runner.mode())
+    );
     println!("\n{} Final Statistics:", "[herscat]".red().bold());
     println!(
         "  Success Events: {} | Failed Events: {}",
         final_stats.success_events.to_string().green(),
         final_stats.failure_events.to_string().red()
     );
+    let breakdown = final_stats.failure_breakdown();
+    if !breakdown.is_empty() {
+        let parts: Vec<String> = breakdown
+            .iter()
+            .map(|(kind, count)| format!("{} {}", kind.as_str(), count.to_string().red()))
+            .collect();
+        println!("  Failure Breakdown: {}", parts.join(" | "));
+    }
+    if let Some(peak) = stress_runner.peak_stable_concurrency() {
+        let peak = if peak == 0 {
+            "none (threshold exceeded from the start)".red()
+        } else {
+            format!("{peak} workers per instance").green()
+        };
+        println!("  Peak Stable Concurrency: {peak}");
+    }
+    println!(
+        "  Peak Active Connections: {} of {} workers",
+        stress_runner.peak_active_connections().to_string().cyan(),
+        stress_runner.effective_worker_count()
+    );
+    if final_stats.retries > 0 {
+        println!("  Retries: {}", final_stats.retries.to_string().yellow());
+    }
+    if let Some(warmup) = stress_runner.connect_warmup_duration() {
+        println!(
+            "  Connection Warm-up: {}s",
+            format!("{:.2}", warmup.as_secs_f64()).cyan()
+        );
+    }
+    if stress_runner.stall_recycles() > 0 {
+        println!(
+            "  Stall Recycles: {}",
+            stress_runner.stall_recycles().to_string().yellow()
+        );
+    }
+    if let Some(ramp) = stress_runner.ramp_down_summary() {
+        println!(
+            "  Ramp-down: {} of {} in-flight workers finished, {} aborted",
+            ramp.completed.to_string().green(),
+            ramp.in_flight,
+            ramp.aborted.to_string().red()
+        );
+    }
     println!(
         "  Test Duration: {}s",
         format!("{:.2}", final_stats.elapsed().as_secs_f64()).cyan()
     );
 
     match stress_runner.mode() {
-        crate::cli::Mode::Download => {
+        Mode::Download => {
             println!(
-                "  Total Traffic: {} MB",
-                format!(
-                    "{:.2}",
-                    final_stats.bytes_transferred as f64 / (1024.0 * 1024.0)
-                )
-                .cyan()
+                "  Total Traffic: {} MiB",
+                format!("{:.2}", final_stats.bytes_transferred as f64 / MEBIBYTE).cyan()
+            );
+            println!(
+                "  Average Throughput: {} MB/s ({} MiB/s)",
+                format!("{:.2}", final_stats.megabytes_per_second()).cyan(),
+                format!("{:.2}", final_stats.mebibytes_per_second()).cyan()
             );
             println!(
                 "  Average Bandwidth: {} Mbps",
+                format!("{:.2}", final_stats.megabytes_per_second() * 8.0).cyan()
+            );
+
+            if final_stats.partial_downloads > 0 {
+                println!(
+                    "  Partial Downloads: {}",
+                    final_stats.partial_downloads.to_string().red()
+                );
+            }
+
+            print_status_codes(stress_runner);
+            print_target_summaries(stress_runner);
+            print_tls_handshakes(stress_runner);
+        }
+        Mode::Replay => {
+            println!(
+                "  Total Traffic: {} MiB",
+                format!("{:.2}", final_stats.bytes_transferred as f64 / MEBIBYTE).cyan()
+            );
+            println!(
+                "  Average Requests/s: {}",
                 format!(
-                    "{:.2}",
-                    (final_stats.bytes_per_second() * 8.0) / (1000.0 * 1000.0)
+                    "{:.1}",
+                    final_stats.success_events as f64 / final_stats.elapsed().as_secs_f64()
                 )
                 .cyan()
             );
+            print_status_codes(stress_runner);
+            print_target_summaries(stress_runner);
         }
-        crate::cli::Mode::TcpFlood | crate::cli::Mode::UdpFlood => {
+        Mode::TcpFlood | Mode::UdpFlood => {
             println!(
                 "  Total Packets: {}",
                 final_stats.packets_sent.to_string().cyan()
@@ -228,16 +786,142 @@ fn print_stats(stress_runner: &StressRunner) {
                 "  Average PPS: {}",
                 format!("{:.0}", final_stats.packets_per_second()).cyan()
             );
+            if let Some(pps) = stress_runner.pps_summary() {
+                println!(
+                    "  PPS per Interval: min {} | avg {} | max {} | stddev {} ({} intervals)",
+                    format!("{:.0}", pps.min).cyan(),
+                    format!("{:.0}", pps.avg).cyan(),
+                    format!("{:.0}", pps.max).cyan(),
+                    format!("{:.1}", pps.stddev).yellow(),
+                    pps.samples
+                );
+            }
             println!(
                 "  Estimated Throughput: {} Mbps",
+                format!("{:.2}", final_stats.megabytes_per_second() * 8.0).cyan()
+            );
+        }
+        Mode::Connect | Mode::TcpConnect => {
+            println!(
+                "  Average Connects/s: {}",
                 format!(
-                    "{:.2}",
-                    (final_stats.bytes_per_second() * 8.0) / (1000.0 * 1000.0)
+                    "{:.1}",
+                    final_stats.success_events as f64 / final_stats.elapsed().as_secs_f64()
                 )
                 .cyan()
             );
+            for summary in stress_runner.latency_summaries() {
+                println!(
+                    "  {} -> avg {} ms | p99 {} ms ({} samples)",
+                    summary.target,
+                    format!("{:.1}", summary.average.as_secs_f64() * 1000.0).cyan(),
+                    format!("{:.1}", summary.p99.as_secs_f64() * 1000.0).cyan(),
+                    summary.samples
+                );
+            }
         }
     }
+
+    if let Some(lifetime) = stress_runner.lifetime_stats() {
+        print_lifetime_stats(&lifetime);
+    }
+}
+
+/// `--state-file` totals, printed after the per-run block above.
+fn print_lifetime_stats(lifetime: &LifetimeStats) {
+    println!(
+        "\n{} Lifetime Statistics across {} run(s), this one included:",
+        "[herscat]".red().bold(),
+        lifetime.runs.to_string().cyan()
+    );
+    println!(
+        "  Success Events: {} | Failed Events: {}",
+        lifetime.success_events.to_string().green(),
+        lifetime.failure_events.to_string().red()
+    );
+    if !lifetime.failures.is_empty() {
+        let parts: Vec<String> = lifetime
+            .failures
+            .iter()
+            .map(|(kind, count)| format!("{} {}", kind, count.to_string().red()))
+            .collect();
+        println!("  Failure Breakdown: {}", parts.join(" | "));
+    }
+    println!(
+        "  Run Time: {}s",
+        format!("{:.2}", lifetime.elapsed_secs).cyan()
+    );
+    println!(
+        "  Total Traffic: {} MiB",
+        format!("{:.2}", lifetime.bytes_transferred as f64 / MEBIBYTE).cyan()
+    );
+    println!(
+        "  Average Bandwidth: {} Mbps",
+        format!("{:.2}", lifetime.mbps()).cyan()
+    );
+    if lifetime.packets_sent > 0 {
+        println!(
+            "  Total Packets: {}",
+            lifetime.packets_sent.to_string().cyan()
+        );
+    }
+}
+
+fn print_tls_handshakes(stress_runner: &StressRunner) {
+    let summaries = stress_runner.tls_handshake_summaries();
+    if summaries.is_empty() {
+        return;
+    }
+    println!("  TLS Handshakes:");
+    for summary in summaries {
+        println!(
+            "    {} -> p50 {} ms | p99 {} ms ({} samples)",
+            summary.target,
+            format!("{:.1}", summary.p50.as_secs_f64() * 1000.0).cyan(),
+            format!("{:.1}", summary.p99.as_secs_f64() * 1000.0).cyan(),
+            summary.samples
+        );
+    }
+}
+
+fn print_status_codes(stress_runner: &StressRunner) {
+    let statuses: Vec<String> = stress_runner
+        .status_counts()
+        .iter()
+        .map(|(status, count)| {
+            let status = match status {
+                200..=299 => status.to_string().green(),
+                300..=399 => status.to_string().yellow(),
+                _ => status.to_string().red(),
+            };
+            format!("{status}: {count}")
+        })
+        .collect();
+    if !statuses.is_empty() {
+        println!("  Status Codes: {}", statuses.join(", "));
+    }
+}
+
+fn print_target_summaries(stress_runner: &StressRunner) {
+    let targets = stress_runner.target_summaries();
+    if !targets.is_empty() {
+        println!("  Targets (by traffic):");
+    }
+    for summary in targets {
+        let status = if summary.success == 0 {
+            "unreachable".red()
+        } else {
+            "ok".green()
+        };
+        println!(
+            "    {:>10} MiB | ok {} | failed {} | {} {}",
+            format!("{:.2}", summary.bytes as f64 / MEBIBYTE).cyan(),
+            summary.success,
+            summary.failure,
+            status,
+            summary.target
+        );
+    }
 }
 
 fn print_banner() {
@@ -1,13 +1,114 @@
-use anyhow::{Context, Result, anyhow};
+use anyhow::{Result, anyhow};
 use base64::Engine;
-use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+use base64::engine::general_purpose::{STANDARD, STANDARD_NO_PAD, URL_SAFE, URL_SAFE_NO_PAD};
 use percent_encoding::percent_decode_str;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
 use url::Url;
 
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+/// Why a proxy link could not be turned into a [`ProxyConfig`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProxyParseError {
+    /// The link was empty after trimming
+    Empty,
+    /// The link is not a well-formed URL
+    InvalidUrl {
+        protocol: &'static str,
+        source: url::ParseError,
+    },
+    /// The scheme is not one of `vless`, `trojan` or `ss`
+    UnsupportedProtocol(String),
+    /// A protocol-specific parser was handed a link with another scheme
+    WrongScheme { expected: &'static str },
+    /// A required part of the link is absent, e.g. the VLESS user ID
+    MissingField {
+        protocol: &'static str,
+        field: &'static str,
+    },
+    /// Port 0 and 1 are placeholders in many subscriptions and are skipped
+    InvalidPort(u16),
+    /// `security=` other than none, tls or reality
+    UnsupportedSecurity(String),
+    /// `type=` transport that xray-core does not know
+    UnsupportedNetwork(String),
+    /// Shadowsocks cipher outside the supported list
+    UnsupportedMethod(String),
+    /// A parameter is present but has an unusable value
+    InvalidParameter {
+        name: &'static str,
+        reason: &'static str,
+    },
+}
+
+impl fmt::Display for ProxyParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProxyParseError::Empty => write!(f, "empty proxy URL"),
+            ProxyParseError::InvalidUrl { protocol, source } => {
+                write!(f, "Failed to parse {protocol} URL: {source}")
+            }
+            ProxyParseError::UnsupportedProtocol(scheme) => {
+                write!(f, "unsupported protocol: {scheme}")
+            }
+            ProxyParseError::WrongScheme { expected } => {
+                write!(f, "Invalid URL: must start with '{expected}://'")
+            }
+            ProxyParseError::MissingField { protocol, field } => {
+                write!(f, "{protocol} URL missing {field}")
+            }
+            ProxyParseError::InvalidPort(port) => write!(f, "skipping port: {port}"),
+            ProxyParseError::UnsupportedSecurity(security) => {
+                write!(f, "Unsupported security type: {security}")
+            }
+            ProxyParseError::UnsupportedNetwork(network) => {
+                write!(f, "Unsupported network type: {network}")
+            }
+            ProxyParseError::UnsupportedMethod(method) => write!(
+                f,
+                "Unsupported Shadowsocks method: {} (supported: {})",
+                method,
+                SHADOWSOCKS_METHODS.join(", ")
+            ),
+            ProxyParseError::InvalidParameter { name, reason } => write!(f, "{name} {reason}"),
+        }
+    }
+}
+
+impl ProxyParseError {
+    /// Short label used to group failures in the proxy list summary.
+    pub fn category(&self) -> String {
+        match self {
+            ProxyParseError::Empty => "empty".to_string(),
+            ProxyParseError::InvalidUrl { .. } => "malformed URL".to_string(),
+            ProxyParseError::UnsupportedProtocol(scheme) => {
+                format!("unsupported protocol {scheme}")
+            }
+            ProxyParseError::WrongScheme { .. } => "wrong scheme".to_string(),
+            ProxyParseError::MissingField { .. } => "missing field".to_string(),
+            ProxyParseError::InvalidPort(_) => "invalid port".to_string(),
+            ProxyParseError::UnsupportedSecurity(_) => "unsupported security".to_string(),
+            ProxyParseError::UnsupportedNetwork(_) => "unsupported network".to_string(),
+            ProxyParseError::UnsupportedMethod(_) => "unsupported method".to_string(),
+            ProxyParseError::InvalidParameter { .. } => "invalid parameter".to_string(),
+        }
+    }
+}
+
+impl std::error::Error for ProxyParseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ProxyParseError::InvalidUrl { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
 pub struct VlessConfig {
+    pub name: Option<String>,
+    /// Text of the `# comment` line right above the link in a proxy list
+    pub label: Option<String>,
     pub id: String,
     pub host: String,
     pub port: u16,
@@ -23,10 +124,12 @@ pub struct VlessConfig {
     pub path: Option<String>,
     pub host_header: Option<String>,
     pub mode: Option<String>,
-    pub extra_xhttp: Option<String>,
+    /// xhttp `extra`: a JSON object of further xhttpSettings
+    pub extra_xhttp: Option<serde_json::Value>,
     pub service_name: Option<String>,
     pub packet_encoding: Option<String>,
     pub spider_x: Option<String>,
+    pub server_names: Vec<String>,
     pub reverse_tag: Option<String>,
     pub multi_mode: bool,
     pub idle_timeout: Option<i32>,
@@ -41,26 +144,29 @@ pub struct VlessConfig {
 }
 
 impl VlessConfig {
-    pub fn parse(vless_url: &str) -> Result<Self> {
+    pub fn parse(vless_url: &str) -> Result<Self, ProxyParseError> {
         if !vless_url.starts_with("vless://") {
-            return Err(anyhow!("Invalid VLESS URL: must start with 'vless://'"));
+            return Err(ProxyParseError::WrongScheme { expected: "vless" });
         }
 
-        let url = Url::parse(vless_url).context("Failed to parse VLESS URL")?;
+        let url = Url::parse(vless_url).map_err(|source| ProxyParseError::InvalidUrl {
+            protocol: "VLESS",
+            source,
+        })?;
 
         let id = url.username();
         if id.is_empty() {
-            return Err(anyhow!("VLESS URL missing user ID"));
+            return Err(missing("VLESS", "user ID"));
         }
 
         let host = url
             .host_str()
-            .ok_or_else(|| anyhow!("VLESS URL missing host"))?
+            .ok_or_else(|| missing("VLESS", "host"))?
             .to_string();
 
         let port = url.port().unwrap_or(443);
         if port == 0 || port == 1 {
-            return Err(anyhow!("skipping port: {}", port));
+            return Err(ProxyParseError::InvalidPort(port));
         }
 
         let params: HashMap<String, String> = url
@@ -78,7 +184,19 @@ impl VlessConfig {
             .or_else(|| params.get("packetencoding"))
             .cloned();
 
-        let spider_x = params.get("spiderX").or_else(|| params.get("spx")).cloned();
+        let spider_x = params.get("spx").or_else(|| params.get("spiderX")).cloned();
+
+        let server_names = params
+            .get("realityServerNames")
+            .or_else(|| params.get("serverNames"))
+            .map(|s| {
+                s.split(',')
+                    .map(str::trim)
+                    .filter(|name| !name.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default();
 
         let reverse_tag = params
             .get("reverse")
@@ -98,6 +216,11 @@ impl VlessConfig {
         let padding = params.get("padding").cloned();
 
         let mut config = VlessConfig {
+            name: url
+                .fragment()
+                .filter(|f| !f.is_empty())
+                .map(percent_decode_lossy),
+            label: None,
             id: id.to_string(),
             host,
             port,
@@ -123,6 +246,7 @@ impl VlessConfig {
             service_name: None,
             packet_encoding,
             spider_x,
+            server_names,
             reverse_tag,
             multi_mode: params
                 .get("multiMode")
@@ -150,8 +274,14 @@ impl VlessConfig {
         if config.network == "xhttp" {
             config.mode = params.get("mode").cloned();
             if let Some(extra) = params.get("extra") {
-                let unquoted = extra.trim_matches('"').to_string();
-                config.extra_xhttp = Some(unquoted);
+                config.extra_xhttp = parse_xhttp_extra(extra);
+                if config.extra_xhttp.is_none() {
+                    log::warn!(
+                        "Ignoring invalid xhttp extra for {}: {}",
+                        config.host,
+                        extra
+                    );
+                }
             }
         }
 
@@ -162,67 +292,87 @@ impl VlessConfig {
         Ok(config)
     }
 
-    pub fn validate(&self) -> Result<()> {
+    pub fn validate(&self) -> Result<(), ProxyParseError> {
         if self.id.is_empty() {
-            return Err(anyhow!("VLESS config missing ID"));
+            return Err(missing("VLESS", "user ID"));
         }
 
         if self.host.is_empty() {
-            return Err(anyhow!("VLESS config missing host"));
+            return Err(missing("VLESS", "host"));
         }
 
         if self.port == 0 {
-            return Err(anyhow!("VLESS config has invalid port"));
+            return Err(ProxyParseError::InvalidPort(self.port));
         }
 
         match self.security.as_str() {
             "none" | "tls" | "reality" => {}
-            _ => return Err(anyhow!("Unsupported security type: {}", self.security)),
+            _ => {
+                return Err(ProxyParseError::UnsupportedSecurity(self.security.clone()));
+            }
         }
 
         if self.encryption.trim().is_empty() {
-            return Err(anyhow!("VLESS config missing encryption parameter"));
+            return Err(missing("VLESS", "encryption parameter"));
+        }
+        if let Some(warning) = self.encryption_warning() {
+            log::warn!("{warning}");
         }
 
         match self.network.as_str() {
             "tcp" | "ws" | "grpc" | "h2" | "xhttp" | "httpupgrade" => {}
-            _ => return Err(anyhow!("Unsupported network type: {}", self.network)),
+            _ => return Err(ProxyParseError::UnsupportedNetwork(self.network.clone())),
         }
 
         if self.security == "reality" {
             if self.public_key.is_none() {
-                return Err(anyhow!("Reality security requires public key"));
+                return Err(missing("VLESS reality", "public key (pbk)"));
             }
             if self.short_id.is_none() {
-                return Err(anyhow!("Reality security requires short ID"));
+                return Err(missing("VLESS reality", "short ID (sid)"));
             }
         }
 
         if let Some(mode) = self.xor_mode && mode > 2 {
-            return Err(anyhow!("xorMode must be between 0 and 2"));
+            return Err(invalid("xorMode", "must be between 0 and 2"));
         }
 
         if let Some(seconds) = self.seconds && seconds == 0 {
-            return Err(anyhow!("seconds must be greater than 0"));
+            return Err(invalid("seconds", "must be greater than 0"));
         }
 
         if let Some(tag) = &self.reverse_tag && tag.trim().is_empty() {
-            return Err(anyhow!("reverse tag cannot be empty"));
+            return Err(invalid("reverse tag", "cannot be empty"));
         }
 
         if self.spider_x.is_some() && self.security != "reality" {
-            return Err(anyhow!(
-                "spiderX is only supported when security is set to reality"
+            return Err(invalid(
+                "spiderX",
+                "is only supported when security is set to reality",
             ));
         }
 
         Ok(())
     }
+
+    /// Unrecognized `encryption` values are passed to xray unchanged, since newer releases may
+    /// support them, but are worth a warning because a typo silently breaks the handshake.
+    pub fn encryption_warning(&self) -> Option<String> {
+        if is_known_vless_encryption(self.encryption.trim()) {
+            return None;
+        }
+        Some(format!(
+            "VLESS {}:{} uses unrecognized encryption {:?}, passing it to xray as-is",
+            self.host, self.port, self.encryption
+        ))
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct TrojanConfig {
     pub name: Option<String>,
+    /// Text of the `# comment` line right above the link in a proxy list
+    pub label: Option<String>,
     pub password: String,
     pub server: String,
     pub port: u16,
@@ -243,24 +393,27 @@ pub struct TrojanConfig {
 }
 
 impl TrojanConfig {
-    pub fn parse(url_str: &str) -> Result<Self> {
+    pub fn parse(url_str: &str) -> Result<Self, ProxyParseError> {
         if !url_str.starts_with("trojan://") {
-            return Err(anyhow!("Invalid Trojan URL: must start with 'trojan://'"));
+            return Err(ProxyParseError::WrongScheme { expected: "trojan" });
         }
-        let u = Url::parse(url_str).context("Failed to parse Trojan URL")?;
+        let u = Url::parse(url_str).map_err(|source| ProxyParseError::InvalidUrl {
+            protocol: "Trojan",
+            source,
+        })?;
 
         let password = u.username().to_string();
         if password.is_empty() {
-            return Err(anyhow!("Trojan URL missing password"));
+            return Err(missing("Trojan", "password"));
         }
 
         let host = u
             .host_str()
-            .ok_or_else(|| anyhow!("Trojan URL missing host"))?
+            .ok_or_else(|| missing("Trojan", "host"))?
             .to_string();
-        let port = u.port().ok_or_else(|| anyhow!("Trojan URL missing port"))?;
+        let port = u.port().ok_or_else(|| missing("Trojan", "port"))?;
         if port == 0 || port == 1 {
-            return Err(anyhow!("skipping port: {}", port));
+            return Err(ProxyParseError::InvalidPort(port));
         }
 
         let mut settings: HashMap<String, String> = HashMap::new();
@@ -273,11 +426,11 @@ impl TrojanConfig {
         }
 
         let config = TrojanConfig {
-            name: if u.fragment().unwrap_or("").is_empty() {
-                None
-            } else {
-                Some(u.fragment().unwrap().to_string())
-            },
+            name: u
+                .fragment()
+                .filter(|f| !f.is_empty())
+                .map(percent_decode_lossy),
+            label: None,
             password,
             server: host,
             port,
@@ -307,9 +460,25 @@ impl TrojanConfig {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+const SHADOWSOCKS_METHODS: &[&str] = &[
+    "aes-128-gcm",
+    "aes-256-gcm",
+    "chacha20-poly1305",
+    "chacha20-ietf-poly1305",
+    "xchacha20-poly1305",
+    "xchacha20-ietf-poly1305",
+    "2022-blake3-aes-128-gcm",
+    "2022-blake3-aes-256-gcm",
+    "2022-blake3-chacha20-poly1305",
+    "none",
+    "plain",
+];
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ShadowsocksConfig {
     pub name: Option<String>,
+    /// Text of the `# comment` line right above the link in a proxy list
+    pub label: Option<String>,
     pub method: String,
     pub password: String,
     pub server: String,
@@ -318,11 +487,14 @@ pub struct ShadowsocksConfig {
 }
 
 impl ShadowsocksConfig {
-    pub fn parse(url_str: &str) -> Result<Self> {
+    pub fn parse(url_str: &str) -> Result<Self, ProxyParseError> {
         if !url_str.starts_with("ss://") {
-            return Err(anyhow!("Invalid Shadowsocks URL: must start with 'ss://'"));
+            return Err(ProxyParseError::WrongScheme { expected: "ss" });
         }
-        let u = Url::parse(url_str).context("Failed to parse Shadowsocks URL")?;
+        let u = Url::parse(url_str).map_err(|source| ProxyParseError::InvalidUrl {
+            protocol: "Shadowsocks",
+            source,
+        })?;
 
         let userinfo = if let Some(pw) = u.password() {
             format!("{}:{}", u.username(), pw)
@@ -330,28 +502,18 @@ impl ShadowsocksConfig {
             u.username().to_string()
         };
         if userinfo.is_empty() {
-            return Err(anyhow!("Shadowsocks URL missing userinfo"));
+            return Err(missing("Shadowsocks", "userinfo"));
         }
 
-        let decoded = auto_decode(&userinfo).unwrap_or_else(|_| userinfo.into_bytes());
-        let decoded_str = String::from_utf8_lossy(&decoded);
-
-        let parts: Vec<&str> = decoded_str.splitn(2, ':').collect();
-        if parts.len() != 2 {
-            return Err(anyhow!("invalid method:password format"));
-        }
-        let method = parts[0].to_string();
-        let password = parts[1].to_string();
+        let (method, password) = split_ss_userinfo(&userinfo)?;
 
         let server = u
             .host_str()
-            .ok_or_else(|| anyhow!("Shadowsocks URL missing host"))?
+            .ok_or_else(|| missing("Shadowsocks", "host"))?
             .to_string();
-        let port = u
-            .port()
-            .ok_or_else(|| anyhow!("Shadowsocks URL missing port"))?;
+        let port = u.port().ok_or_else(|| missing("Shadowsocks", "port"))?;
         if port == 0 || port == 1 {
-            return Err(anyhow!("skipping port: {}", port));
+            return Err(ProxyParseError::InvalidPort(port));
         }
 
         let mut settings: HashMap<String, String> = HashMap::new();
@@ -360,11 +522,11 @@ impl ShadowsocksConfig {
         }
 
         Ok(ShadowsocksConfig {
-            name: if u.fragment().unwrap_or("").is_empty() {
-                None
-            } else {
-                Some(u.fragment().unwrap().to_string())
-            },
+            name: u
+                .fragment()
+                .filter(|f| !f.is_empty())
+                .map(percent_decode_lossy),
+            label: None,
             method,
             password,
             server,
@@ -372,9 +534,46 @@ impl ShadowsocksConfig {
             settings,
         })
     }
+
+    pub fn validate(&self) -> Result<(), ProxyParseError> {
+        let method = self.method.to_ascii_lowercase();
+        if !SHADOWSOCKS_METHODS.contains(&method.as_str()) {
+            return Err(ProxyParseError::UnsupportedMethod(self.method.clone()));
+        }
+
+        if self.password.is_empty() {
+            return Err(missing("Shadowsocks", "password"));
+        }
+
+        Ok(())
+    }
+
+    pub fn is_2022(&self) -> bool {
+        is_2022_method(&self.method)
+    }
+
+    pub fn udp_over_tcp(&self) -> bool {
+        self.settings
+            .get("uot")
+            .or_else(|| self.settings.get("udp-over-tcp"))
+            .map(|v| is_truthy(v))
+            .unwrap_or(false)
+    }
+}
+
+/// `none`, or xray's post-quantum client form `mlkem768x25519plus.<mode>.<rtt>.<key>...`.
+fn is_known_vless_encryption(value: &str) -> bool {
+    if value == "none" {
+        return true;
+    }
+    let mut parts = value.split('.');
+    parts.next() == Some("mlkem768x25519plus")
+        && matches!(parts.next(), Some("native" | "xorpub" | "random"))
+        && matches!(parts.next(), Some("0rtt" | "1rtt"))
+        && parts.next().is_some_and(|key| !key.is_empty())
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(tag = "protocol", content = "config")]
 pub enum ProxyConfig {
     Vless(Box<VlessConfig>),
@@ -382,17 +581,98 @@ pub enum ProxyConfig {
     Shadowsocks(ShadowsocksConfig),
 }
 
-pub fn parse_proxy_url(proxy_url: &str) -> Result<ProxyConfig> {
+impl ProxyConfig {
+    /// Display name taken from the URL `#fragment`, if any.
+    pub fn name(&self) -> Option<&str> {
+        match self {
+            ProxyConfig::Vless(v) => v.name.as_deref(),
+            ProxyConfig::Trojan(t) => t.name.as_deref(),
+            ProxyConfig::Shadowsocks(s) => s.name.as_deref(),
+        }
+    }
+
+    /// Label from the comment line preceding the link in a proxy list, if any.
+    pub fn label(&self) -> Option<&str> {
+        match self {
+            ProxyConfig::Vless(v) => v.label.as_deref(),
+            ProxyConfig::Trojan(t) => t.label.as_deref(),
+            ProxyConfig::Shadowsocks(s) => s.label.as_deref(),
+        }
+    }
+
+    pub fn set_label(&mut self, label: Option<String>) {
+        match self {
+            ProxyConfig::Vless(v) => v.label = label,
+            ProxyConfig::Trojan(t) => t.label = label,
+            ProxyConfig::Shadowsocks(s) => s.label = label,
+        }
+    }
+
+    /// What logs and tables call this proxy: the list label, else the URL name.
+    pub fn display_name(&self) -> Option<String> {
+        self.label().or_else(|| self.name()).map(str::to_string)
+    }
+
+    fn name_contains(&self, needle: &str) -> bool {
+        self.name()
+            .is_some_and(|name| name.to_lowercase().contains(&needle.to_lowercase()))
+    }
+}
+
+/// Keeps proxies whose name contains `include` and drops those whose name contains `exclude`.
+/// Matching is case-insensitive; unnamed proxies never match.
+pub fn filter_by_name(
+    configs: Vec<ProxyConfig>,
+    include: Option<&str>,
+    exclude: Option<&str>,
+) -> Result<Vec<ProxyConfig>> {
+    let total = configs.len();
+    let filtered: Vec<ProxyConfig> = configs
+        .into_iter()
+        .filter(|cfg| include.is_none_or(|needle| cfg.name_contains(needle)))
+        .filter(|cfg| !exclude.is_some_and(|needle| cfg.name_contains(needle)))
+        .collect();
+
+    if filtered.is_empty() {
+        return Err(anyhow!(
+            "Name filters removed all {total} proxies (filter: {include:?}, exclude: {exclude:?})"
+        ));
+    }
+    if filtered.len() < total {
+        log::info!("Name filters kept {} of {} proxies", filtered.len(), total);
+    }
+    Ok(filtered)
+}
+
+/// Keeps only the proxy at 0-based `index` in list order (`--only-proxy`).
+pub fn select_by_index(mut configs: Vec<ProxyConfig>, index: usize) -> Result<Vec<ProxyConfig>> {
+    let total = configs.len();
+    if index >= total {
+        return Err(anyhow!(
+            "Proxy index {index} is out of range, the list has {total} proxies (0..={})",
+            total.saturating_sub(1)
+        ));
+    }
+    let selected = configs.swap_remove(index);
+    log::info!(
+        "Using only proxy {} ({})",
+        index,
+        selected.display_name().as_deref().unwrap_or("unnamed")
+    );
+    Ok(vec![selected])
+}
+
+pub fn parse_proxy_url(proxy_url: &str) -> Result<ProxyConfig, ProxyParseError> {
     let proxy_url = proxy_url.trim();
     if proxy_url.is_empty() {
-        return Err(anyhow!("empty proxy URL"));
+        return Err(ProxyParseError::Empty);
     }
 
-    let u = Url::parse(proxy_url).context("error parsing proxy URL")?;
+    let u = Url::parse(proxy_url).map_err(|source| ProxyParseError::InvalidUrl {
+        protocol: "proxy",
+        source,
+    })?;
     let scheme = u.scheme();
-    if scheme.is_empty() {
-        return Err(anyhow!("protocol is missing in URL: {}", proxy_url));
-    }
 
     match scheme {
         "vless" => {
@@ -401,32 +681,112 @@ pub fn parse_proxy_url(proxy_url: &str) -> Result<ProxyConfig> {
             Ok(ProxyConfig::Vless(Box::new(cfg)))
         }
         "trojan" => Ok(ProxyConfig::Trojan(Box::new(TrojanConfig::parse(proxy_url)?))),
-        "ss" => Ok(ProxyConfig::Shadowsocks(ShadowsocksConfig::parse(
-            proxy_url,
-        )?)),
-        _ => Err(anyhow!("unsupported protocol: {}", scheme)),
+        "ss" => {
+            let cfg = ShadowsocksConfig::parse(proxy_url)?;
+            cfg.validate()?;
+            Ok(ProxyConfig::Shadowsocks(cfg))
+        }
+        _ => Err(ProxyParseError::UnsupportedProtocol(scheme.to_string())),
     }
 }
 
+/// Line counts of a parsed proxy list, with failures grouped by [`ProxyParseError::category`].
+#[derive(Debug, Default)]
+struct ListSummary {
+    lines: usize,
+    skipped: usize,
+    parsed: usize,
+    failures: BTreeMap<String, usize>,
+}
+
+impl fmt::Display for ListSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let failed: usize = self.failures.values().sum();
+        write!(
+            f,
+            "{} lines: {} parsed, {} blank or comment, {} failed",
+            self.lines, self.parsed, self.skipped, failed
+        )?;
+        if !self.failures.is_empty() {
+            let breakdown: Vec<String> = self
+                .failures
+                .iter()
+                .map(|(category, count)| format!("{category}: {count}"))
+                .collect();
+            write!(f, " ({})", breakdown.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Parses one link per line. A `# comment` directly above a link becomes its
+/// [`ProxyConfig::label`]; a blank line in between drops it. A commented-out link is never a
+/// label, since its credentials would end up in logs and reports.
 pub fn parse_proxy_list(content: &str) -> Result<Vec<ProxyConfig>> {
     let mut configs = Vec::new();
+    let mut summary = ListSummary::default();
+    let mut label: Option<String> = None;
     for (line_num, line) in content.lines().enumerate() {
+        summary.lines += 1;
         let line = line.trim();
-        if line.is_empty() || line.starts_with('#') {
+        if let Some(comment) = line.strip_prefix('#') {
+            summary.skipped += 1;
+            label =
+                Some(comment.trim().to_string()).filter(|c| !c.is_empty() && !c.contains("://"));
+            continue;
+        }
+        if line.is_empty() {
+            summary.skipped += 1;
+            label = None;
             continue;
         }
 
         match parse_proxy_url(line) {
-            Ok(cfg) => configs.push(cfg),
-            Err(e) => log::warn!("Failed to parse proxy URL on line {}: {}", line_num + 1, e),
+            Ok(mut cfg) => {
+                cfg.set_label(label.take());
+                configs.push(cfg);
+            }
+            Err(e) => {
+                label = None;
+                log::warn!("Failed to parse proxy URL on line {}: {}", line_num + 1, e);
+                *summary.failures.entry(e.category()).or_default() += 1;
+            }
         }
     }
+    summary.parsed = configs.len();
+
     if configs.is_empty() {
-        return Err(anyhow!("No valid proxy configurations found"));
+        return Err(anyhow!("No valid proxy configurations found ({summary})"));
     }
+    log::info!("Proxy list: {summary}");
     Ok(configs)
 }
 
+fn missing(protocol: &'static str, field: &'static str) -> ProxyParseError {
+    ProxyParseError::MissingField { protocol, field }
+}
+
+fn invalid(name: &'static str, reason: &'static str) -> ProxyParseError {
+    ProxyParseError::InvalidParameter { name, reason }
+}
+
+/// Decodes percent-escapes in a URL fragment or a double-encoded `extra` value.
+/// Values without escapes are returned unchanged.
+fn percent_decode_lossy(value: &str) -> String {
+    percent_decode_str(value).decode_utf8_lossy().into_owned()
+}
+
+/// Some links quote `extra` or percent-encode it twice, so both are undone before giving up.
+fn parse_xhttp_extra(raw: &str) -> Option<serde_json::Value> {
+    let unquoted = raw.trim_matches('"');
+    let parsed = serde_json::from_str(unquoted)
+        .or_else(|_| serde_json::from_str(percent_decode_lossy(unquoted).trim_matches('"')));
+    match parsed {
+        Ok(extra @ serde_json::Value::Object(_)) => Some(extra),
+        _ => None,
+    }
+}
+
 fn is_truthy(value: &str) -> bool {
     match value.trim() {
         "1" => true,
@@ -437,24 +797,59 @@ fn is_truthy(value: &str) -> bool {
     }
 }
 
-fn auto_decode(input: &str) -> Result<Vec<u8>> {
-    if let Ok(decoded) = percent_decode_str(input).decode_utf8() {
-        let s = decoded.to_string();
-        if let Ok(bytes) = STANDARD.decode(s.as_bytes()) {
-            return Ok(bytes);
-        }
-        if let Ok(bytes) = URL_SAFE_NO_PAD.decode(s.as_bytes()) {
-            return Ok(bytes);
-        }
-        return Ok(s.into_bytes());
-    }
-    if let Ok(bytes) = STANDARD.decode(input.as_bytes()) {
-        return Ok(bytes);
-    }
-    if let Ok(bytes) = URL_SAFE_NO_PAD.decode(input.as_bytes()) {
-        return Ok(bytes);
-    }
-    Ok(input.as_bytes().to_vec())
+fn is_2022_method(method: &str) -> bool {
+    method.to_ascii_lowercase().starts_with("2022-blake3-")
+}
+
+/// Splits SIP002 userinfo into method and password. Accepts plain `method:password`
+/// (percent-encoded), fully base64-encoded `method:password`, and `method:base64(password)`.
+/// The password is everything after the first colon, so it may contain colons itself.
+fn split_ss_userinfo(userinfo: &str) -> Result<(String, String), ProxyParseError> {
+    let text = percent_decode_str(userinfo)
+        .decode_utf8_lossy()
+        .into_owned();
+    // Base64 never contains ':', so a colon means the userinfo is already plain text.
+    let plain = if text.contains(':') {
+        text
+    } else {
+        decode_base64(&text)
+            .and_then(|bytes| String::from_utf8(bytes).ok())
+            .unwrap_or(text)
+    };
+
+    let Some((method, password)) = plain.split_once(':') else {
+        return Err(invalid("userinfo", "must be in method:password format"));
+    };
+
+    // 2022 ciphers take the base64 key itself as the password.
+    let password = if is_2022_method(method) {
+        password.to_string()
+    } else if let Some(decoded) = decode_base64_password(password) {
+        // A literal password can look like padded base64 too, so the rewrite is not silent.
+        log::warn!(
+            "Shadowsocks {method} password looks like padded base64 and is used decoded; \
+             the literal value is not tried"
+        );
+        decoded
+    } else {
+        password.to_string()
+    };
+    Ok((method.to_string(), password))
+}
+
+fn decode_base64(input: &str) -> Option<Vec<u8>> {
+    [STANDARD, STANDARD_NO_PAD, URL_SAFE, URL_SAFE_NO_PAD]
+        .iter()
+        .find_map(|engine| engine.decode(input).ok())
+}
+
+/// Only strictly padded base64 that decodes to printable ASCII counts as an encoded password;
+/// anything else is taken literally.
+fn decode_base64_password(password: &str) -> Option<String> {
+    let bytes = STANDARD.decode(password).ok()?;
+    let decoded = String::from_utf8(bytes).ok()?;
+    let printable = !decoded.is_empty() && decoded.chars().all(|c| c.is_ascii_graphic());
+    printable.then_some(decoded)
 }
 
 #[cfg(test)]
@@ -487,6 +882,147 @@ mod tests {
         assert_eq!(config.raw, url);
     }
 
+    #[test]
+    fn test_vless_encryption_none() {
+        let config = VlessConfig::parse("vless://id@example.com:443?encryption=none").unwrap();
+        assert_eq!(config.encryption, "none");
+        assert_eq!(config.encryption_warning(), None);
+
+        let config = VlessConfig::parse("vless://id@example.com:443").unwrap();
+        assert_eq!(config.encryption, "none");
+    }
+
+    #[test]
+    fn test_vless_unrecognized_encryption_warns() {
+        let mlkem = "mlkem768x25519plus.native.0rtt.client-key";
+        let url = format!("vless://id@example.com:443?encryption={mlkem}");
+        let config = VlessConfig::parse(&url).unwrap();
+        assert_eq!(config.encryption, mlkem);
+        assert_eq!(config.encryption_warning(), None);
+
+        let url = "vless://id@example.com:443?encryption=aes-128-gcm";
+        let config = VlessConfig::parse(url).unwrap();
+        assert_eq!(config.encryption, "aes-128-gcm");
+        let warning = config.encryption_warning().unwrap();
+        assert!(warning.contains("\"aes-128-gcm\""), "{warning}");
+    }
+
+    #[test]
+    fn test_parse_reality_spider_x_and_server_names() {
+        let url = "vless://uuid@example.com:443?security=reality&pbk=key&sid=ab&spx=%2Fsearch&serverNames=a.example.com,b.example.com";
+        let config = VlessConfig::parse(url).unwrap();
+
+        assert_eq!(config.spider_x.as_deref(), Some("/search"));
+        assert_eq!(config.server_names, vec!["a.example.com", "b.example.com"]);
+
+        let literal = VlessConfig::parse(
+            "vless://uuid@example.com:443?security=reality&pbk=key&sid=ab&spx=%2Fq%3D100%2525",
+        )
+        .unwrap();
+        assert_eq!(literal.spider_x.as_deref(), Some("/q=100%25"));
+    }
+
+    #[test]
+    fn test_parse_xhttp_extra_json() {
+        let url = "vless://uuid@example.com:443?type=xhttp&mode=auto&extra=%7B%22xPaddingBytes%22%3A%22100-1000%22%2C%22downloadSettings%22%3A%7B%22address%22%3A%22dl.example.com%22%7D%7D";
+        let config = VlessConfig::parse(url).unwrap();
+        let extra = config.extra_xhttp.unwrap();
+        assert_eq!(extra["xPaddingBytes"], "100-1000");
+        assert_eq!(extra["downloadSettings"]["address"], "dl.example.com");
+
+        let double_encoded = VlessConfig::parse(
+            "vless://uuid@example.com:443?type=xhttp&extra=%257B%2522noSSEHeader%2522%253Atrue%257D",
+        )
+        .unwrap();
+        assert_eq!(double_encoded.extra_xhttp.unwrap()["noSSEHeader"], true);
+
+        let invalid =
+            VlessConfig::parse("vless://uuid@example.com:443?type=xhttp&extra=%7Bnope").unwrap();
+        assert_eq!(invalid.extra_xhttp, None);
+    }
+
+    #[test]
+    fn test_percent_decode_path_host_and_service_name() {
+        let vless = VlessConfig::parse(
+            "vless://uuid@example.com:443?type=ws&path=%2Fws%3Fed%3D2048&host=cdn.example.com",
+        )
+        .unwrap();
+        assert_eq!(vless.path.as_deref(), Some("/ws?ed=2048"));
+        assert_eq!(vless.host_header.as_deref(), Some("cdn.example.com"));
+
+        // Query parsing already decoded once; a literal `%` must survive.
+        let literal =
+            VlessConfig::parse("vless://uuid@example.com:443?type=ws&path=/a%2520b").unwrap();
+        assert_eq!(literal.path.as_deref(), Some("/a%20b"));
+
+        let grpc = VlessConfig::parse("vless://uuid@example.com:443?type=grpc&serviceName=%2Fgun")
+            .unwrap();
+        assert_eq!(grpc.service_name.as_deref(), Some("/gun"));
+
+        let trojan = TrojanConfig::parse(
+            "trojan://pass@example.com:443?type=ws&path=%2Fws&host=cdn.example.com&serviceName=svc",
+        )
+        .unwrap();
+        assert_eq!(trojan.path.as_deref(), Some("/ws"));
+        assert_eq!(trojan.host.as_deref(), Some("cdn.example.com"));
+        assert_eq!(trojan.service_name.as_deref(), Some("svc"));
+
+        let trojan =
+            TrojanConfig::parse("trojan://pass@example.com:443?type=ws&path=/100%25").unwrap();
+        assert_eq!(trojan.path.as_deref(), Some("/100%"));
+    }
+
+    #[test]
+    fn test_filter_by_name() {
+        let configs = parse_proxy_list(
+            "vless://id@de.example.com:443#DE%20Frankfurt\n\
+             trojan://pass@us.example.com:443#US-NewYork\n\
+             ss://YWVzLTI1Ni1nY206cGFzcw@nl.example.com:8388#NL%20Amsterdam%20(backup)\n\
+             vless://id@unnamed.example.com:443",
+        )
+        .unwrap();
+        assert_eq!(configs[0].name(), Some("DE Frankfurt"));
+        assert_eq!(configs[2].name(), Some("NL Amsterdam (backup)"));
+
+        let kept = filter_by_name(configs.clone(), Some("us"), None).unwrap();
+        assert_eq!(kept.len(), 1);
+        assert_eq!(kept[0].name(), Some("US-NewYork"));
+
+        let kept = filter_by_name(configs.clone(), None, Some("BACKUP")).unwrap();
+        assert_eq!(kept.len(), 3);
+
+        assert!(filter_by_name(configs, Some("jp"), None).is_err());
+    }
+
+    #[test]
+    fn test_names_are_decoded_once() {
+        let configs = parse_proxy_list(
+            "vless://id@a.example.com:443#50%2541off\n\
+             trojan://pass@b.example.com:443#50%2541off\n\
+             ss://aes-128-gcm:c@c.example.com:8388#50%2541off",
+        )
+        .unwrap();
+        for config in &configs {
+            assert_eq!(config.name(), Some("50%41off"));
+            assert_eq!(config.display_name().as_deref(), Some("50%41off"));
+        }
+        assert_eq!(filter_by_name(configs, Some("%41"), None).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_parse_h2_vless() {
+        let url = "vless://uuid@example.com:443?security=tls&type=h2&host=a.example.com,b.example.com&path=%2Fh2";
+        let config = VlessConfig::parse(url).unwrap();
+
+        assert_eq!(config.network, "h2");
+        assert_eq!(config.path.as_deref(), Some("/h2"));
+        assert_eq!(
+            config.host_header.as_deref(),
+            Some("a.example.com,b.example.com")
+        );
+        assert!(config.validate().is_ok());
+    }
+
     #[test]
     fn test_invalid_url() {
         let url = "http://example.com";
@@ -563,10 +1099,151 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_parse_shadowsocks_password_with_colon() {
+        let cfg = ShadowsocksConfig::parse("ss://aes-256-gcm:pa:ss:word@host:8388").unwrap();
+        assert_eq!(cfg.method, "aes-256-gcm");
+        assert_eq!(cfg.password, "pa:ss:word");
+
+        // base64("aes-256-gcm:pa:ss:word"), unpadded
+        let url = "ss://YWVzLTI1Ni1nY206cGE6c3M6d29yZA@host:8388";
+        let cfg = ShadowsocksConfig::parse(url).unwrap();
+        assert_eq!(cfg.method, "aes-256-gcm");
+        assert_eq!(cfg.password, "pa:ss:word");
+    }
+
+    #[test]
+    fn test_parse_shadowsocks_base64_password_only() {
+        // base64("pa:ss")
+        let cfg = ShadowsocksConfig::parse("ss://aes-256-gcm:cGE6c3M=@host:8388").unwrap();
+        assert_eq!(cfg.method, "aes-256-gcm");
+        assert_eq!(cfg.password, "pa:ss");
+    }
+
+    #[test]
+    fn test_parse_shadowsocks_2022_key() {
+        let url = "ss://2022-blake3-aes-256-gcm:\
+                   YctPZ6U7xPPcU%2Bgp3u%2BOUZ5mL%2F7hv1Ng1xlGEk4T3ZQ%3D@host:8388";
+        let cfg = ShadowsocksConfig::parse(url).unwrap();
+        assert_eq!(cfg.method, "2022-blake3-aes-256-gcm");
+        assert_eq!(cfg.password, "YctPZ6U7xPPcU+gp3u+OUZ5mL/7hv1Ng1xlGEk4T3ZQ=");
+        assert!(cfg.validate().is_ok());
+    }
+
+    #[test]
+    fn test_shadowsocks_validate_known_method() {
+        let cfg = ShadowsocksConfig::parse("ss://2022-blake3-aes-256-gcm:key@host:8388").unwrap();
+        assert!(cfg.validate().is_ok());
+    }
+
+    #[test]
+    fn test_shadowsocks_validate_unknown_method() {
+        let url = "ss://aes-256-gcmm:pwd@host:8388";
+        let err = parse_proxy_url(url).unwrap_err();
+        assert!(err.to_string().contains("aes-256-gcmm"));
+    }
+
     #[test]
     fn test_parse_proxy_url_unsupported() {
         let url = "socks5://localhost:1080";
-        assert!(parse_proxy_url(url).is_err());
+        assert_eq!(
+            parse_proxy_url(url).unwrap_err(),
+            ProxyParseError::UnsupportedProtocol("socks5".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_proxy_list_failure_summary() {
+        let content = "# subscription\n\nvmess://a\nvmess://b\nvless://id@host:1\n";
+        let err = parse_proxy_list(content).unwrap_err().to_string();
+        assert!(
+            err.contains(
+                "5 lines: 0 parsed, 2 blank or comment, 3 failed \
+                 (invalid port: 1, unsupported protocol vmess: 2)"
+            ),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn test_parse_proxy_list_comment_labels() {
+        let content = "# Germany node 1\n\
+                       ss://aes-128-gcm:a@one.example:8388#de-1\n\
+                       ss://aes-128-gcm:b@two.example:8388#two%20b\n\
+                       # stale note\n\
+                       \n\
+                       ss://aes-128-gcm:c@three.example:8388\n\
+                       # broken\n\
+                       vmess://ignored\n\
+                       ss://aes-128-gcm:d@four.example:8388\n";
+        let configs = parse_proxy_list(content).unwrap();
+
+        assert_eq!(configs[0].label(), Some("Germany node 1"));
+        assert_eq!(configs[0].display_name().as_deref(), Some("Germany node 1"));
+        assert_eq!(configs[1].label(), None);
+        assert_eq!(configs[1].display_name().as_deref(), Some("two b"));
+        // A blank line or a failed link in between drops the comment.
+        assert_eq!(configs[2].display_name(), None);
+        assert_eq!(configs[3].label(), None);
+    }
+
+    #[test]
+    fn test_parse_proxy_list_commented_link_is_not_a_label() {
+        let content = "# Germany node 1\n\
+                       # vless://secret-uuid@old.example:443?security=tls\n\
+                       ss://aes-128-gcm:a@one.example:8388\n";
+        let configs = parse_proxy_list(content).unwrap();
+
+        assert_eq!(configs.len(), 1);
+        assert_eq!(configs[0].label(), None);
+        assert_eq!(configs[0].display_name(), None);
+    }
+
+    #[test]
+    fn test_select_by_index() {
+        let configs = parse_proxy_list(
+            "ss://aes-128-gcm:a@one.example:8388#one\n\
+             ss://aes-128-gcm:b@two.example:8388#two\n",
+        )
+        .unwrap();
+
+        let selected = select_by_index(configs.clone(), 1).unwrap();
+        assert_eq!(selected.len(), 1);
+        assert_eq!(selected[0].name(), Some("two"));
+
+        let err = select_by_index(configs, 2).unwrap_err();
+        assert!(err.to_string().contains("out of range"), "{err}");
+    }
+
+    #[test]
+    fn test_parse_errors_are_matchable() {
+        assert_eq!(parse_proxy_url("  ").unwrap_err(), ProxyParseError::Empty);
+        assert_eq!(
+            parse_proxy_url("vless://id@host:1").unwrap_err(),
+            ProxyParseError::InvalidPort(1)
+        );
+        assert_eq!(
+            parse_proxy_url("vless://id@host:443?security=xtls").unwrap_err(),
+            ProxyParseError::UnsupportedSecurity("xtls".to_string())
+        );
+        assert_eq!(
+            parse_proxy_url("trojan://@host:443").unwrap_err(),
+            ProxyParseError::MissingField {
+                protocol: "Trojan",
+                field: "password"
+            }
+        );
+        assert!(matches!(
+            parse_proxy_url("ss://aes-256-gcmm:pwd@host:8388"),
+            Err(ProxyParseError::UnsupportedMethod(method)) if method == "aes-256-gcmm"
+        ));
+        assert!(matches!(
+            parse_proxy_url("vless://id@host:99999"),
+            Err(ProxyParseError::InvalidUrl { .. })
+        ));
+
+        let as_anyhow: anyhow::Error = ProxyParseError::InvalidPort(0).into();
+        assert_eq!(as_anyhow.to_string(), "skipping port: 0");
     }
 
     #[test]
@@ -1,33 +1,57 @@
-use crate::config::ConfigGenerator;
+use crate::cli::InstanceMode;
+use crate::config::{ConfigGenerator, ConfigOptions, InboundAuth, XrayConfig};
 use crate::parser::ProxyConfig;
+use crate::stressor::{perform_greeting, seeded_rng};
 use anyhow::{Context, Result};
+use futures::future::join_all;
+use rand::seq::SliceRandom;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
 use std::io::ErrorKind;
-use std::net::TcpListener;
+use std::net::{IpAddr, TcpListener};
 use std::os::unix::process::CommandExt;
 use std::process::{Child, Command, Stdio};
 use std::sync::Arc;
 use std::time::Duration;
+use tokio::net::TcpStream;
 use tokio::sync::Mutex;
-use tokio::time::sleep;
+use tokio::time::{sleep, timeout};
+
+/// How long a `--monitor-probe` greeting may take before it counts as failed.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+/// Consecutive failed greetings before a running instance is considered hung; one miss is
+/// tolerated so an instance still binding its port after a restart is not killed again.
+const PROBE_FAILURES_BEFORE_RESTART: u32 = 2;
 
 #[derive(Debug)]
 pub struct XrayInstance {
     pub port: u16,
+    instance_idx: usize,
     proxy_config: ProxyConfig,
     pub process: Child,
+    restarts: u32,
+    /// Consecutive failed `--monitor-probe` greetings
+    probe_failures: u32,
+    /// Crash already logged by a monitor that does not restart (`--no-restart`)
+    left_down: bool,
 }
 
 impl XrayInstance {
     pub fn new(
         proxy_config: &ProxyConfig,
         port: u16,
+        instance_idx: usize,
         config_generator: &ConfigGenerator,
     ) -> Result<Self> {
-        let config_path = config_generator.generate_config(proxy_config, port)?;
+        let config_path = config_generator.generate_config(proxy_config, port, instance_idx)?;
 
         log::info!(
-            "Starting xray-core instance on port {} with config: {}",
+            "Starting xray-core instance on port {} for {} with config: {}",
             port,
+            proxy_config
+                .display_name()
+                .as_deref()
+                .unwrap_or("unnamed proxy"),
             config_path.display()
         );
 
@@ -64,8 +88,12 @@ impl XrayInstance {
 
         Ok(XrayInstance {
             port,
+            instance_idx,
             proxy_config: proxy_config.clone(),
             process,
+            restarts: 0,
+            probe_failures: 0,
+            left_down: false,
         })
     }
 
@@ -87,11 +115,16 @@ impl XrayInstance {
             return Ok(());
         }
 
-        let config_path = config_generator.generate_config(&self.proxy_config, self.port)?;
+        let config_path =
+            config_generator.generate_config(&self.proxy_config, self.port, self.instance_idx)?;
 
         log::warn!(
-            "Restarting xray-core instance on port {} with config: {}",
+            "Restarting xray-core instance on port {} for {} with config: {}",
             self.port,
+            self.proxy_config
+                .display_name()
+                .as_deref()
+                .unwrap_or("unnamed proxy"),
             config_path.display()
         );
 
@@ -129,6 +162,8 @@ impl XrayInstance {
         }
 
         self.process = process;
+        self.restarts += 1;
+        self.probe_failures = 0;
         Ok(())
     }
 
@@ -179,22 +214,110 @@ impl Drop for XrayInstance {
     }
 }
 
+/// Point-in-time view of one xray-core instance, as shown by the `--tui` dashboard and
+/// written to the `--report` file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct InstanceStatus {
+    pub port: u16,
+    pub pid: u32,
+    /// False between a crash and the monitor's next restart attempt, for good with `--no-restart`
+    pub alive: bool,
+    /// Restarts by the monitor or `--stall-restart` so far
+    pub restarts: u32,
+    /// [`ProxyConfig::display_name`] of the proxy this instance serves
+    pub proxy: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub enum PortAllocation {
+    /// Probe upwards from `base_port` for `instances` free ports
+    Sequential { base_port: u16, instances: usize },
+    /// Start exactly one instance on each listed port
+    Explicit(Vec<u16>),
+}
+
+impl PortAllocation {
+    fn instance_count(&self) -> usize {
+        match self {
+            PortAllocation::Sequential { instances, .. } => *instances,
+            PortAllocation::Explicit(ports) => ports.len(),
+        }
+    }
+}
+
+/// Pause between two xray-core launches unless overridden with [`ProcessManager::with_launch_stagger`].
+pub const DEFAULT_LAUNCH_STAGGER: Duration = Duration::from_millis(100);
+
 #[derive(Clone)]
 pub struct ProcessManager {
     instances: Arc<Mutex<Vec<XrayInstance>>>,
     config_generator: Arc<ConfigGenerator>,
+    launch_stagger: Duration,
+    listen: IpAddr,
+    require_all: bool,
+    seed: Option<u64>,
+    probe: Option<InboundProbe>,
+    restart: bool,
 }
 
 impl ProcessManager {
-    pub fn new() -> Result<Self> {
+    pub fn new(config_options: ConfigOptions) -> Result<Self> {
+        let listen = config_options.listen_addr();
         Ok(Self {
             instances: Arc::new(Mutex::new(Vec::new())),
-            config_generator: Arc::new(ConfigGenerator::new()?),
+            config_generator: Arc::new(ConfigGenerator::new(config_options)?),
+            launch_stagger: DEFAULT_LAUNCH_STAGGER,
+            listen,
+            require_all: false,
+            seed: None,
+            probe: None,
+            restart: true,
         })
     }
 
-    fn is_port_available(port: u16) -> bool {
-        match TcpListener::bind(("127.0.0.1", port)) {
+    /// Makes [`ProcessManager::start_instances`] fail unless every requested instance started.
+    pub fn with_require_all(mut self, require_all: bool) -> Self {
+        self.require_all = require_all;
+        self
+    }
+
+    /// Seeds the `shuffle` instance mode so a `--seed` run assigns proxies in the same order.
+    pub fn with_seed(mut self, seed: Option<u64>) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Makes the monitor greet every SOCKS5 inbound each pass and restart instances whose process
+    /// is alive but whose inbound stopped answering (`--monitor-probe`).
+    pub fn with_monitor_probe(mut self, enabled: bool) -> Self {
+        let options = self.config_generator.options();
+        self.probe = enabled.then(|| InboundProbe {
+            host: options.proxy_host(),
+            auth: options.auth.clone(),
+        });
+        self
+    }
+
+    /// With `false` the monitor only logs crashed instances and leaves them down (`--no-restart`).
+    pub fn with_restart(mut self, enabled: bool) -> Self {
+        self.restart = enabled;
+        self
+    }
+
+    pub fn restarts_enabled(&self) -> bool {
+        self.restart
+    }
+
+    /// Sets the pause between consecutive instance launches (zero launches them back-to-back).
+    pub fn with_launch_stagger(mut self, stagger: Duration) -> Self {
+        self.launch_stagger = stagger;
+        self
+    }
+
+    /// Probes the address the inbounds will listen on, so a port that is free on loopback but
+    /// taken on `--listen` is not handed out.
+    fn is_port_available(listen: IpAddr, port: u16) -> bool {
+        match TcpListener::bind((listen, port)) {
             Ok(listener) => {
                 drop(listener);
                 true
@@ -203,9 +326,15 @@ impl ProcessManager {
         }
     }
 
-    fn find_next_free_port(mut start_port: u16) -> Option<u16> {
+    fn find_next_free_port(
+        listen: IpAddr,
+        mut start_port: u16,
+        assigned: &HashSet<u16>,
+    ) -> Option<u16> {
         for _ in 0..10_000u32 {
-            if Self::is_port_available(start_port) {
+            if assigned.contains(&start_port) {
+                log::debug!("Port {start_port} already assigned to another instance, skipping");
+            } else if Self::is_port_available(listen, start_port) {
                 return Some(start_port);
             }
             if start_port == u16::MAX {
@@ -216,30 +345,114 @@ impl ProcessManager {
         None
     }
 
+    fn assign_proxies(
+        proxy_configs: &[ProxyConfig],
+        num_instances: usize,
+        mode: InstanceMode,
+        seed: Option<u64>,
+    ) -> Vec<&ProxyConfig> {
+        match mode {
+            InstanceMode::Cycle => (0..num_instances)
+                .map(|i| &proxy_configs[i % proxy_configs.len()])
+                .collect(),
+            InstanceMode::OnePerProxy => {
+                let mut distinct: Vec<&ProxyConfig> = Vec::with_capacity(proxy_configs.len());
+                for cfg in proxy_configs {
+                    if !distinct.contains(&cfg) {
+                        distinct.push(cfg);
+                    }
+                }
+                distinct
+            }
+            InstanceMode::Shuffle => {
+                let mut order: Vec<&ProxyConfig> = proxy_configs.iter().collect();
+                order.shuffle(&mut seeded_rng(seed));
+                (0..num_instances).map(|i| order[i % order.len()]).collect()
+            }
+        }
+    }
+
     pub async fn start_instances(
         &self,
         proxy_configs: &[ProxyConfig],
-        base_port: u16,
-        num_instances: usize,
+        allocation: &PortAllocation,
+        instance_mode: InstanceMode,
     ) -> Result<Vec<u16>> {
+        if proxy_configs.is_empty() {
+            return Err(anyhow::anyhow!("No proxy configurations to launch"));
+        }
+
         let mut instances = self.instances.lock().await;
         let mut ports = Vec::new();
+        let mut assignments = Self::assign_proxies(
+            proxy_configs,
+            allocation.instance_count(),
+            instance_mode,
+            self.seed,
+        );
 
-        log::info!("Starting {num_instances} xray-core instances from base port {base_port}");
+        if let PortAllocation::Explicit(list) = allocation
+            && assignments.len() > list.len()
+        {
+            log::warn!(
+                "Only {} ports listed for {} proxies; the remaining proxies will not be started",
+                list.len(),
+                assignments.len()
+            );
+            assignments.truncate(list.len());
+        }
+        let num_instances = assignments.len();
+
+        match allocation {
+            PortAllocation::Sequential { base_port, .. } => log::info!(
+                "Starting {num_instances} xray-core instances from base port {base_port} ({instance_mode:?} assignment)"
+            ),
+            PortAllocation::Explicit(list) => log::info!(
+                "Starting {num_instances} xray-core instances on ports {list:?} ({instance_mode:?} assignment)"
+            ),
+        }
 
-        let mut probe_port = base_port;
-        for i in 0..num_instances {
-            let port = match Self::find_next_free_port(probe_port) {
-                Some(p) => p,
-                None => {
-                    log::error!("No free port found starting from {probe_port} for instance {i}");
-                    break;
+        let mut assigned_ports = HashSet::new();
+        let mut probe_port = match allocation {
+            PortAllocation::Sequential { base_port, .. } => *base_port,
+            PortAllocation::Explicit(_) => 0,
+        };
+        for (i, proxy_config) in assignments.into_iter().enumerate() {
+            let port = match allocation {
+                PortAllocation::Sequential { .. } => {
+                    match Self::find_next_free_port(self.listen, probe_port, &assigned_ports) {
+                        Some(p) => p,
+                        None => {
+                            return Err(anyhow::anyhow!(
+                                "Could not find enough distinct free ports for {num_instances} instances: \
+                                 no free port from {probe_port} for instance {i} ({} already assigned)",
+                                assigned_ports.len()
+                            ));
+                        }
+                    }
+                }
+                PortAllocation::Explicit(list) => {
+                    let port = list[i];
+                    if assigned_ports.contains(&port) {
+                        log::error!("Port {port} is listed more than once, skipping instance {i}");
+                        continue;
+                    }
+                    if !Self::is_port_available(self.listen, port) {
+                        log::error!("Listed port {port} is not available, skipping instance {i}");
+                        continue;
+                    }
+                    port
                 }
             };
+            assigned_ports.insert(port);
             probe_port = port.saturating_add(1);
-            let proxy_config = &proxy_configs[i % proxy_configs.len()];
 
-            match XrayInstance::new(proxy_config, port, &self.config_generator) {
+            // Let the previous instance bind its port before the next one competes for CPU.
+            if assigned_ports.len() > 1 && !self.launch_stagger.is_zero() {
+                sleep(self.launch_stagger).await;
+            }
+
+            match XrayInstance::new(proxy_config, port, i, &self.config_generator) {
                 Ok(instance) => {
                     ports.push(port);
                     instances.push(instance);
@@ -253,43 +466,95 @@ impl ProcessManager {
         if ports.is_empty() {
             return Err(anyhow::anyhow!("Failed to start any xray-core instances"));
         }
+        if ports.len() < num_instances {
+            let message = format!(
+                "Requested {num_instances} xray-core instances, only {} started",
+                ports.len()
+            );
+            if self.require_all {
+                // Dropping the instances started by this call stops them again.
+                let keep = instances.len() - ports.len();
+                instances.truncate(keep);
+                return Err(anyhow::anyhow!("{message} (--require-all-instances)"));
+            }
+            log::warn!("{message}; the test runs with reduced capacity");
+        }
 
         log::info!("Successfully started {} xray-core instances", ports.len());
         Ok(ports)
     }
 
+    /// Kills and relaunches every instance on its port (`--stall-restart`), returning how many
+    /// came back up.
+    pub async fn recycle_all(&self) -> usize {
+        let mut instances = self.instances.lock().await;
+        let mut restarted = 0;
+        for inst in instances.iter_mut() {
+            if let Err(e) = inst.terminate() {
+                log::error!("Failed to stop xray-core on port {}: {}", inst.port, e);
+                continue;
+            }
+            match inst.restart(&self.config_generator) {
+                Ok(()) => restarted += 1,
+                Err(e) => log::error!("Failed to restart xray-core on port {}: {}", inst.port, e),
+            }
+        }
+        restarted
+    }
+
+    /// Ports whose xray-core process is still running.
+    pub async fn live_ports(&self) -> Vec<u16> {
+        let mut instances = self.instances.lock().await;
+        instances
+            .iter_mut()
+            .filter_map(|inst| inst.is_running().then_some(inst.port))
+            .collect()
+    }
+
+    pub async fn instance_statuses(&self) -> Vec<InstanceStatus> {
+        let mut instances = self.instances.lock().await;
+        instances
+            .iter_mut()
+            .map(|inst| InstanceStatus {
+                port: inst.port,
+                pid: inst.process.id(),
+                alive: inst.is_running(),
+                restarts: inst.restarts,
+                proxy: inst.proxy_config.display_name(),
+            })
+            .collect()
+    }
+
+    /// Generated xray config of every running instance, keyed by its inbound port.
+    pub async fn instance_configs(&self) -> Result<BTreeMap<u16, XrayConfig>> {
+        let instances = self.instances.lock().await;
+        instances
+            .iter()
+            .map(|inst| {
+                let config = self.config_generator.build_config(
+                    &inst.proxy_config,
+                    inst.port,
+                    inst.instance_idx,
+                )?;
+                Ok((inst.port, config))
+            })
+            .collect()
+    }
+
     pub fn start_monitor(&self, interval: Duration) {
         let instances = Arc::clone(&self.instances);
         let cfg = Arc::clone(&self.config_generator);
+        let probe = self.probe.clone();
+        let restart = self.restart;
 
         tokio::spawn(async move {
             {
                 let mut guard = instances.lock().await;
-                let total = guard.len();
-                let mut alive = 0;
-                let mut restarted = 0;
-
-                for inst in guard.iter_mut() {
-                    if inst.is_running() {
-                        alive += 1;
-                    } else {
-                        log::warn!(
-                            "Detected crashed xray-core on port {}. Attempting restart...",
-                            inst.port
-                        );
-                        if let Err(e) = inst.restart(&cfg) {
-                            log::error!("Failed to restart xray-core on port {}: {}", inst.port, e);
-                        } else {
-                            restarted += 1;
-                            alive += 1;
-                        }
-                    }
-                }
-
+                let (alive, restarted) = check_instances(&mut guard, &cfg, restart);
                 log::debug!(
                     "Monitor initial check: {}/{} alive, {} restarted",
                     alive,
-                    total,
+                    guard.len(),
                     restarted
                 );
             }
@@ -298,25 +563,7 @@ impl ProcessManager {
                 sleep(interval).await;
                 let mut guard = instances.lock().await;
                 let total = guard.len();
-                let mut alive = 0;
-                let mut restarted = 0;
-
-                for inst in guard.iter_mut() {
-                    if inst.is_running() {
-                        alive += 1;
-                    } else {
-                        log::warn!(
-                            "Detected crashed xray-core on port {}. Attempting restart...",
-                            inst.port
-                        );
-                        if let Err(e) = inst.restart(&cfg) {
-                            log::error!("Failed to restart xray-core on port {}: {}", inst.port, e);
-                        } else {
-                            restarted += 1;
-                            alive += 1;
-                        }
-                    }
-                }
+                let (alive, restarted) = check_instances(&mut guard, &cfg, restart);
 
                 if restarted > 0 {
                     log::info!(
@@ -333,6 +580,11 @@ impl ProcessManager {
                         restarted
                     );
                 }
+                drop(guard);
+
+                if let Some(probe) = &probe {
+                    probe.check(&instances, &cfg).await;
+                }
             }
         });
     }
@@ -403,9 +655,192 @@ impl Drop for ProcessManager {
     }
 }
 
+/// One monitor pass: restarts crashed instances, or with `restart` off only logs each crash once.
+/// Returns how many instances are alive and how many of them were just restarted.
+fn check_instances(
+    instances: &mut [XrayInstance],
+    cfg: &ConfigGenerator,
+    restart: bool,
+) -> (usize, usize) {
+    let mut alive = 0;
+    let mut restarted = 0;
+
+    for inst in instances.iter_mut() {
+        if inst.is_running() {
+            alive += 1;
+        } else if !restart {
+            if !inst.left_down {
+                inst.left_down = true;
+                let status = match inst.process.try_wait() {
+                    Ok(Some(status)) => status.to_string(),
+                    _ => "unknown status".to_string(),
+                };
+                log::warn!(
+                    "xray-core on port {} exited ({}), leaving it down (--no-restart)",
+                    inst.port,
+                    status
+                );
+            }
+        } else {
+            log::warn!(
+                "Detected crashed xray-core on port {}. Attempting restart...",
+                inst.port
+            );
+            if let Err(e) = inst.restart(cfg) {
+                log::error!("Failed to restart xray-core on port {}: {}", inst.port, e);
+            } else {
+                restarted += 1;
+                alive += 1;
+            }
+        }
+    }
+
+    (alive, restarted)
+}
+
+/// `--monitor-probe`: where the monitor sends its SOCKS5 greetings.
+#[derive(Debug, Clone)]
+struct InboundProbe {
+    host: IpAddr,
+    auth: Option<InboundAuth>,
+}
+
+impl InboundProbe {
+    /// The greeting (plus login when the inbound requires one) that a worker starts with.
+    async fn greet(&self, port: u16) -> Result<()> {
+        timeout(PROBE_TIMEOUT, async {
+            let mut stream = TcpStream::connect((self.host, port)).await?;
+            perform_greeting(&mut stream, self.auth.as_ref()).await
+        })
+        .await
+        .context("SOCKS5 greeting timed out")?
+    }
+
+    /// Greets every inbound without holding the lock, then restarts the instances that are still
+    /// running but failed [`PROBE_FAILURES_BEFORE_RESTART`] greetings in a row.
+    async fn check(&self, instances: &Mutex<Vec<XrayInstance>>, cfg: &ConfigGenerator) {
+        let ports: Vec<u16> = instances
+            .lock()
+            .await
+            .iter()
+            .map(|inst| inst.port)
+            .collect();
+        let results = join_all(ports.iter().map(|&port| self.greet(port))).await;
+
+        let mut guard = instances.lock().await;
+        for (port, result) in ports.into_iter().zip(results) {
+            let Some(inst) = guard.iter_mut().find(|inst| inst.port == port) else {
+                continue;
+            };
+            let Err(err) = result else {
+                inst.probe_failures = 0;
+                continue;
+            };
+            inst.probe_failures += 1;
+            log::debug!("SOCKS5 probe of port {port} failed: {err:#}");
+            // A dead process is the crash check's job on the next pass.
+            if inst.probe_failures < PROBE_FAILURES_BEFORE_RESTART || !inst.is_running() {
+                continue;
+            }
+
+            log::warn!(
+                "xray-core on port {port} is running but its inbound stopped answering ({err:#}). Restarting..."
+            );
+            if let Err(e) = inst.terminate() {
+                log::error!("Failed to stop xray-core on port {port}: {e}");
+                continue;
+            }
+            if let Err(e) = inst.restart(cfg) {
+                log::error!("Failed to restart xray-core on port {port}: {e}");
+            }
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 enum TerminationStatus {
     Killed,
     AlreadyExited,
     RaceExited,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_next_free_port_skips_assigned() {
+        let port = TcpListener::bind(("127.0.0.1", 0))
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let assigned = HashSet::from([port]);
+
+        let localhost = IpAddr::from([127, 0, 0, 1]);
+        let found = ProcessManager::find_next_free_port(localhost, port, &assigned).unwrap();
+        assert_ne!(found, port);
+        assert!(found > port);
+    }
+
+    #[test]
+    fn test_port_probe_uses_listen_address() {
+        let listen = IpAddr::from([127, 0, 0, 2]);
+        let taken = TcpListener::bind((listen, 0)).unwrap();
+        let port = taken.local_addr().unwrap().port();
+
+        assert!(!ProcessManager::is_port_available(listen, port));
+        assert!(ProcessManager::is_port_available(
+            IpAddr::from([127, 0, 0, 1]),
+            port
+        ));
+    }
+
+    #[test]
+    fn test_no_restart_leaves_crashed_instance_down() {
+        let mut process = Command::new("true").spawn().unwrap();
+        process.wait().unwrap();
+        let mut instances = vec![XrayInstance {
+            port: 10808,
+            instance_idx: 0,
+            proxy_config: crate::parser::parse_proxy_url("vless://id@host:443").unwrap(),
+            process,
+            restarts: 0,
+            probe_failures: 0,
+            left_down: false,
+        }];
+        let cfg = ConfigGenerator::new(ConfigOptions::default()).unwrap();
+
+        assert_eq!(check_instances(&mut instances, &cfg, false), (0, 0));
+        assert!(instances[0].left_down);
+        assert_eq!(check_instances(&mut instances, &cfg, false), (0, 0));
+        assert_eq!(instances[0].restarts, 0);
+    }
+
+    #[tokio::test]
+    async fn test_inbound_probe_needs_a_socks_answer() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let socks = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let socks_port = socks.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (mut stream, _) = socks.accept().await.unwrap();
+            let mut greeting = [0u8; 3];
+            stream.read_exact(&mut greeting).await.unwrap();
+            stream.write_all(&[0x05, 0x00]).await.unwrap();
+        });
+        // Accepts the connection, then hangs up without a word.
+        let mute = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mute_port = mute.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            drop(mute.accept().await);
+        });
+
+        let probe = InboundProbe {
+            host: IpAddr::from([127, 0, 0, 1]),
+            auth: None,
+        };
+        assert!(probe.greet(socks_port).await.is_ok());
+        assert!(probe.greet(mute_port).await.is_err());
+    }
+}
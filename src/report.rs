@@ -0,0 +1,341 @@
+//! `--report`: a JSON summary of one run, written once the test ends or is interrupted.
+
+use crate::config::XrayConfig;
+use crate::process::{InstanceStatus, ProcessManager};
+use crate::stressor::{
+    LifetimeStats, PpsSummary, StressConfig, StressRunner, StressStats, Target, TargetSummary,
+};
+use anyhow::{Context, Result};
+use jiff::Timestamp;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// How the run ended, as recorded in the report.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RunOutcome {
+    /// Duration, deadline or a request/byte limit reached, or stopped from the dashboard
+    Completed,
+    /// Ctrl+C
+    Interrupted,
+    /// The stress runner returned an error
+    Failed,
+}
+
+/// Run parameters fixed before the first worker starts.
+#[derive(Debug, Clone, Serialize)]
+pub struct RunInfo {
+    pub mode: &'static str,
+    /// `--concurrency` as given; per instance or in total depending on `concurrency_mode`
+    pub concurrency: usize,
+    pub concurrency_mode: &'static str,
+    pub workers: usize,
+    pub duration_secs: Option<u64>,
+    pub deadline_secs: Option<u64>,
+    pub max_requests: Option<u64>,
+    pub max_bytes: Option<u64>,
+    pub seed: Option<u64>,
+    pub targets: Vec<String>,
+    pub proxies: usize,
+    pub proxy_ports: Vec<u16>,
+}
+
+impl RunInfo {
+    pub fn new(config: &StressConfig, workers: usize, proxies: usize) -> Self {
+        let mut targets: Vec<String> = config
+            .targets
+            .iter()
+            .map(|target| match target {
+                Target::Http(url) => url.clone(),
+                Target::Socket(target) => target.display(),
+            })
+            .collect();
+        targets.extend(
+            config
+                .replay
+                .iter()
+                .map(|request| format!("{} {}", request.method, request.url)),
+        );
+
+        Self {
+            mode: config.mode.as_str(),
+            concurrency: config.concurrency,
+            concurrency_mode: config.concurrency_mode.as_str(),
+            workers,
+            duration_secs: config.duration.map(|d| d.as_secs()),
+            deadline_secs: config.deadline.map(|d| d.as_secs()),
+            max_requests: config.max_requests,
+            max_bytes: config.max_bytes,
+            seed: config.seed,
+            targets,
+            proxies,
+            proxy_ports: config.proxy_ports.clone(),
+        }
+    }
+}
+
+/// Final counters, flattened from [`StressStats`].
+#[derive(Debug, Clone, Serialize)]
+pub struct ReportStats {
+    pub elapsed_secs: f64,
+    pub success_events: u64,
+    pub failure_events: u64,
+    pub failure_rate: f64,
+    pub bytes_transferred: u64,
+    pub mbps: f64,
+    pub packets_sent: u64,
+    pub retries: u64,
+    pub partial_downloads: u64,
+}
+
+impl From<&StressStats> for ReportStats {
+    fn from(stats: &StressStats) -> Self {
+        Self {
+            elapsed_secs: stats.elapsed().as_secs_f64(),
+            success_events: stats.success_events,
+            failure_events: stats.failure_events,
+            failure_rate: stats.failure_rate(),
+            bytes_transferred: stats.bytes_transferred,
+            mbps: stats.bytes_per_second() * 8.0 / (1000.0 * 1000.0),
+            packets_sent: stats.packets_sent,
+            retries: stats.retries,
+            partial_downloads: stats.partial_downloads,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RunReport {
+    /// RFC 3339 UTC timestamps
+    pub started_at: String,
+    pub finished_at: String,
+    pub outcome: RunOutcome,
+    pub config: RunInfo,
+    pub stats: ReportStats,
+    /// Failure counts by category, only categories that occurred
+    pub failures: BTreeMap<&'static str, u64>,
+    /// HTTP responses by status code
+    pub status_codes: BTreeMap<u16, u64>,
+    pub targets: Vec<TargetSummary>,
+    /// Spread of the per-interval packet rate, flood modes only
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pps: Option<PpsSummary>,
+    /// Times `--stall-restart` recycled all instances
+    pub stall_recycles: u64,
+    pub instances: Vec<InstanceStatus>,
+    /// `--report-configs`: each instance's generated xray config, keyed by port
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub configs: Option<BTreeMap<u16, XrayConfig>>,
+    /// `--state-file` totals across runs, this one included; `stats` stays per run
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lifetime: Option<LifetimeStats>,
+}
+
+impl RunReport {
+    /// Gathers the final numbers; call before `terminate_all` so the instances are still listed.
+    pub async fn collect(
+        info: &RunInfo,
+        started_at: Timestamp,
+        outcome: RunOutcome,
+        runner: &StressRunner,
+        processes: &ProcessManager,
+    ) -> Self {
+        let instances = processes.instance_statuses().await;
+        let mut report = Self::new(
+            info,
+            started_at,
+            Timestamp::now(),
+            outcome,
+            &runner.get_current_stats(),
+            runner.target_summaries(),
+            runner.status_counts(),
+            instances,
+        );
+        report.pps = runner.pps_summary();
+        report.stall_recycles = runner.stall_recycles();
+        report.lifetime = runner.lifetime_stats();
+        report
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        info: &RunInfo,
+        started_at: Timestamp,
+        finished_at: Timestamp,
+        outcome: RunOutcome,
+        stats: &StressStats,
+        targets: Vec<TargetSummary>,
+        status_codes: Vec<(u16, u64)>,
+        instances: Vec<InstanceStatus>,
+    ) -> Self {
+        Self {
+            started_at: format_timestamp(started_at),
+            finished_at: format_timestamp(finished_at),
+            outcome,
+            config: info.clone(),
+            stats: ReportStats::from(stats),
+            failures: stats
+                .failure_breakdown()
+                .into_iter()
+                .map(|(kind, count)| (kind.as_str(), count))
+                .collect(),
+            status_codes: status_codes.into_iter().collect(),
+            targets,
+            pps: None,
+            stall_recycles: 0,
+            instances,
+            configs: None,
+            lifetime: None,
+        }
+    }
+
+    /// Writes through a temporary file and a rename, so readers never see a half-written report.
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(self).context("Failed to serialize report")?;
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        fs::write(&tmp, json + "\n")
+            .with_context(|| format!("Failed to write report to {}", path.display()))?;
+        fs::rename(&tmp, path)
+            .with_context(|| format!("Failed to replace report {}", path.display()))
+    }
+}
+
+/// `--report` destination plus what is known about the run before it starts.
+#[derive(Debug, Clone)]
+pub struct ReportSpec {
+    pub path: PathBuf,
+    pub info: RunInfo,
+    pub started_at: Timestamp,
+    /// Embed the xray configs (`--report-configs`)
+    pub include_configs: bool,
+}
+
+impl ReportSpec {
+    /// Call right before the run starts; that moment becomes `started_at`.
+    pub fn new(path: PathBuf, info: RunInfo, include_configs: bool) -> Self {
+        Self {
+            path,
+            info,
+            started_at: Timestamp::now(),
+            include_configs,
+        }
+    }
+
+    /// Collects and writes the report, logging instead of failing so shutdown carries on.
+    pub async fn write(
+        &self,
+        outcome: RunOutcome,
+        runner: &StressRunner,
+        processes: &ProcessManager,
+    ) {
+        let mut report =
+            RunReport::collect(&self.info, self.started_at, outcome, runner, processes).await;
+        if self.include_configs {
+            match processes.instance_configs().await {
+                Ok(configs) => report.configs = Some(configs),
+                Err(err) => log::warn!("Leaving xray configs out of the report: {err:#}"),
+            }
+        }
+        match report.write(&self.path) {
+            Ok(()) => log::info!("Report written to {}", self.path.display()),
+            Err(err) => log::error!("{err:#}"),
+        }
+    }
+}
+
+/// Millisecond precision, e.g. `2025-03-14T09:26:53.589Z`.
+fn format_timestamp(timestamp: Timestamp) -> String {
+    format!("{timestamp:.3}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stressor::{FailureKind, SocketTarget};
+    use std::time::Duration;
+
+    #[test]
+    fn test_report_serializes_run_and_breakdowns() {
+        let config = StressConfig {
+            mode: crate::cli::Mode::TcpFlood,
+            targets: vec![Target::Socket(SocketTarget {
+                host: "example.com".to_string(),
+                port: 443,
+            })],
+            concurrency: 4,
+            duration: Some(Duration::from_secs(30)),
+            proxy_ports: vec![10801, 10802],
+            ..Default::default()
+        };
+        let mut stats = StressStats::new();
+        stats.success_events = 9;
+        stats.failure_events = 1;
+        stats.failures_by_kind[FailureKind::Timeout.index()] = 1;
+        let started_at: Timestamp = "2025-03-14T09:26:53.5891Z".parse().unwrap();
+        let finished_at = started_at + jiff::SignedDuration::from_secs(30);
+
+        let report = RunReport::new(
+            &RunInfo::new(&config, 8, 3),
+            started_at,
+            finished_at,
+            RunOutcome::Interrupted,
+            &stats,
+            Vec::new(),
+            vec![(200, 7), (503, 2)],
+            vec![InstanceStatus {
+                port: 10801,
+                pid: 42,
+                alive: true,
+                restarts: 2,
+                proxy: Some("Germany node 1".to_string()),
+            }],
+        );
+        let json: serde_json::Value =
+            serde_json::from_str(&serde_json::to_string(&report).unwrap()).unwrap();
+
+        assert_eq!(json["started_at"], "2025-03-14T09:26:53.589Z");
+        assert_eq!(json["finished_at"], "2025-03-14T09:27:23.589Z");
+        assert_eq!(json["outcome"], "interrupted");
+        assert_eq!(json["config"]["mode"], "tcp-flood");
+        assert_eq!(json["config"]["workers"], 8);
+        assert_eq!(json["config"]["proxies"], 3);
+        assert_eq!(json["config"]["duration_secs"], 30);
+        assert_eq!(json["config"]["targets"][0], "example.com:443");
+        assert_eq!(json["stats"]["failure_rate"], 10.0);
+        assert_eq!(json["failures"]["timeout"], 1);
+        assert_eq!(json["status_codes"]["503"], 2);
+        assert_eq!(json["stall_recycles"], 0);
+        assert_eq!(json["instances"][0]["restarts"], 2);
+        assert_eq!(json["instances"][0]["proxy"], "Germany node 1");
+        assert!(json.get("configs").is_none());
+    }
+
+    #[test]
+    fn test_report_embeds_configs_by_port() {
+        let config = StressConfig::default();
+        let proxy = crate::parser::parse_proxy_url("vless://id@host:443?type=tcp").unwrap();
+        let rendered = crate::config::ConfigOptions::default()
+            .build_config_json(&proxy, 10801, 0)
+            .unwrap();
+        let xray_config: XrayConfig = serde_json::from_str(&rendered).unwrap();
+        let mut report = RunReport::new(
+            &RunInfo::new(&config, 1, 1),
+            Timestamp::UNIX_EPOCH,
+            Timestamp::UNIX_EPOCH,
+            RunOutcome::Completed,
+            &StressStats::new(),
+            Vec::new(),
+            Vec::new(),
+            Vec::new(),
+        );
+        report.configs = Some(BTreeMap::from([(10801, xray_config)]));
+
+        let json: serde_json::Value = serde_json::to_value(&report).unwrap();
+        let embedded = &json["configs"]["10801"];
+        assert_eq!(embedded["inbounds"][0]["port"], 10801);
+        assert_eq!(embedded["outbounds"][0]["protocol"], "vless");
+    }
+}
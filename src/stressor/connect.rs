@@ -0,0 +1,158 @@
+use super::failure::classify_socks;
+use super::{
+    ConcurrencyGate, RunLimits, SharedCounters, ShutdownSignal, SocketTarget, StressConfig,
+    apply_jitter, socks_connect, supervise_workers, worker_rng, worker_targets,
+};
+use crate::config::InboundAuth;
+use anyhow::{Result, anyhow};
+use rand::rngs::StdRng;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+use tokio::time::sleep;
+
+pub async fn run(
+    config: &StressConfig,
+    counters: SharedCounters,
+    shutdown: ShutdownSignal,
+    gate: ConcurrencyGate,
+    end_time: Option<Instant>,
+) -> Result<()> {
+    let targets = config.socket_targets();
+    if targets.is_empty() {
+        return Err(anyhow!("No host:port targets configured for connect mode"));
+    }
+    let targets = Arc::new(targets);
+
+    let connect_interval = config.send_interval();
+
+    let mut handles: Vec<JoinHandle<()>> = Vec::new();
+    for (idx, port) in config.proxy_ports.iter().enumerate() {
+        for worker in 0..config.instance_workers(idx) {
+            let worker_id = idx * 10_000 + worker;
+            let params = ConnectWorkerParams {
+                worker_id,
+                slot: worker,
+                proxy_host: config.proxy_host,
+                proxy_port: *port,
+                targets: worker_targets(&targets, worker_id, config.shuffle_targets, config.seed),
+                connect_interval,
+                end_time,
+                limits: config.limits(),
+                jitter: config.jitter,
+                rng: worker_rng(config.seed, worker_id),
+                counters: counters.clone(),
+                auth: config.inbound_auth.clone(),
+                shutdown: shutdown.clone(),
+                gate: gate.clone(),
+            };
+            let handle = tokio::spawn(async move {
+                connect_worker_loop(params).await;
+            });
+            handles.push(handle);
+        }
+    }
+
+    supervise_workers(
+        handles,
+        end_time,
+        config.ramp_down,
+        &counters,
+        config.limits(),
+        &shutdown,
+    )
+    .await
+}
+
+struct ConnectWorkerParams {
+    worker_id: usize,
+    slot: usize,
+    proxy_host: IpAddr,
+    proxy_port: u16,
+    targets: Arc<Vec<SocketTarget>>,
+    connect_interval: Option<Duration>,
+    end_time: Option<Instant>,
+    limits: RunLimits,
+    jitter: Option<(Duration, Duration)>,
+    rng: StdRng,
+    counters: SharedCounters,
+    auth: Option<InboundAuth>,
+    shutdown: ShutdownSignal,
+    gate: ConcurrencyGate,
+}
+
+async fn connect_worker_loop(mut params: ConnectWorkerParams) {
+    loop {
+        for target in params.targets.iter() {
+            if let Some(end) = params.end_time
+                && Instant::now() >= end
+            {
+                log::debug!(
+                    "Connect worker {} finished due to duration limit",
+                    params.worker_id
+                );
+                return;
+            }
+
+            if params.counters.limit_reached(params.limits) {
+                log::debug!(
+                    "Connect worker {} finished due to request or byte limit",
+                    params.worker_id
+                );
+                return;
+            }
+
+            if params.shutdown.is_triggered() {
+                log::debug!(
+                    "Connect worker {} finished due to shutdown",
+                    params.worker_id
+                );
+                return;
+            }
+
+            if params.counters.down_ports.contains(params.proxy_port) {
+                log::debug!(
+                    "Connect worker {} finished, xray-core on port {} is down",
+                    params.worker_id,
+                    params.proxy_port
+                );
+                return;
+            }
+
+            if !params.gate.wait_for_slot(params.slot).await {
+                return;
+            }
+
+            let proxy = SocketAddr::new(params.proxy_host, params.proxy_port);
+            let started = Instant::now();
+            let connection = params.counters.active_connections.open();
+            let result = socks_connect(proxy, target, params.auth.as_ref()).await;
+            drop(connection);
+            match result {
+                Ok(stream) => {
+                    params
+                        .counters
+                        .record_connect(&target.display(), started.elapsed());
+                    drop(stream);
+                }
+                Err(err) => {
+                    log::debug!(
+                        "Connect worker {} failed to connect via proxy {} -> {}: {}",
+                        params.worker_id,
+                        params.proxy_port,
+                        target.display(),
+                        err
+                    );
+                    params.counters.record_failure(classify_socks(&err));
+                    sleep(Duration::from_millis(200)).await;
+                }
+            }
+
+            if let Some(interval) = params.connect_interval {
+                sleep(interval).await;
+            }
+            apply_jitter(params.jitter, &mut params.rng).await;
+        }
+    }
+}
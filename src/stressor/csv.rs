@@ -0,0 +1,63 @@
+use anyhow::{Context, Result};
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const CSV_HEADER: &str =
+    "timestamp,elapsed_secs,bytes_transferred,bytes_delta,mbps,pps,success,failure";
+
+pub struct ThroughputSample {
+    pub elapsed_secs: f64,
+    pub bytes_transferred: u64,
+    pub bytes_delta: u64,
+    pub mbps: f64,
+    pub pps: f64,
+    pub success: u64,
+    pub failure: u64,
+}
+
+pub struct CsvLogger {
+    writer: BufWriter<File>,
+}
+
+impl CsvLogger {
+    pub fn open(path: &Path) -> Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Failed to open CSV log {}", path.display()))?;
+        let is_empty = file.metadata().map(|m| m.len() == 0).unwrap_or(true);
+
+        let mut writer = BufWriter::new(file);
+        if is_empty {
+            writeln!(writer, "{CSV_HEADER}").context("Failed to write CSV header")?;
+            writer.flush().context("Failed to flush CSV log")?;
+        }
+
+        Ok(Self { writer })
+    }
+
+    pub fn write_sample(&mut self, sample: &ThroughputSample) -> Result<()> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs_f64())
+            .unwrap_or_default();
+
+        writeln!(
+            self.writer,
+            "{:.3},{:.3},{},{},{:.3},{:.1},{},{}",
+            timestamp,
+            sample.elapsed_secs,
+            sample.bytes_transferred,
+            sample.bytes_delta,
+            sample.mbps,
+            sample.pps,
+            sample.success,
+            sample.failure
+        )
+        .context("Failed to write CSV sample")?;
+        self.writer.flush().context("Failed to flush CSV log")
+    }
+}
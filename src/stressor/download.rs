@@ -1,11 +1,25 @@
-use super::{SharedCounters, StressConfig, supervise_workers};
+use super::failure::{FailureKind, classify_reqwest};
+use super::tls::TlsProbe;
+use super::{
+    ConcurrencyGate, GATE_POLL, RunLimits, SharedCounters, ShutdownSignal, StressConfig,
+    TargetCursor, WarmupTicket, apply_jitter, seeded_rng, supervise_workers, worker_rng,
+    worker_targets,
+};
+use crate::cli::{AcceptEncoding, InboundProtocol};
 use anyhow::{Context, Result, anyhow};
 use futures::StreamExt;
-use rand::{Rng, rng};
+use futures::future::join_all;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use reqwest::redirect::Policy;
 use reqwest::{Client, Proxy};
+use std::net::SocketAddr;
 use std::sync::Arc;
+use std::sync::atomic::Ordering;
 use std::time::{Duration, Instant};
-use tokio::task::JoinHandle;
+use tokio::task::{JoinHandle, JoinSet};
+use tokio::time::sleep;
+use url::Url;
 
 pub const DEFAULT_HTTP_TARGETS: &[&str] = &[
     "http://speedtest.tele2.net/1GB.zip",
@@ -44,6 +58,11 @@ pub const DEFAULT_HTTP_TARGETS: &[&str] = &[
     "https://speed.cloudflare.com/__down?bytes=10000000",
 ];
 
+const RETRY_BACKOFF_BASE: Duration = Duration::from_millis(200);
+const RETRY_BACKOFF_MAX: Duration = Duration::from_millis(3200);
+/// `--tls-timing` samples the first https download of a worker and every this many after it.
+const TLS_SAMPLE_EVERY: u32 = 20;
+
 const USER_AGENTS: &[&str] = &[
     "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/91.0.4472.124 Safari/537.36",
     "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/91.0.4472.124 Safari/537.36",
@@ -55,24 +74,127 @@ const USER_AGENTS: &[&str] = &[
 pub async fn run(
     config: &StressConfig,
     counters: SharedCounters,
-    start_time: Instant,
+    shutdown: ShutdownSignal,
+    gate: ConcurrencyGate,
+    end_time: Option<Instant>,
 ) -> Result<()> {
     let targets = config.http_targets();
     if targets.is_empty() {
         return Err(anyhow!("No HTTP targets configured for download mode"));
     }
 
+    let clients = build_clients(config)?;
+    let targets = Arc::new(parse_targets(&targets)?);
+    let limits = config.limits();
+    let chunk_bytes = config.chunk_bytes;
+    let retries = config.retries;
+    let check_length = config.check_length;
+    let accept_encoding = config.accept_encoding;
+    let fail_on_status = config.fail_on_status;
+    let jitter = config.jitter;
+    let mut handles: Vec<JoinHandle<()>> = Vec::new();
+
+    for (idx, client) in clients.into_iter().enumerate() {
+        let tls_probe = if config.tls_timing {
+            let inbound = SocketAddr::new(config.proxy_host, config.proxy_ports[idx]);
+            let auth = config.inbound_auth.clone();
+            Some(TlsProbe::new(inbound, auth, config.verify_certs)?)
+        } else {
+            None
+        };
+        for worker in 0..config.instance_workers(idx) {
+            let worker_id = idx * 10_000 + worker;
+            let client_clone = client.clone();
+            let targets_clone =
+                worker_targets(&targets, worker_id, config.shuffle_targets, config.seed);
+            let counters_clone = counters.clone();
+            let shutdown_clone = shutdown.clone();
+            let gate_clone = gate.clone();
+            let params = WorkerParams {
+                thread_id: worker_id,
+                slot: worker,
+                proxy_port: config.proxy_ports[idx],
+                client: client_clone,
+                targets: targets_clone,
+                end_time,
+                limits,
+                chunk_bytes,
+                retries,
+                check_length,
+                accept_encoding,
+                fail_on_status,
+                jitter,
+                requests_in_flight: config.requests_in_flight,
+                tls_probe: tls_probe.clone(),
+                counters: counters_clone,
+                shutdown: shutdown_clone,
+                gate: gate_clone,
+            };
+            let rng = worker_rng(config.seed, worker_id);
+            let cursor = TargetCursor::new(config.shuffle_targets);
+            let warmup = gate.warmup_ticket(worker);
+            let handle = tokio::spawn(async move {
+                http_worker_loop(params, rng, cursor, warmup).await;
+            });
+            handles.push(handle);
+        }
+    }
+
+    supervise_workers(
+        handles,
+        end_time,
+        config.ramp_down,
+        &counters,
+        config.limits(),
+        &shutdown,
+    )
+    .await
+}
+
+/// One HTTP client per local proxy port, shared by the download and replay modes.
+pub(super) fn build_clients(config: &StressConfig) -> Result<Vec<Client>> {
     let mut clients = Vec::new();
-    for &port in &config.proxy_ports {
-        let proxy = Proxy::all(format!("socks5://127.0.0.1:{port}"))
-            .context("Failed to configure SOCKS5 proxy")?;
+    for (idx, &port) in config.proxy_ports.iter().enumerate() {
+        let inbound = SocketAddr::new(config.proxy_host, port);
+        let proxy_url = match config.inbound {
+            InboundProtocol::Socks => format!("socks5://{inbound}"),
+            InboundProtocol::Http => format!("http://{inbound}"),
+        };
+        let mut proxy_url = Url::parse(&proxy_url).context("Invalid local proxy URL")?;
+        if let Some(auth) = &config.inbound_auth {
+            proxy_url
+                .set_username(&auth.user)
+                .and_then(|()| proxy_url.set_password(Some(&auth.pass)))
+                .map_err(|()| anyhow!("Failed to set local proxy credentials"))?;
+        }
+        let proxy = Proxy::all(proxy_url).context("Failed to configure local proxy")?;
+
+        let redirect_policy = if config.follow_redirects {
+            Policy::default()
+        } else {
+            Policy::none()
+        };
+
+        // The warm-up connections are only worth making if the pool keeps all of them.
+        let pool_size = if config.connect_only_first {
+            let warmed = config.instance_workers(idx) * config.requests_in_flight;
+            config.pool_size.max(warmed)
+        } else {
+            config.pool_size
+        };
 
         let client = Client::builder()
             .proxy(proxy)
+            .redirect(redirect_policy)
             .connect_timeout(Duration::from_secs(10))
             .timeout(Duration::from_secs(600))
-            .danger_accept_invalid_certs(true)
+            .danger_accept_invalid_certs(!config.verify_certs)
             .tcp_keepalive(Duration::from_secs(60))
+            .pool_max_idle_per_host(pool_size)
+            .local_address(config.local_address)
+            .gzip(config.accept_encoding.decompresses())
+            .deflate(config.accept_encoding.decompresses())
+            .brotli(config.accept_encoding.decompresses())
             .build()
             .context("Failed to create HTTP client")?;
 
@@ -82,54 +204,115 @@ pub async fn run(
     if clients.is_empty() {
         return Err(anyhow!("No HTTP clients available"));
     }
+    Ok(clients)
+}
 
-    let targets = Arc::new(targets);
-    let end_time = config.duration.map(|d| start_time + d);
-    let mut handles: Vec<JoinHandle<()>> = Vec::new();
-
-    for (idx, client) in clients.into_iter().enumerate() {
-        for worker in 0..config.concurrency {
-            let worker_id = idx * 10_000 + worker;
-            let client_clone = client.clone();
-            let targets_clone = Arc::clone(&targets);
-            let counters_clone = counters.clone();
-            let handle = tokio::spawn(async move {
-                match build_requests(&client_clone, &targets_clone) {
-                    Ok(requests) => {
-                        let params = WorkerParams {
-                            thread_id: worker_id,
-                            client: client_clone,
-                            requests: Arc::new(requests),
-                            end_time,
-                            counters: counters_clone,
-                        };
-                        http_worker_loop(params).await;
-                    }
-                    Err(err) => {
-                        log::error!("Failed to build requests: {err}");
-                    }
-                }
-            });
-            handles.push(handle);
+/// `--connect-only-first` for the HTTP modes: `connections` HEAD requests to `url` at once, which
+/// leave their connections idle in the client's pool. True if every one got an answer.
+pub(super) async fn warm_up(client: &Client, url: &Url, connections: usize) -> bool {
+    let requests = (0..connections).map(|_| client.head(url.clone()).send());
+    let mut connected = true;
+    for result in join_all(requests).await {
+        if let Err(err) = result {
+            log::debug!("Warm-up request to {url} failed: {err}");
+            connected = false;
         }
     }
+    connected
+}
+
+/// One-shot download of `url` through the first of `config.proxy_ports`, used by `herscat check`.
+/// Returns the body size; non-2xx answers and truncated bodies count as failures.
+pub async fn fetch_once(config: &StressConfig, url: &Url) -> Result<u64, FailureKind> {
+    let client = build_clients(config)
+        .map_err(|err| {
+            log::warn!("Failed to create HTTP client: {err:#}");
+            FailureKind::Other
+        })?
+        .swap_remove(0);
+    let shutdown = ShutdownSignal::default();
+    let params = WorkerParams {
+        thread_id: 0,
+        slot: 0,
+        proxy_port: config.proxy_ports[0],
+        client,
+        targets: Arc::new(vec![url.clone()]),
+        end_time: None,
+        limits: RunLimits::default(),
+        chunk_bytes: None,
+        retries: 0,
+        check_length: true,
+        accept_encoding: config.accept_encoding,
+        fail_on_status: true,
+        jitter: None,
+        requests_in_flight: 1,
+        tls_probe: None,
+        counters: SharedCounters::new(),
+        gate: ConcurrencyGate::new(1, shutdown.clone()),
+        shutdown,
+    };
 
-    supervise_workers(handles, end_time).await
+    let encoding = params.accept_encoding;
+    let mut rng = seeded_rng(config.seed);
+    let request = build_request(&params.client, url, None, encoding, &mut rng).map_err(|err| {
+        log::warn!("Failed to build request for {url}: {err}");
+        FailureKind::Other
+    })?;
+    execute_request(&params, request).await?;
+
+    if params.counters.partial_downloads.load(Ordering::Relaxed) > 0 {
+        return Err(FailureKind::Stream);
+    }
+    Ok(params.counters.bytes_transferred.load(Ordering::Relaxed))
 }
 
 struct WorkerParams {
     thread_id: usize,
+    slot: usize,
+    proxy_port: u16,
     client: Client,
-    requests: Arc<Vec<reqwest::Request>>,
+    targets: Arc<Vec<Url>>,
     end_time: Option<Instant>,
+    limits: RunLimits,
+    chunk_bytes: Option<u64>,
+    retries: u32,
+    check_length: bool,
+    accept_encoding: AcceptEncoding,
+    fail_on_status: bool,
+    jitter: Option<(Duration, Duration)>,
+    /// `--requests-in-flight`: downloads this worker keeps going at once
+    requests_in_flight: usize,
+    tls_probe: Option<TlsProbe>,
     counters: SharedCounters,
+    shutdown: ShutdownSignal,
+    gate: ConcurrencyGate,
 }
 
-async fn http_worker_loop(params: WorkerParams) {
-    let req_len = params.requests.len();
+/// Keeps up to `requests_in_flight` downloads going, each followed by its own jitter pause.
+/// Aborting the worker (end of `--ramp-down`) drops the set and with it the downloads.
+async fn http_worker_loop(
+    params: WorkerParams,
+    mut rng: StdRng,
+    mut cursor: TargetCursor,
+    mut warmup: WarmupTicket,
+) {
+    if warmup.is_pending() {
+        let url = &params.targets[0];
+        let connected = warm_up(&params.client, url, params.requests_in_flight).await;
+        warmup.arrive(connected).await;
+    }
+
+    let params = Arc::new(params);
+    let target_len = params.targets.len();
     let thread_id = params.thread_id;
+    let mut in_flight = JoinSet::new();
+    let mut https_downloads = 0u32;
 
     loop {
+        while in_flight.len() >= params.requests_in_flight {
+            in_flight.join_next().await;
+        }
+
         if let Some(end) = params.end_time
             && Instant::now() >= end
         {
@@ -137,76 +320,443 @@ async fn http_worker_loop(params: WorkerParams) {
             break;
         }
 
-        let idx = rng().random_range(0..req_len);
-        let req = match params.requests[idx].try_clone() {
-            Some(req) => req,
-            None => {
-                log::warn!("Failed to clone HTTP request (reqwest dropped body)");
-                continue;
+        if params.counters.limit_reached(params.limits) {
+            log::debug!("HTTP worker {thread_id} stopping due to request or byte limit");
+            break;
+        }
+
+        if params.shutdown.is_triggered() {
+            log::debug!("HTTP worker {thread_id} stopping due to shutdown");
+            break;
+        }
+
+        if params.counters.down_ports.contains(params.proxy_port) {
+            log::debug!(
+                "HTTP worker {thread_id} stopping, xray-core on port {} is down",
+                params.proxy_port
+            );
+            break;
+        }
+
+        if !params.gate.wait_for_slot(params.slot).await {
+            break;
+        }
+
+        let idx = cursor.pick(target_len, &mut rng);
+        if let Some(probe) = &params.tls_probe
+            && params.targets[idx].scheme() == "https"
+        {
+            if https_downloads.is_multiple_of(TLS_SAMPLE_EVERY) {
+                time_tls_handshake(probe, &params.targets[idx], &params).await;
+            }
+            https_downloads = https_downloads.wrapping_add(1);
+        }
+        let params = Arc::clone(&params);
+        let mut rng = StdRng::from_rng(&mut rng);
+        in_flight.spawn(async move {
+            fetch_with_retries(&params, idx, &mut rng).await;
+            apply_jitter(params.jitter, &mut rng).await;
+        });
+    }
+
+    while in_flight.join_next().await.is_some() {}
+    log::debug!("HTTP worker {thread_id} completed");
+}
+
+/// Downloads target `idx`, retrying failures up to `--retries` times before counting one failure.
+async fn fetch_with_retries(params: &WorkerParams, idx: usize, rng: &mut StdRng) {
+    let url = &params.targets[idx];
+    let target = url.to_string();
+    let mut attempt = 0u32;
+
+    loop {
+        let request = build_request(
+            &params.client,
+            url,
+            params.chunk_bytes,
+            params.accept_encoding,
+            rng,
+        );
+        let request = match request {
+            Ok(request) => request,
+            Err(err) => {
+                log::warn!("Failed to build request for {target}: {err}");
+                return;
             }
         };
 
-        execute_request(&params.client, req, &params.counters).await;
+        let Err(kind) = execute_request(params, request).await else {
+            return;
+        };
+
+        if attempt >= params.retries || params.shutdown.is_draining() {
+            params.counters.record_failure(kind);
+            params.counters.targets.record_failure(&target);
+            return;
+        }
+
+        attempt += 1;
+        params.counters.record_retry();
+        let backoff = retry_backoff(attempt);
+        log::debug!(
+            "Retrying {} ({}/{}) in {:?} after {} failure",
+            target,
+            attempt,
+            params.retries,
+            backoff,
+            kind.as_str()
+        );
+        sleep(backoff).await;
     }
+}
 
-    log::debug!("HTTP worker {thread_id} completed");
+/// Diagnostic only: a failed probe is logged but never counted against the run.
+/// Gives up on the probe as soon as the run starts to wind down.
+async fn time_tls_handshake(probe: &TlsProbe, url: &Url, params: &WorkerParams) {
+    let stopping = async {
+        while !params.shutdown.is_draining() {
+            sleep(GATE_POLL).await;
+        }
+    };
+    tokio::select! {
+        result = probe.handshake(url) => match result {
+            Ok((target, elapsed)) => params.counters.tls_handshakes.record(&target, elapsed),
+            Err(err) => log::debug!("TLS timing probe to {url} failed: {err:#}"),
+        },
+        () = stopping => {}
+    }
 }
 
-async fn execute_request(client: &Client, request: reqwest::Request, counters: &SharedCounters) {
+fn retry_backoff(attempt: u32) -> Duration {
+    RETRY_BACKOFF_BASE
+        .saturating_mul(1 << attempt.saturating_sub(1).min(4))
+        .min(RETRY_BACKOFF_MAX)
+}
+
+async fn execute_request(
+    params: &WorkerParams,
+    request: reqwest::Request,
+) -> Result<(), FailureKind> {
+    let counters = &params.counters;
     let target = request.url().to_string();
-    match client.execute(request).await {
+    let _connection = counters.active_connections.open();
+    match params.client.execute(request).await {
         Ok(response) => {
+            if response.status().is_redirection() {
+                log::debug!(
+                    "{} answered {} (redirects disabled)",
+                    target,
+                    response.status()
+                );
+            }
+            let status = response.status();
+            counters.status_codes.record(status.as_u16());
+            if params.fail_on_status && !status.is_success() {
+                log::debug!("{target} answered {status}");
+                return Err(FailureKind::Status);
+            }
             counters.record_success();
+            let expected_bytes = response.content_length();
             let mut stream = response.bytes_stream();
             let mut total_bytes = 0u64;
+            let mut stream_error = None;
+            let mut stopped_early = false;
 
             while let Some(chunk_result) = stream.next().await {
                 match chunk_result {
                     Ok(chunk) => {
+                        // The chunk has already been transferred, so it counts before any stop.
                         let chunk_size = chunk.len() as u64;
                         total_bytes += chunk_size;
                         counters.record_bytes(chunk_size);
+
+                        // Servers that ignore Range still only get read up to the cap.
+                        if params.chunk_bytes.is_some_and(|cap| total_bytes >= cap)
+                            || params.shutdown.is_triggered()
+                            || counters.byte_limit_reached(params.limits.max_bytes)
+                        {
+                            stopped_early = true;
+                            break;
+                        }
                     }
                     Err(err) => {
                         log::debug!(
-                            "Stream error from {} after {}MB: {}",
+                            "Stream error from {} after {} MiB: {}",
                             target,
                             total_bytes / (1024 * 1024),
                             err
                         );
-                        counters.record_failure();
+                        stream_error = Some(classify_reqwest(&err));
                         break;
                     }
                 }
             }
 
+            match stream_error {
+                Some(_) => counters.targets.record_bytes(&target, total_bytes),
+                None => counters.targets.record_success(&target, total_bytes),
+            }
+
+            // A clean close before Content-Length bytes means the transfer was truncated.
+            if params.check_length
+                && stream_error.is_none()
+                && !stopped_early
+                && let Some(expected) = expected_bytes
+                && total_bytes != expected
+            {
+                log::debug!(
+                    "Partial download from {}: got {} of {} bytes",
+                    target,
+                    total_bytes,
+                    expected
+                );
+                counters.record_partial();
+            }
+
             if total_bytes > 0 {
                 log::debug!(
-                    "Completed download from {}: {}MB total",
+                    "Completed download from {}: {} MiB total",
                     target,
                     total_bytes / (1024 * 1024)
                 );
             }
+
+            stream_error.map_or(Ok(()), Err)
         }
         Err(err) => {
             log::debug!("Connection failed to {target}: {err}");
-            counters.record_failure();
+            Err(classify_reqwest(&err))
         }
     }
 }
 
-fn build_requests(client: &Client, targets: &[String]) -> Result<Vec<reqwest::Request>> {
-    let mut requests = Vec::with_capacity(targets.len());
+fn parse_targets(targets: &[String]) -> Result<Vec<Url>> {
+    targets
+        .iter()
+        .map(|target| {
+            Url::parse(target).with_context(|| format!("Invalid download target {target}"))
+        })
+        .collect()
+}
 
-    for target in targets {
-        let user_agent = USER_AGENTS[rng().random_range(0..USER_AGENTS.len())];
-        let req = client
-            .get(target)
-            .header("User-Agent", user_agent)
-            .build()
-            .with_context(|| format!("Failed to build request for {target}"))?;
-        requests.push(req);
+/// Builds a fresh request for every attempt so each one carries a newly picked User-Agent.
+fn build_request(
+    client: &Client,
+    url: &Url,
+    chunk_bytes: Option<u64>,
+    accept_encoding: AcceptEncoding,
+    rng: &mut StdRng,
+) -> reqwest::Result<reqwest::Request> {
+    let mut builder = client
+        .get(url.clone())
+        .header("User-Agent", pick_user_agent(rng));
+    if let Some(chunk) = chunk_bytes {
+        builder = builder.header("Range", format!("bytes=0-{}", chunk - 1));
+    }
+    if let Some(encoding) = accept_encoding.header_value() {
+        builder = builder.header("Accept-Encoding", encoding);
+    }
+    builder.build()
+}
+
+fn pick_user_agent(rng: &mut StdRng) -> &'static str {
+    USER_AGENTS[rng.random_range(0..USER_AGENTS.len())]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Plays a local HTTP proxy that answers every request itself with `response`.
+    async fn serve_once(response: &'static [u8]) -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0u8; 1024];
+            while !request.ends_with(b"\r\n\r\n") {
+                let n = stream.read(&mut buf).await.unwrap();
+                request.extend_from_slice(&buf[..n]);
+            }
+            stream.write_all(response).await.unwrap();
+        });
+        port
     }
 
-    Ok(requests)
+    #[tokio::test]
+    async fn test_fetch_once_reports_body_size_and_status_failures() {
+        let url = Url::parse("http://check.example/__down?bytes=5").unwrap();
+        let config = |port| StressConfig {
+            proxy_ports: vec![port],
+            inbound: InboundProtocol::Http,
+            ..StressConfig::default()
+        };
+
+        let port = serve_once(b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello").await;
+        assert_eq!(fetch_once(&config(port), &url).await, Ok(5));
+
+        let port = serve_once(b"HTTP/1.1 503 Unavailable\r\nContent-Length: 0\r\n\r\n").await;
+        assert_eq!(
+            fetch_once(&config(port), &url).await,
+            Err(FailureKind::Status)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_chunk_received_during_shutdown_is_counted() {
+        let port = serve_once(b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello").await;
+        let config = StressConfig {
+            proxy_ports: vec![port],
+            inbound: InboundProtocol::Http,
+            ..StressConfig::default()
+        };
+        let shutdown = ShutdownSignal::default();
+        let params = WorkerParams {
+            thread_id: 0,
+            slot: 0,
+            proxy_port: port,
+            client: build_clients(&config).unwrap().swap_remove(0),
+            targets: Arc::new(Vec::new()),
+            end_time: None,
+            limits: RunLimits::default(),
+            chunk_bytes: None,
+            retries: 0,
+            check_length: true,
+            accept_encoding: AcceptEncoding::Identity,
+            fail_on_status: true,
+            jitter: None,
+            requests_in_flight: 1,
+            tls_probe: None,
+            counters: SharedCounters::new(),
+            gate: ConcurrencyGate::new(1, shutdown.clone()),
+            shutdown,
+        };
+        params.shutdown.trigger();
+
+        let url = Url::parse("http://check.example/hello").unwrap();
+        let request = params.client.get(url).build().unwrap();
+        execute_request(&params, request).await.unwrap();
+        assert_eq!(params.counters.bytes_transferred.load(Ordering::Relaxed), 5);
+    }
+
+    #[tokio::test]
+    async fn test_requests_in_flight_overlap_downloads() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                tokio::spawn(async move {
+                    let mut buf = [0u8; 1024];
+                    let _ = stream.read(&mut buf).await;
+                    sleep(Duration::from_millis(200)).await;
+                    let response = b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello";
+                    let _ = stream.write_all(response).await;
+                });
+            }
+        });
+        let config = StressConfig {
+            targets: vec![crate::stressor::Target::Http(
+                "http://slow.example/".to_string(),
+            )],
+            concurrency: 1,
+            requests_in_flight: 3,
+            max_requests: Some(3),
+            proxy_ports: vec![port],
+            inbound: InboundProtocol::Http,
+            ..StressConfig::default()
+        };
+        let counters = SharedCounters::new();
+        let shutdown = ShutdownSignal::default();
+        let gate = ConcurrencyGate::new(1, shutdown.clone());
+
+        run(&config, counters.clone(), shutdown, gate, None)
+            .await
+            .unwrap();
+        assert_eq!(counters.success_events.load(Ordering::Relaxed), 3);
+        assert_eq!(counters.active_connections.peak(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_counted_bytes_follow_accept_encoding() {
+        const GZIP_RESPONSE: &[u8] = b"HTTP/1.1 200 OK\r\nContent-Encoding: gzip\r\n\
+            Content-Length: 28\r\n\r\n\
+            \x1f\x8b\x08\x00\x00\x00\x00\x00\x02\x03\xcb\x48\xcd\xc9\xc9\x57\xc8\x40\
+            \x27\x01\xe3\x51\x3d\x8d\x17\x00\x00\x00";
+        let url = Url::parse("http://check.example/hello.gz").unwrap();
+        let config = |port, accept_encoding| StressConfig {
+            proxy_ports: vec![port],
+            inbound: InboundProtocol::Http,
+            accept_encoding,
+            ..StressConfig::default()
+        };
+
+        let port = serve_once(GZIP_RESPONSE).await;
+        let wire = fetch_once(&config(port, AcceptEncoding::Gzip), &url).await;
+        assert_eq!(wire, Ok(28));
+
+        let port = serve_once(GZIP_RESPONSE).await;
+        let decoded = fetch_once(&config(port, AcceptEncoding::Auto), &url).await;
+        assert_eq!(decoded, Ok("hello hello hello hello".len() as u64));
+    }
+
+    #[test]
+    fn test_range_header_only_with_chunk_bytes() {
+        let client = Client::new();
+        let url = Url::parse("http://example.com/10GB.bin").unwrap();
+        let rng = &mut seeded_rng(None);
+        let identity = AcceptEncoding::Identity;
+
+        let plain = build_request(&client, &url, None, identity, rng).unwrap();
+        assert!(plain.headers().get("Range").is_none());
+
+        let ranged = build_request(&client, &url, Some(1024 * 1024), identity, rng).unwrap();
+        assert_eq!(ranged.headers()["Range"], "bytes=0-1048575");
+    }
+
+    #[test]
+    fn test_accept_encoding_header_follows_option() {
+        let client = Client::new();
+        let url = Url::parse("http://example.com/10GB.bin").unwrap();
+        let rng = &mut seeded_rng(None);
+        let mut header = |encoding| {
+            let request = build_request(&client, &url, None, encoding, rng).unwrap();
+            request.headers().get("Accept-Encoding").cloned()
+        };
+
+        assert_eq!(header(AcceptEncoding::Identity).unwrap(), "identity");
+        assert_eq!(header(AcceptEncoding::Br).unwrap(), "br");
+        assert!(header(AcceptEncoding::Auto).is_none());
+    }
+
+    #[test]
+    fn test_user_agent_is_picked_per_request() {
+        let client = Client::new();
+        let url = Url::parse("http://example.com/10GB.bin").unwrap();
+        let rng = &mut seeded_rng(None);
+        let agents: HashSet<_> = (0..50)
+            .map(|_| build_request(&client, &url, None, AcceptEncoding::Identity, rng).unwrap())
+            .map(|request| request.headers()["User-Agent"].clone())
+            .collect();
+        assert!(agents.len() > 1);
+    }
+
+    #[test]
+    fn test_invalid_download_target_is_rejected() {
+        let targets = vec!["not a url".to_string()];
+        assert!(parse_targets(&targets).is_err());
+    }
+
+    #[test]
+    fn test_retry_backoff_doubles_up_to_cap() {
+        assert_eq!(retry_backoff(1), Duration::from_millis(200));
+        assert_eq!(retry_backoff(2), Duration::from_millis(400));
+        assert_eq!(retry_backoff(3), Duration::from_millis(800));
+        assert_eq!(retry_backoff(5), RETRY_BACKOFF_MAX);
+        assert_eq!(retry_backoff(40), RETRY_BACKOFF_MAX);
+    }
 }
@@ -0,0 +1,130 @@
+use std::error::Error as StdError;
+use std::io;
+
+/// Coarse failure categories used for the final error breakdown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FailureKind {
+    /// The local inbound or the proxy refused or failed to open the connection
+    Connect,
+    /// A connect, handshake or read took too long
+    Timeout,
+    /// TLS handshake or certificate problems towards the target
+    Tls,
+    /// The connection broke after it was established
+    Stream,
+    /// The server answered with a non-2xx status (only with --fail-on-status)
+    Status,
+    /// Anything that does not fit the categories above
+    Other,
+}
+
+impl FailureKind {
+    pub const ALL: [FailureKind; 6] = [
+        FailureKind::Connect,
+        FailureKind::Timeout,
+        FailureKind::Tls,
+        FailureKind::Stream,
+        FailureKind::Status,
+        FailureKind::Other,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FailureKind::Connect => "connect",
+            FailureKind::Timeout => "timeout",
+            FailureKind::Tls => "tls",
+            FailureKind::Stream => "stream",
+            FailureKind::Status => "status",
+            FailureKind::Other => "other",
+        }
+    }
+
+    pub(crate) fn index(&self) -> usize {
+        *self as usize
+    }
+}
+
+/// Classifies a failed HTTP request or body read.
+pub fn classify_reqwest(err: &reqwest::Error) -> FailureKind {
+    if err.is_timeout() {
+        FailureKind::Timeout
+    } else if mentions_tls(err) {
+        FailureKind::Tls
+    } else if err.is_connect() {
+        FailureKind::Connect
+    } else if err.is_body() || err.is_decode() {
+        FailureKind::Stream
+    } else {
+        FailureKind::Other
+    }
+}
+
+/// Classifies a failed SOCKS5 connect made through `tokio_socks`.
+pub fn classify_socks(err: &tokio_socks::Error) -> FailureKind {
+    match err {
+        tokio_socks::Error::Io(io_err) => classify_io(io_err, FailureKind::Connect),
+        tokio_socks::Error::TtlExpired => FailureKind::Timeout,
+        _ => FailureKind::Connect,
+    }
+}
+
+/// Classifies an I/O error, falling back to `fallback` for non-timeout errors.
+pub fn classify_io(err: &io::Error, fallback: FailureKind) -> FailureKind {
+    match err.kind() {
+        io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock => FailureKind::Timeout,
+        io::ErrorKind::ConnectionRefused => FailureKind::Connect,
+        _ => fallback,
+    }
+}
+
+/// Classifies an `anyhow` error from the hand-written SOCKS5/UDP code paths.
+pub fn classify_anyhow(err: &anyhow::Error, fallback: FailureKind) -> FailureKind {
+    match err.downcast_ref::<io::Error>() {
+        Some(io_err) => classify_io(io_err, fallback),
+        None => fallback,
+    }
+}
+
+fn mentions_tls(err: &(dyn StdError + 'static)) -> bool {
+    let mut current: Option<&(dyn StdError + 'static)> = Some(err);
+    while let Some(e) = current {
+        let message = e.to_string().to_ascii_lowercase();
+        if message.contains("tls") || message.contains("certificate") {
+            return true;
+        }
+        current = e.source();
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify_io_and_socks_errors() {
+        let timed_out = io::Error::new(io::ErrorKind::TimedOut, "timed out");
+        let reset = io::Error::new(io::ErrorKind::ConnectionReset, "reset");
+
+        assert_eq!(
+            classify_io(&timed_out, FailureKind::Stream),
+            FailureKind::Timeout
+        );
+        assert_eq!(
+            classify_io(&reset, FailureKind::Stream),
+            FailureKind::Stream
+        );
+        assert_eq!(
+            classify_anyhow(&anyhow::Error::new(reset), FailureKind::Connect),
+            FailureKind::Connect
+        );
+        assert_eq!(
+            classify_socks(&tokio_socks::Error::ConnectionRefused),
+            FailureKind::Connect
+        );
+        assert_eq!(
+            classify_socks(&tokio_socks::Error::Io(timed_out)),
+            FailureKind::Timeout
+        );
+    }
+}
@@ -1,18 +1,59 @@
+mod connect;
+mod csv;
 mod download;
+mod failure;
+mod replay;
+mod state;
 mod tcp;
+mod tls;
 mod udp;
 
-use crate::cli::Mode;
-use crate::stressor::download::DEFAULT_HTTP_TARGETS;
-use anyhow::{Result, anyhow};
+pub use download::{DEFAULT_HTTP_TARGETS, fetch_once};
+pub use failure::FailureKind;
+pub use replay::{ReplayRequest, load_replay_file};
+pub use state::LifetimeStats;
+pub use tcp::{ResponseEnd, TcpTemplate};
+pub use udp::MAX_UDP_PACKET_SIZE;
+pub(crate) use udp::perform_greeting;
+
+use crate::cli::{AcceptEncoding, ConcurrencyMode, InboundProtocol, Mode, PayloadPattern};
+use crate::config::InboundAuth;
+use crate::stressor::csv::{CsvLogger, ThroughputSample};
+use anyhow::{Context, Result, anyhow};
 use futures::future::join_all;
-use std::sync::Arc;
-use std::sync::atomic::{AtomicU64, Ordering};
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::fs;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
 use std::time::{Duration, Instant};
-use tokio::task::JoinHandle;
-use tokio::time::sleep;
+use tokio::net::{TcpStream, lookup_host};
+use tokio::sync::Notify;
+use tokio::task::{AbortHandle, JoinHandle};
+use tokio::time::{MissedTickBehavior, interval_at, sleep, sleep_until, timeout};
+use tokio_socks::tcp::Socks5Stream;
 use url::Url;
 
+const SUPERVISOR_TICK: Duration = Duration::from_millis(100);
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(2);
+const GATE_POLL: Duration = Duration::from_millis(100);
+
+/// `--ema-alpha` default: the newest interval counts 30% towards the printed rates.
+pub const DEFAULT_EMA_ALPHA: f64 = 0.3;
+
+/// Decimal megabyte (MB), also the basis of every Mbps figure.
+pub const MEGABYTE: f64 = 1000.0 * 1000.0;
+/// Binary mebibyte (MiB).
+pub const MEBIBYTE: f64 = 1024.0 * 1024.0;
+/// Binary gibibyte (GiB), used for running totals.
+pub const GIBIBYTE: f64 = 1024.0 * MEBIBYTE;
+
 #[derive(Debug, Clone)]
 pub enum Target {
     Http(String),
@@ -35,12 +76,192 @@ impl SocketTarget {
 pub struct StressConfig {
     pub mode: Mode,
     pub targets: Vec<Target>,
+    pub replay: Vec<ReplayRequest>,
     pub concurrency: usize,
+    /// `--concurrency-mode`: whether `concurrency` is per proxy port or split across them
+    pub concurrency_mode: ConcurrencyMode,
+    pub pool_size: usize,
+    /// `--requests-in-flight`: overlapping downloads per download worker
+    pub requests_in_flight: usize,
+    pub verify_certs: bool,
+    /// `--tls-timing`: time a separate TLS handshake before each https download
+    pub tls_timing: bool,
+    pub follow_redirects: bool,
+    pub fail_on_status: bool,
+    pub chunk_bytes: Option<u64>,
+    pub retries: u32,
+    pub check_length: bool,
+    pub accept_encoding: AcceptEncoding,
     pub duration: Option<Duration>,
+    pub deadline: Option<Duration>,
+    pub ramp_down: Option<Duration>,
+    pub max_requests: Option<u64>,
+    pub max_bytes: Option<u64>,
+    pub warmup: Option<Duration>,
+    /// `--connect-only-first`: start the load only once every worker has connected
+    pub connect_only_first: bool,
     pub proxy_ports: Vec<u16>,
+    /// Address the local inbounds are reached on (see `ConfigOptions::proxy_host`)
+    pub proxy_host: IpAddr,
+    pub inbound: InboundProtocol,
+    pub inbound_auth: Option<InboundAuth>,
     pub packet_size: usize,
+    pub payload_pattern: PayloadPattern,
+    /// `--tcp-template`: sent instead of the generated payload, each send waiting for an answer
+    pub tcp_template: Option<TcpTemplate>,
     pub packet_rate: Option<u32>,
+    /// `--total-pps`: aggregate rate split across all workers, used instead of `packet_rate`
+    pub total_pps: Option<u32>,
+    /// `--ema-alpha`: weight of the newest interval in the reported rates
+    pub ema_alpha: f64,
     pub packets_per_connection: Option<u32>,
+    /// `--conn-lifetime`: tcp-flood reconnects once a connection is this old
+    pub conn_lifetime: Option<Duration>,
+    pub hold: Option<Duration>,
+    pub udp_reassociate: bool,
+    pub udp_local_port: Option<u16>,
+    pub local_address: Option<IpAddr>,
+    pub jitter: Option<(Duration, Duration)>,
+    pub shuffle_targets: bool,
+    /// `--seed`: base seed of every worker's random source (see [`worker_rng`])
+    pub seed: Option<u64>,
+    pub auto_concurrency: Option<AutoConcurrency>,
+    pub stall_restart: Option<StallRestart>,
+    pub csv_log: Option<PathBuf>,
+    /// `--state-file`: where lifetime totals are kept between runs
+    pub state_file: Option<PathBuf>,
+    pub progress_bar: bool,
+}
+
+/// `--stall-restart`: ask for an xray-core recycle once throughput stays below `min_mbps`
+/// for `intervals` consecutive stats intervals.
+#[derive(Debug, Clone, Copy)]
+pub struct StallRestart {
+    pub min_mbps: f64,
+    pub intervals: u32,
+}
+
+/// Counts consecutive slow intervals for [`StallRestart`].
+struct StallDetector {
+    limit: StallRestart,
+    slow_intervals: u32,
+}
+
+impl StallDetector {
+    fn new(limit: StallRestart) -> Self {
+        Self {
+            limit,
+            slow_intervals: 0,
+        }
+    }
+
+    /// Returns true when this interval completes a stall; the count then starts over.
+    fn observe(&mut self, mbps: f64) -> bool {
+        if mbps >= self.limit.min_mbps {
+            self.slow_intervals = 0;
+            return false;
+        }
+        self.slow_intervals += 1;
+        if self.slow_intervals < self.limit.intervals {
+            return false;
+        }
+        self.slow_intervals = 0;
+        true
+    }
+}
+
+/// Exponential moving average of one reported rate (`--ema-alpha`); the first sample is taken as
+/// is and an alpha of 1.0 turns smoothing off.
+#[derive(Debug, Clone, Copy)]
+struct Ema {
+    alpha: f64,
+    value: Option<f64>,
+}
+
+impl Ema {
+    fn new(alpha: f64) -> Self {
+        Self { alpha, value: None }
+    }
+
+    fn update(&mut self, sample: f64) -> f64 {
+        let value = match self.value {
+            Some(previous) => self.alpha * sample + (1.0 - self.alpha) * previous,
+            None => sample,
+        };
+        self.value = Some(value);
+        value
+    }
+}
+
+/// A byte rate as `12.50 MB/s (11.92 MiB/s, 100 Mbps)`.
+fn format_rate(bytes_per_sec: f64) -> String {
+    format!(
+        "{:.2} MB/s ({:.2} MiB/s, {:.0} Mbps)",
+        bytes_per_sec / MEGABYTE,
+        bytes_per_sec / MEBIBYTE,
+        bytes_per_sec * 8.0 / MEGABYTE
+    )
+}
+
+/// `--auto-concurrency`: ramp the active workers per instance up to `concurrency`
+/// while the failure rate of each interval stays at or below `max_failure_rate` percent.
+#[derive(Debug, Clone, Copy)]
+pub struct AutoConcurrency {
+    pub max_failure_rate: f64,
+    pub interval: Duration,
+}
+
+impl Default for StressConfig {
+    fn default() -> Self {
+        Self {
+            mode: Mode::Download,
+            targets: Vec::new(),
+            replay: Vec::new(),
+            concurrency: 200,
+            concurrency_mode: ConcurrencyMode::PerInstance,
+            pool_size: 10,
+            requests_in_flight: 1,
+            verify_certs: false,
+            tls_timing: false,
+            follow_redirects: true,
+            fail_on_status: false,
+            chunk_bytes: None,
+            retries: 0,
+            check_length: false,
+            accept_encoding: AcceptEncoding::Identity,
+            duration: None,
+            deadline: None,
+            ramp_down: None,
+            max_requests: None,
+            max_bytes: None,
+            warmup: None,
+            connect_only_first: false,
+            proxy_ports: Vec::new(),
+            proxy_host: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            inbound: InboundProtocol::Socks,
+            inbound_auth: None,
+            packet_size: 1024,
+            payload_pattern: PayloadPattern::Random,
+            tcp_template: None,
+            packet_rate: None,
+            total_pps: None,
+            ema_alpha: DEFAULT_EMA_ALPHA,
+            packets_per_connection: None,
+            conn_lifetime: None,
+            hold: None,
+            udp_reassociate: false,
+            udp_local_port: None,
+            local_address: None,
+            jitter: None,
+            shuffle_targets: false,
+            seed: None,
+            auto_concurrency: None,
+            stall_restart: None,
+            csv_log: None,
+            state_file: None,
+            progress_bar: false,
+        }
+    }
 }
 
 impl StressConfig {
@@ -63,6 +284,50 @@ impl StressConfig {
             })
             .collect()
     }
+
+    /// Workers spawned for proxy port number `idx`.
+    pub fn instance_workers(&self, idx: usize) -> usize {
+        self.concurrency_mode
+            .instance_workers(self.concurrency, self.proxy_ports.len(), idx)
+    }
+
+    /// Most workers any one proxy port gets; the ceiling for `--auto-concurrency`.
+    pub fn max_instance_workers(&self) -> usize {
+        self.instance_workers(0)
+    }
+
+    /// Workers spawned across all proxy ports.
+    pub fn total_workers(&self) -> usize {
+        (0..self.proxy_ports.len())
+            .map(|idx| self.instance_workers(idx))
+            .sum()
+    }
+
+    /// Pause between two sends (connects in connect mode) of one worker: `--packet-rate` as is,
+    /// or `--total-pps` divided evenly over all spawned workers.
+    pub fn send_interval(&self) -> Option<Duration> {
+        match self.total_pps {
+            Some(total) if total > 0 => {
+                let workers = self.total_workers().max(1);
+                Some(Duration::from_secs_f64(workers as f64 / total as f64))
+            }
+            _ => packet_interval(self.packet_rate),
+        }
+    }
+
+    pub fn limits(&self) -> RunLimits {
+        RunLimits {
+            max_requests: self.max_requests,
+            max_bytes: self.max_bytes,
+        }
+    }
+}
+
+/// Stop conditions besides `--duration` that workers and the supervisor poll.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RunLimits {
+    pub max_requests: Option<u64>,
+    pub max_bytes: Option<u64>,
 }
 
 #[derive(Debug, Clone)]
@@ -71,9 +336,18 @@ pub struct StressStats {
     pub failure_events: u64,
     pub bytes_transferred: u64,
     pub packets_sent: u64,
+    pub retries: u64,
+    pub partial_downloads: u64,
+    pub failures_by_kind: [u64; FailureKind::ALL.len()],
     pub start_time: Instant,
 }
 
+impl Default for StressStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl StressStats {
     pub fn new() -> Self {
         Self {
@@ -81,6 +355,9 @@ impl StressStats {
             failure_events: 0,
             bytes_transferred: 0,
             packets_sent: 0,
+            retries: 0,
+            partial_downloads: 0,
+            failures_by_kind: [0; FailureKind::ALL.len()],
             start_time: Instant::now(),
         }
     }
@@ -89,6 +366,35 @@ impl StressStats {
         self.start_time.elapsed()
     }
 
+    /// Counts accumulated after `baseline` was taken, timed from the baseline instant.
+    pub fn since(&self, baseline: &StressStats) -> StressStats {
+        StressStats {
+            success_events: self.success_events.saturating_sub(baseline.success_events),
+            failure_events: self.failure_events.saturating_sub(baseline.failure_events),
+            bytes_transferred: self
+                .bytes_transferred
+                .saturating_sub(baseline.bytes_transferred),
+            packets_sent: self.packets_sent.saturating_sub(baseline.packets_sent),
+            retries: self.retries.saturating_sub(baseline.retries),
+            partial_downloads: self
+                .partial_downloads
+                .saturating_sub(baseline.partial_downloads),
+            failures_by_kind: std::array::from_fn(|i| {
+                self.failures_by_kind[i].saturating_sub(baseline.failures_by_kind[i])
+            }),
+            start_time: baseline.start_time,
+        }
+    }
+
+    /// Failure counts per category, skipping categories that never occurred.
+    pub fn failure_breakdown(&self) -> Vec<(FailureKind, u64)> {
+        FailureKind::ALL
+            .iter()
+            .map(|kind| (*kind, self.failures_by_kind[kind.index()]))
+            .filter(|(_, count)| *count > 0)
+            .collect()
+    }
+
     pub fn bytes_per_second(&self) -> f64 {
         let elapsed_secs = self.elapsed().as_secs_f64();
         if elapsed_secs.is_normal() {
@@ -98,6 +404,39 @@ impl StressStats {
         }
     }
 
+    /// Decimal MB/s (10^6 bytes).
+    pub fn megabytes_per_second(&self) -> f64 {
+        self.bytes_per_second() / MEGABYTE
+    }
+
+    /// Binary MiB/s (2^20 bytes).
+    pub fn mebibytes_per_second(&self) -> f64 {
+        self.bytes_per_second() / MEBIBYTE
+    }
+
+    pub fn failure_rate(&self) -> f64 {
+        let total = self.success_events + self.failure_events;
+        if total == 0 {
+            0.0
+        } else {
+            self.failure_events as f64 * 100.0 / total as f64
+        }
+    }
+
+    /// Single `key=value` line for log scrapers, e.g.
+    /// `RESULT mode=download bytes=123456789 mbps=85.3 success=4210 failure=12 duration=60.0`.
+    pub fn result_line(&self, mode: Mode) -> String {
+        format!(
+            "RESULT mode={} bytes={} mbps={:.1} success={} failure={} duration={:.1}",
+            mode.as_str(),
+            self.bytes_transferred,
+            self.megabytes_per_second() * 8.0,
+            self.success_events,
+            self.failure_events,
+            self.elapsed().as_secs_f64()
+        )
+    }
+
     pub fn packets_per_second(&self) -> f64 {
         let elapsed_secs = self.elapsed().as_secs_f64();
         if elapsed_secs.is_normal() && elapsed_secs > 0.0 {
@@ -108,12 +447,326 @@ impl StressStats {
     }
 }
 
+#[derive(Debug, Clone)]
+pub struct LatencySummary {
+    pub target: String,
+    pub samples: usize,
+    pub average: Duration,
+    pub p50: Duration,
+    pub p99: Duration,
+}
+
+/// Latencies kept per target for the percentiles; later ones replace kept ones at random.
+const LATENCY_RESERVOIR_LEN: usize = 4096;
+
+/// Count and sum of every latency of one target, plus a uniform sample of them.
+#[derive(Default)]
+struct LatencySamples {
+    seen: usize,
+    total: Duration,
+    reservoir: Vec<Duration>,
+}
+
+impl LatencySamples {
+    fn push(&mut self, latency: Duration) {
+        use rand::Rng;
+
+        self.seen += 1;
+        self.total += latency;
+        if self.reservoir.len() < LATENCY_RESERVOIR_LEN {
+            self.reservoir.push(latency);
+        } else {
+            let slot = rand::rng().random_range(0..self.seen);
+            if let Some(kept) = self.reservoir.get_mut(slot) {
+                *kept = latency;
+            }
+        }
+    }
+}
+
+/// Connect and TLS handshake latencies per target, in bounded memory however long the run.
+#[derive(Clone, Default)]
+pub struct LatencyTracker {
+    samples: Arc<Mutex<HashMap<String, LatencySamples>>>,
+}
+
+impl LatencyTracker {
+    pub fn record(&self, target: &str, latency: Duration) {
+        if let Ok(mut samples) = self.samples.lock() {
+            samples.entry(target.to_string()).or_default().push(latency);
+        }
+    }
+
+    pub fn clear(&self) {
+        if let Ok(mut samples) = self.samples.lock() {
+            samples.clear();
+        }
+    }
+
+    pub fn summaries(&self) -> Vec<LatencySummary> {
+        let Ok(samples) = self.samples.lock() else {
+            return Vec::new();
+        };
+
+        let mut summaries: Vec<LatencySummary> = samples
+            .iter()
+            .filter(|(_, values)| values.seen > 0)
+            .map(|(target, values)| {
+                let mut sorted = values.reservoir.clone();
+                sorted.sort_unstable();
+                let percentile = |p: f64| {
+                    let idx = ((sorted.len() as f64 * p).ceil() as usize).saturating_sub(1);
+                    sorted[idx.min(sorted.len() - 1)]
+                };
+                LatencySummary {
+                    target: target.clone(),
+                    samples: values.seen,
+                    average: values.total.div_f64(values.seen as f64),
+                    p50: percentile(0.5),
+                    p99: percentile(0.99),
+                }
+            })
+            .collect();
+        summaries.sort_by(|a, b| a.target.cmp(&b.target));
+        summaries
+    }
+}
+
+/// Per-target request outcome, keyed by URL in download mode.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct TargetSummary {
+    pub target: String,
+    pub success: u64,
+    pub failure: u64,
+    pub bytes: u64,
+}
+
+#[derive(Clone, Default)]
+pub struct TargetTracker {
+    targets: Arc<Mutex<HashMap<String, TargetSummary>>>,
+}
+
+impl TargetTracker {
+    fn update(&self, target: &str, apply: impl FnOnce(&mut TargetSummary)) {
+        if let Ok(mut targets) = self.targets.lock() {
+            let entry = targets
+                .entry(target.to_string())
+                .or_insert_with(|| TargetSummary {
+                    target: target.to_string(),
+                    ..Default::default()
+                });
+            apply(entry);
+        }
+    }
+
+    pub fn record_success(&self, target: &str, bytes: u64) {
+        self.update(target, |t| {
+            t.success += 1;
+            t.bytes += bytes;
+        });
+    }
+
+    pub fn record_failure(&self, target: &str) {
+        self.update(target, |t| t.failure += 1);
+    }
+
+    pub fn record_bytes(&self, target: &str, bytes: u64) {
+        self.update(target, |t| t.bytes += bytes);
+    }
+
+    pub fn clear(&self) {
+        if let Ok(mut targets) = self.targets.lock() {
+            targets.clear();
+        }
+    }
+
+    /// All targets seen so far, most bytes first.
+    pub fn summaries(&self) -> Vec<TargetSummary> {
+        let Ok(targets) = self.targets.lock() else {
+            return Vec::new();
+        };
+
+        let mut summaries: Vec<TargetSummary> = targets.values().cloned().collect();
+        summaries.sort_by(|a, b| b.bytes.cmp(&a.bytes).then_with(|| a.target.cmp(&b.target)));
+        summaries
+    }
+}
+
+/// Response status code distribution for the download and replay modes.
+#[derive(Clone, Default)]
+pub struct StatusTracker {
+    codes: Arc<Mutex<BTreeMap<u16, u64>>>,
+}
+
+impl StatusTracker {
+    pub fn record(&self, status: u16) {
+        if let Ok(mut codes) = self.codes.lock() {
+            *codes.entry(status).or_default() += 1;
+        }
+    }
+
+    pub fn clear(&self) {
+        if let Ok(mut codes) = self.codes.lock() {
+            codes.clear();
+        }
+    }
+
+    /// `(status, count)` pairs in ascending status order.
+    pub fn counts(&self) -> Vec<(u16, u64)> {
+        let Ok(codes) = self.codes.lock() else {
+            return Vec::new();
+        };
+        codes
+            .iter()
+            .map(|(status, count)| (*status, *count))
+            .collect()
+    }
+}
+
+/// Stats intervals whose packet rate is kept; about 5.7 hours at the default 5 s interval.
+const PPS_HISTORY_LEN: usize = 4096;
+
+/// Spread of the per-interval packet rates of a flood run.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct PpsSummary {
+    pub samples: usize,
+    pub min: f64,
+    pub avg: f64,
+    pub max: f64,
+    /// Population standard deviation
+    pub stddev: f64,
+}
+
+/// Raw packets per second of each stats interval in the flood modes, oldest dropped first.
+#[derive(Clone, Default)]
+pub struct PpsHistory {
+    samples: Arc<Mutex<VecDeque<f64>>>,
+}
+
+impl PpsHistory {
+    pub fn record(&self, pps: f64) {
+        if let Ok(mut samples) = self.samples.lock() {
+            if samples.len() == PPS_HISTORY_LEN {
+                samples.pop_front();
+            }
+            samples.push_back(pps);
+        }
+    }
+
+    pub fn clear(&self) {
+        if let Ok(mut samples) = self.samples.lock() {
+            samples.clear();
+        }
+    }
+
+    /// `None` until the first interval has been recorded.
+    pub fn summary(&self) -> Option<PpsSummary> {
+        let samples = self.samples.lock().ok()?;
+        if samples.is_empty() {
+            return None;
+        }
+        let count = samples.len() as f64;
+        let avg = samples.iter().sum::<f64>() / count;
+        let variance = samples.iter().map(|pps| (pps - avg).powi(2)).sum::<f64>() / count;
+        Some(PpsSummary {
+            samples: samples.len(),
+            min: samples.iter().copied().fold(f64::INFINITY, f64::min),
+            avg,
+            max: samples.iter().copied().fold(0.0, f64::max),
+            stddev: variance.sqrt(),
+        })
+    }
+}
+
+/// Connections and requests currently in progress, with the highest values seen.
+#[derive(Clone, Default)]
+pub struct ConnectionGauge {
+    active: Arc<AtomicU64>,
+    peak: Arc<AtomicU64>,
+    interval_peak: Arc<AtomicU64>,
+}
+
+impl ConnectionGauge {
+    /// Counts one more open connection until the returned guard is dropped.
+    pub fn open(&self) -> ConnectionGuard {
+        let active = self.active.fetch_add(1, Ordering::Relaxed) + 1;
+        self.peak.fetch_max(active, Ordering::Relaxed);
+        self.interval_peak.fetch_max(active, Ordering::Relaxed);
+        ConnectionGuard {
+            active: Arc::clone(&self.active),
+        }
+    }
+
+    pub fn active(&self) -> u64 {
+        self.active.load(Ordering::Relaxed)
+    }
+
+    pub fn peak(&self) -> u64 {
+        self.peak.load(Ordering::Relaxed)
+    }
+
+    /// Highest value since the previous call; the next interval starts from the current count.
+    pub fn take_interval_peak(&self) -> u64 {
+        self.interval_peak.swap(self.active(), Ordering::Relaxed)
+    }
+}
+
+pub struct ConnectionGuard {
+    active: Arc<AtomicU64>,
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        self.active.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// `--no-restart`: proxy ports whose xray-core instance exited; their workers stop.
+#[derive(Clone, Default)]
+pub struct DownPorts {
+    ports: Arc<Mutex<HashSet<u16>>>,
+    /// Lets the per-iteration check skip the lock while every instance is up
+    any: Arc<AtomicBool>,
+}
+
+impl DownPorts {
+    /// Returns whether `port` was newly marked down.
+    pub fn mark(&self, port: u16) -> bool {
+        self.any.store(true, Ordering::Relaxed);
+        self.ports.lock().is_ok_and(|mut ports| ports.insert(port))
+    }
+
+    pub fn contains(&self, port: u16) -> bool {
+        self.any.load(Ordering::Relaxed)
+            && self.ports.lock().is_ok_and(|ports| ports.contains(&port))
+    }
+}
+
 #[derive(Clone)]
 pub struct SharedCounters {
     pub success_events: Arc<AtomicU64>,
     pub failure_events: Arc<AtomicU64>,
     pub bytes_transferred: Arc<AtomicU64>,
     pub packets_sent: Arc<AtomicU64>,
+    pub retries: Arc<AtomicU64>,
+    pub partial_downloads: Arc<AtomicU64>,
+    pub failures_by_kind: Arc<[AtomicU64; FailureKind::ALL.len()]>,
+    pub connect_latencies: LatencyTracker,
+    /// `--tls-timing` handshake durations by `host:port`
+    pub tls_handshakes: LatencyTracker,
+    pub targets: TargetTracker,
+    pub status_codes: StatusTracker,
+    /// Per-interval packet rates, filled by the stats reporter
+    pub pps_history: PpsHistory,
+    pub active_connections: ConnectionGauge,
+    pub down_ports: DownPorts,
+    pub ramp_down: Arc<Mutex<Option<RampDownSummary>>>,
+}
+
+impl Default for SharedCounters {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl SharedCounters {
@@ -123,6 +776,17 @@ impl SharedCounters {
             failure_events: Arc::new(AtomicU64::new(0)),
             bytes_transferred: Arc::new(AtomicU64::new(0)),
             packets_sent: Arc::new(AtomicU64::new(0)),
+            retries: Arc::new(AtomicU64::new(0)),
+            partial_downloads: Arc::new(AtomicU64::new(0)),
+            failures_by_kind: Arc::new(std::array::from_fn(|_| AtomicU64::new(0))),
+            connect_latencies: LatencyTracker::default(),
+            tls_handshakes: LatencyTracker::default(),
+            targets: TargetTracker::default(),
+            status_codes: StatusTracker::default(),
+            pps_history: PpsHistory::default(),
+            active_connections: ConnectionGauge::default(),
+            down_ports: DownPorts::default(),
+            ramp_down: Arc::new(Mutex::new(None)),
         }
     }
 
@@ -130,14 +794,42 @@ impl SharedCounters {
         self.success_events.fetch_add(1, Ordering::Relaxed);
     }
 
-    pub fn record_failure(&self) {
+    pub fn record_failure(&self, kind: FailureKind) {
         self.failure_events.fetch_add(1, Ordering::Relaxed);
+        self.failures_by_kind[kind.index()].fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_retry(&self) {
+        self.retries.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_partial(&self) {
+        self.partial_downloads.fetch_add(1, Ordering::Relaxed);
     }
 
+    pub fn request_limit_reached(&self, max_requests: Option<u64>) -> bool {
+        max_requests.is_some_and(|limit| self.success_events.load(Ordering::Relaxed) >= limit)
+    }
+
+    pub fn byte_limit_reached(&self, max_bytes: Option<u64>) -> bool {
+        max_bytes.is_some_and(|limit| self.bytes_transferred.load(Ordering::Relaxed) >= limit)
+    }
+
+    pub fn limit_reached(&self, limits: RunLimits) -> bool {
+        self.request_limit_reached(limits.max_requests) || self.byte_limit_reached(limits.max_bytes)
+    }
+
+    /// Counts response body bytes as read from the stream: the bytes on the wire (after HTTP
+    /// framing) unless `--accept-encoding auto` has the client decompress them first.
     pub fn record_bytes(&self, bytes: u64) {
         self.bytes_transferred.fetch_add(bytes, Ordering::Relaxed);
     }
 
+    pub fn record_connect(&self, target: &str, latency: Duration) {
+        self.record_success();
+        self.connect_latencies.record(target, latency);
+    }
+
     pub fn record_packet(&self, payload_bytes: usize) {
         self.record_success();
         self.packets_sent.fetch_add(1, Ordering::Relaxed);
@@ -145,106 +837,597 @@ impl SharedCounters {
             .fetch_add(payload_bytes as u64, Ordering::Relaxed);
     }
 
+    /// One `--tcp-template` round trip: a single success, with both directions in the byte total.
+    pub fn record_exchange(&self, sent: usize, received: usize) {
+        self.record_packet(sent);
+        self.record_bytes(received as u64);
+    }
+
     pub fn snapshot(&self, start_time: Instant) -> StressStats {
         StressStats {
             success_events: self.success_events.load(Ordering::Relaxed),
             failure_events: self.failure_events.load(Ordering::Relaxed),
             bytes_transferred: self.bytes_transferred.load(Ordering::Relaxed),
             packets_sent: self.packets_sent.load(Ordering::Relaxed),
+            retries: self.retries.load(Ordering::Relaxed),
+            partial_downloads: self.partial_downloads.load(Ordering::Relaxed),
+            failures_by_kind: std::array::from_fn(|i| {
+                self.failures_by_kind[i].load(Ordering::Relaxed)
+            }),
             start_time,
         }
     }
 }
 
+#[derive(Clone, Default)]
+pub struct ShutdownSignal {
+    flag: Arc<AtomicBool>,
+    draining: Arc<AtomicBool>,
+}
+
+impl ShutdownSignal {
+    pub fn trigger(&self) {
+        self.flag.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_triggered(&self) -> bool {
+        self.flag.load(Ordering::Relaxed)
+    }
+
+    /// `--ramp-down`: no new requests or retries, but in-flight transfers may finish.
+    pub fn drain(&self) {
+        self.draining.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::Relaxed) || self.is_triggered()
+    }
+}
+
+/// Workers still busy when `--ramp-down` began, split by whether they finished within the window.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RampDownSummary {
+    pub in_flight: usize,
+    pub completed: usize,
+    pub aborted: usize,
+}
+
+/// Caps how many worker slots per instance may run; workers above the cap park until admitted.
 #[derive(Clone)]
-pub struct StressRunner {
-    config: StressConfig,
-    counters: SharedCounters,
-    stats: StressStats,
+pub(crate) struct ConcurrencyGate {
+    active: Arc<AtomicUsize>,
+    shutdown: ShutdownSignal,
+    warmup: Option<ConnectWarmup>,
 }
 
-impl StressRunner {
-    pub fn new(config: StressConfig) -> Result<Self> {
-        if config.proxy_ports.is_empty() {
-            return Err(anyhow!("No proxy ports provided for stress runner"));
+impl ConcurrencyGate {
+    fn new(active: usize, shutdown: ShutdownSignal) -> Self {
+        Self {
+            active: Arc::new(AtomicUsize::new(active)),
+            shutdown,
+            warmup: None,
         }
+    }
 
-        Ok(Self {
-            config,
-            counters: SharedCounters::new(),
-            stats: StressStats::new(),
-        })
+    fn with_warmup(mut self, warmup: ConnectWarmup) -> Self {
+        self.warmup = Some(warmup);
+        self
     }
 
-    pub async fn run(&self) -> Result<()> {
-        match self.config.mode {
-            Mode::Download => {
-                download::run(&self.config, self.counters.clone(), self.stats.start_time).await
-            }
-            Mode::TcpFlood => {
-                tcp::run(&self.config, self.counters.clone(), self.stats.start_time).await
-            }
-            Mode::UdpFlood => {
-                udp::run(&self.config, self.counters.clone(), self.stats.start_time).await
-            }
+    /// The `--connect-only-first` ticket of worker `slot`; empty without a warm-up or for slots
+    /// parked from the start.
+    pub(crate) fn warmup_ticket(&self, slot: usize) -> WarmupTicket {
+        let warmup = self.warmup.as_ref().filter(|_| self.admits(slot)).cloned();
+        WarmupTicket {
+            warmup,
+            shutdown: self.shutdown.clone(),
         }
     }
 
-    pub async fn start_stats_reporter(&self, interval: Duration) {
-        let counters = self.counters.clone();
-        let mode = self.config.mode;
-        let start_time = self.stats.start_time;
-        let end_time = self.config.duration.map(|d| start_time + d);
+    fn active(&self) -> usize {
+        self.active.load(Ordering::Relaxed)
+    }
 
-        tokio::spawn(async move {
-            let mut last_bytes = 0u64;
-            let mut last_packets = 0u64;
-            loop {
-                sleep(interval).await;
+    fn set_active(&self, active: usize) {
+        self.active.store(active, Ordering::Relaxed);
+    }
 
-                let bytes = counters.bytes_transferred.load(Ordering::Relaxed);
-                let packets = counters.packets_sent.load(Ordering::Relaxed);
-                let bytes_delta = bytes - last_bytes;
-                let packets_delta = packets - last_packets;
+    pub(crate) fn admits(&self, slot: usize) -> bool {
+        slot < self.active()
+    }
 
-                let seconds = interval.as_secs_f64().max(1.0);
-                let mb_per_sec = (bytes_delta as f64 / seconds) / (1024.0 * 1024.0);
-                let mbit_per_sec = (bytes_delta as f64 * 8.0) / (seconds * 1_000_000.0);
-                let pps = packets_delta as f64 / seconds;
-                let total_gb = bytes as f64 / (1024.0 * 1024.0 * 1024.0);
+    /// Parks until `slot` is admitted; returns `false` if shutdown was triggered meanwhile.
+    pub(crate) async fn wait_for_slot(&self, slot: usize) -> bool {
+        while !self.admits(slot) {
+            if self.shutdown.is_draining() {
+                return false;
+            }
+            sleep(GATE_POLL).await;
+        }
+        true
+    }
+}
 
-                match mode {
-                    Mode::Download => {
-                        log::info!(
-                            "[HTTP] Speed: {:.2} MB/s ({:.0} Mbps) | Delta: {:.1} MB | Total: {:.2} GB",
-                            mb_per_sec,
-                            mbit_per_sec,
-                            bytes_delta as f64 / (1024.0 * 1024.0),
-                            total_gb
-                        );
-                    }
-                    Mode::TcpFlood => {
-                        log::info!(
-                            "[TCP] PPS: {:.0} | Throughput: {:.2} MB/s ({:.0} Mbps) | Total: {:.2} GB",
-                            pps,
-                            mb_per_sec,
-                            mbit_per_sec,
-                            total_gb
-                        );
+/// `--connect-only-first`: counts the workers that still have to make their first connection.
+#[derive(Clone)]
+pub(crate) struct ConnectWarmup {
+    workers: usize,
+    pending: Arc<AtomicUsize>,
+    connected: Arc<AtomicUsize>,
+    started: Instant,
+    finished: Arc<OnceLock<Instant>>,
+}
+
+impl ConnectWarmup {
+    fn new(workers: usize, started: Instant) -> Self {
+        let finished = Arc::new(OnceLock::new());
+        if workers == 0 {
+            let _ = finished.set(started);
+        }
+        Self {
+            workers,
+            pending: Arc::new(AtomicUsize::new(workers)),
+            connected: Arc::new(AtomicUsize::new(0)),
+            started,
+            finished,
+        }
+    }
+
+    fn arrive(&self, connected: bool) {
+        if connected {
+            self.connected.fetch_add(1, Ordering::Relaxed);
+        }
+        if self.pending.fetch_sub(1, Ordering::AcqRel) == 1 {
+            let _ = self.finished.set(Instant::now());
+        }
+    }
+
+    fn is_finished(&self) -> bool {
+        self.finished.get().is_some()
+    }
+
+    /// Time from the start of the run until the last worker arrived.
+    fn took(&self) -> Option<Duration> {
+        self.finished
+            .get()
+            .map(|end| end.duration_since(self.started))
+    }
+}
+
+/// One worker's place in the `--connect-only-first` warm-up. Dropping it unused, because the
+/// worker stopped early, still counts the worker in so the others are not held back.
+pub(crate) struct WarmupTicket {
+    warmup: Option<ConnectWarmup>,
+    shutdown: ShutdownSignal,
+}
+
+impl WarmupTicket {
+    /// Reports the worker's first connection attempt and parks until every worker has reported
+    /// theirs (or shutdown). Later calls return at once.
+    pub(crate) async fn arrive(&mut self, connected: bool) {
+        let Some(warmup) = self.warmup.take() else {
+            return;
+        };
+        warmup.arrive(connected);
+        while !warmup.is_finished() && !self.shutdown.is_draining() {
+            sleep(GATE_POLL).await;
+        }
+    }
+
+    pub(crate) fn is_pending(&self) -> bool {
+        self.warmup.is_some()
+    }
+}
+
+impl Drop for WarmupTicket {
+    fn drop(&mut self) {
+        if let Some(warmup) = self.warmup.take() {
+            warmup.arrive(false);
+        }
+    }
+}
+
+/// Counter snapshot taken when the `--warmup` window ends; totals are reported relative to it.
+#[derive(Clone, Default)]
+struct WarmupBaseline {
+    snapshot: Arc<Mutex<Option<StressStats>>>,
+}
+
+impl WarmupBaseline {
+    fn mark(&self, stats: StressStats) {
+        if let Ok(mut snapshot) = self.snapshot.lock() {
+            *snapshot = Some(stats);
+        }
+    }
+
+    fn is_marked(&self) -> bool {
+        matches!(self.snapshot.lock().as_deref(), Ok(Some(_)))
+    }
+
+    fn apply(&self, current: StressStats) -> StressStats {
+        let baseline = self.snapshot.lock().ok().and_then(|s| s.clone());
+        match baseline {
+            Some(baseline) => current.since(&baseline),
+            None => current,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct StressRunner {
+    config: StressConfig,
+    counters: SharedCounters,
+    stats: StressStats,
+    progress: Option<ProgressBar>,
+    shutdown: ShutdownSignal,
+    warmup_baseline: WarmupBaseline,
+    connect_warmup: Option<ConnectWarmup>,
+    gate: ConcurrencyGate,
+    peak_stable: Arc<AtomicUsize>,
+    stalled: Arc<Notify>,
+    stall_recycles: Arc<AtomicU64>,
+    /// Totals of earlier runs, loaded from `--state-file`
+    previous: Option<LifetimeStats>,
+}
+
+impl StressRunner {
+    pub fn new(mut config: StressConfig) -> Result<Self> {
+        if config.proxy_ports.is_empty() {
+            return Err(anyhow!("No proxy ports provided for stress runner"));
+        }
+        config.ramp_down = ramp_down_window(&config);
+
+        let progress = match config.duration {
+            Some(duration) if config.progress_bar => Some(build_progress_bar(duration)),
+            _ => None,
+        };
+
+        let initial_workers = match config.auto_concurrency {
+            Some(_) => auto_concurrency_step(config.max_instance_workers()),
+            None => config.max_instance_workers(),
+        };
+
+        let shutdown = ShutdownSignal::default();
+        let previous = config
+            .state_file
+            .as_deref()
+            .map(LifetimeStats::load)
+            .transpose()?;
+
+        let stats = StressStats::new();
+        let mut gate = ConcurrencyGate::new(initial_workers, shutdown.clone());
+        let connect_warmup = config.connect_only_first.then(|| {
+            let workers = (0..config.proxy_ports.len())
+                .map(|idx| config.instance_workers(idx).min(initial_workers))
+                .sum();
+            ConnectWarmup::new(workers, stats.start_time)
+        });
+        if let Some(warmup) = &connect_warmup {
+            gate = gate.with_warmup(warmup.clone());
+        }
+
+        Ok(Self {
+            gate,
+            peak_stable: Arc::new(AtomicUsize::new(0)),
+            stalled: Arc::new(Notify::new()),
+            stall_recycles: Arc::new(AtomicU64::new(0)),
+            config,
+            counters: SharedCounters::new(),
+            stats,
+            progress,
+            shutdown,
+            warmup_baseline: WarmupBaseline::default(),
+            connect_warmup,
+            previous,
+        })
+    }
+
+    pub async fn run(&self) -> Result<()> {
+        let warmup_task = self.config.warmup.map(|warmup| {
+            let counters = self.counters.clone();
+            let baseline = self.warmup_baseline.clone();
+            let warmup_end = self.stats.start_time + warmup;
+            tokio::spawn(async move {
+                sleep_until(warmup_end.into()).await;
+                reset_baseline(&counters, &baseline);
+                log::info!(
+                    "Warm-up of {}s finished, statistics baseline reset",
+                    warmup.as_secs()
+                );
+            })
+        });
+
+        let connect_warmup_task = self.connect_warmup.clone().map(|warmup| {
+            let counters = self.counters.clone();
+            let baseline = self.warmup_baseline.clone();
+            let shutdown = self.shutdown.clone();
+            tokio::spawn(async move {
+                while !warmup.is_finished() {
+                    if shutdown.is_draining() {
+                        return;
+                    }
+                    sleep(GATE_POLL).await;
+                }
+                reset_baseline(&counters, &baseline);
+                log::info!(
+                    "Connection warm-up took {:.2}s: {} of {} workers connected, starting load",
+                    warmup.took().unwrap_or_default().as_secs_f64(),
+                    warmup.connected.load(Ordering::Relaxed),
+                    warmup.workers
+                );
+            })
+        });
+
+        let scaler_task = self.config.auto_concurrency.map(|auto| {
+            tokio::spawn(auto_scale(
+                auto,
+                self.config.max_instance_workers(),
+                self.gate.clone(),
+                self.counters.clone(),
+                Arc::clone(&self.peak_stable),
+            ))
+        });
+
+        let result = self.run_mode().await;
+        if let Some(deadline) = self.config.deadline
+            && self.stats.start_time.elapsed() >= deadline
+        {
+            log::warn!(
+                "Deadline of {}s reached, stopped workers",
+                deadline.as_secs()
+            );
+        }
+        for task in [warmup_task, connect_warmup_task, scaler_task]
+            .into_iter()
+            .flatten()
+        {
+            task.abort();
+        }
+        if let Some(bar) = &self.progress {
+            bar.finish();
+        }
+        result
+    }
+
+    async fn run_mode(&self) -> Result<()> {
+        let end_time = self.end_time();
+        match self.config.mode {
+            Mode::Download => {
+                download::run(
+                    &self.config,
+                    self.counters.clone(),
+                    self.shutdown.clone(),
+                    self.gate.clone(),
+                    end_time,
+                )
+                .await
+            }
+            Mode::TcpFlood | Mode::TcpConnect => {
+                tcp::run(
+                    &self.config,
+                    self.counters.clone(),
+                    self.shutdown.clone(),
+                    self.gate.clone(),
+                    end_time,
+                )
+                .await
+            }
+            Mode::UdpFlood => {
+                udp::run(
+                    &self.config,
+                    self.counters.clone(),
+                    self.shutdown.clone(),
+                    self.gate.clone(),
+                    end_time,
+                )
+                .await
+            }
+            Mode::Connect => {
+                connect::run(
+                    &self.config,
+                    self.counters.clone(),
+                    self.shutdown.clone(),
+                    self.gate.clone(),
+                    end_time,
+                )
+                .await
+            }
+            Mode::Replay => {
+                replay::run(
+                    &self.config,
+                    self.counters.clone(),
+                    self.shutdown.clone(),
+                    self.gate.clone(),
+                    end_time,
+                )
+                .await
+            }
+        }
+    }
+
+    pub async fn start_stats_reporter(&self, interval: Duration) -> Result<()> {
+        if interval.is_zero() {
+            return Err(anyhow!("Statistics interval must be greater than zero"));
+        }
+
+        let counters = self.counters.clone();
+        let mode = self.config.mode;
+        let start_time = self.stats.start_time;
+        let end_time = self.end_time();
+        let mut csv_logger = match &self.config.csv_log {
+            Some(path) => Some(CsvLogger::open(path)?),
+            None => None,
+        };
+        let progress = self.progress.clone();
+        let warmup = self.config.warmup;
+        let baseline = self.warmup_baseline.clone();
+        let mut stall_detector = self.config.stall_restart.map(StallDetector::new);
+        let alpha = self.config.ema_alpha;
+        let (mut byte_rate, mut packet_rate) = (Ema::new(alpha), Ema::new(alpha));
+        let (mut success_ema, mut failure_ema) = (Ema::new(alpha), Ema::new(alpha));
+        let stalled = Arc::clone(&self.stalled);
+        let stall_recycles = Arc::clone(&self.stall_recycles);
+        let state = self.config.state_file.clone().zip(self.previous.clone());
+
+        tokio::spawn(async move {
+            let mut ticker = interval_at((Instant::now() + interval).into(), interval);
+            ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+            let mut last_tick = Instant::now();
+            let mut last_bytes = 0u64;
+            let mut last_packets = 0u64;
+            let mut last_success = 0u64;
+            let mut last_failure = 0u64;
+            loop {
+                ticker.tick().await;
+                let now = Instant::now();
+                let seconds = now.duration_since(last_tick).as_secs_f64();
+                last_tick = now;
+
+                let success = counters.success_events.load(Ordering::Relaxed);
+                let failure = counters.failure_events.load(Ordering::Relaxed);
+                let bytes = counters.bytes_transferred.load(Ordering::Relaxed);
+                let packets = counters.packets_sent.load(Ordering::Relaxed);
+                let bytes_delta = bytes - last_bytes;
+                let packets_delta = packets - last_packets;
+
+                let mbit_per_sec = (bytes_delta as f64 * 8.0) / (seconds * 1_000_000.0);
+                let pps = packets_delta as f64 / seconds;
+                let warming_up = warmup.is_some() && !baseline.is_marked();
+                if matches!(mode, Mode::TcpFlood | Mode::UdpFlood) && !warming_up {
+                    counters.pps_history.record(pps);
+                }
+                // What gets printed is smoothed by --ema-alpha; --stall-restart and the CSV
+                // samples keep the raw figures of this interval.
+                let bytes_per_sec = byte_rate.update(bytes_delta as f64 / seconds);
+                let speed = format_rate(bytes_per_sec);
+                let shown_pps = packet_rate.update(pps);
+                let success_rate = success_ema.update((success - last_success) as f64 / seconds);
+                let failure_rate = failure_ema.update((failure - last_failure) as f64 / seconds);
+
+                let totals = baseline.apply(counters.snapshot(start_time));
+                let total_gib = totals.bytes_transferred as f64 / GIBIBYTE;
+                let phase = if warming_up { " (warmup)" } else { "" };
+                let mut active = format!(
+                    " | Active: {} (peak {})",
+                    counters.active_connections.active(),
+                    counters.active_connections.take_interval_peak()
+                );
+                if let Some((path, previous)) = &state {
+                    let lifetime = previous.with_run(&totals);
+                    if let Err(e) = lifetime.save(path) {
+                        log::warn!("{e:#}");
+                    }
+                    active += &match mode {
+                        Mode::Connect | Mode::TcpConnect => format!(
+                            " | Lifetime OK: {} | Lifetime Failed: {}",
+                            lifetime.success_events, lifetime.failure_events
+                        ),
+                        _ => format!(
+                            " | Lifetime: {:.2} GiB",
+                            lifetime.bytes_transferred as f64 / GIBIBYTE
+                        ),
+                    };
+                }
+
+                match mode {
+                    Mode::Download => {
+                        log::info!(
+                            "[HTTP] Speed: {} | Delta: {:.1} MiB | Total: {:.2} GiB{}{}",
+                            speed,
+                            bytes_delta as f64 / MEBIBYTE,
+                            total_gib,
+                            active,
+                            phase
+                        );
+                    }
+                    Mode::TcpFlood => {
+                        log::info!(
+                            "[TCP] PPS: {:.0} | Throughput: {} | Total: {:.2} GiB{}{}",
+                            shown_pps,
+                            speed,
+                            total_gib,
+                            active,
+                            phase
+                        );
                     }
                     Mode::UdpFlood => {
                         log::info!(
-                            "[UDP] PPS: {:.0} | Throughput: {:.2} MB/s ({:.0} Mbps) | Total: {:.2} GB",
-                            pps,
-                            mb_per_sec,
-                            mbit_per_sec,
-                            total_gb
+                            "[UDP] PPS: {:.0} | Throughput: {} | Total: {:.2} GiB{}{}",
+                            shown_pps,
+                            speed,
+                            total_gib,
+                            active,
+                            phase
                         );
                     }
+                    Mode::Replay => {
+                        log::info!(
+                            "[REPLAY] Req/s: {:.1} | Failed/s: {:.1} | Speed: {} | Total: {:.2} GiB{}{}",
+                            success_rate,
+                            failure_rate,
+                            speed,
+                            total_gib,
+                            active,
+                            phase
+                        );
+                    }
+                    Mode::Connect | Mode::TcpConnect => {
+                        let tag = match mode {
+                            Mode::TcpConnect => "TCP-CONNECT",
+                            _ => "CONNECT",
+                        };
+                        log::info!(
+                            "[{}] Conn/s: {:.1} | Failed/s: {:.1} | Total OK: {} | Total Failed: {}{}{}",
+                            tag,
+                            success_rate,
+                            failure_rate,
+                            totals.success_events,
+                            totals.failure_events,
+                            active,
+                            phase
+                        );
+                    }
+                }
+
+                if let Some(detector) = stall_detector.as_mut()
+                    && !warming_up
+                    && detector.observe(mbit_per_sec)
+                {
+                    let limit = detector.limit;
+                    log::warn!(
+                        "Throughput stayed below {} Mbps for {} intervals, recycling xray-core instances",
+                        limit.min_mbps,
+                        limit.intervals
+                    );
+                    stall_recycles.fetch_add(1, Ordering::Relaxed);
+                    stalled.notify_one();
+                }
+
+                if let Some(bar) = &progress {
+                    bar.set_position(start_time.elapsed().as_secs());
+                    bar.set_message(format!("{:.2} MB/s{phase}", bytes_per_sec / MEGABYTE));
+                }
+
+                if let Some(logger) = csv_logger.as_mut() {
+                    let sample = ThroughputSample {
+                        elapsed_secs: start_time.elapsed().as_secs_f64(),
+                        bytes_transferred: bytes,
+                        bytes_delta,
+                        mbps: mbit_per_sec,
+                        pps,
+                        success,
+                        failure,
+                    };
+                    if let Err(e) = logger.write_sample(&sample) {
+                        log::warn!("Failed to append CSV sample: {e}");
+                    }
                 }
 
                 last_bytes = bytes;
                 last_packets = packets;
+                last_success = success;
+                last_failure = failure;
 
                 if let Some(end) = end_time
                     && Instant::now() >= end
@@ -253,15 +1436,227 @@ impl StressRunner {
                 }
             }
         });
+
+        Ok(())
+    }
+
+    /// Worker tasks spawned for the run, per `--concurrency-mode` in every mode.
+    pub fn effective_worker_count(&self) -> usize {
+        self.config.total_workers()
+    }
+
+    /// Workers currently allowed to run; lower than the spawned count under `--auto-concurrency`.
+    pub fn active_worker_count(&self) -> usize {
+        let active = self.gate.active();
+        (0..self.config.proxy_ports.len())
+            .map(|idx| self.config.instance_workers(idx).min(active))
+            .sum()
     }
 
+    /// When workers must stop: the earlier of `--duration` and `--deadline`, if any.
+    fn end_time(&self) -> Option<Instant> {
+        let start_time = self.stats.start_time;
+        let duration_end = self.config.duration.map(|d| start_time + d);
+        let deadline_end = self.config.deadline.map(|d| start_time + d);
+        match (duration_end, deadline_end) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        }
+    }
+
+    /// `--no-restart`: stops the workers of every proxy port missing from `live`.
+    pub fn retain_ports(&self, live: &[u16]) {
+        for &port in &self.config.proxy_ports {
+            if !live.contains(&port) && self.counters.down_ports.mark(port) {
+                log::warn!("Stopping workers on port {port}: its xray-core instance is down");
+            }
+        }
+    }
+
+    /// Asks all workers to finish their current iteration and stop.
+    pub fn shutdown(&self) {
+        self.shutdown.trigger();
+    }
+
+    /// Totals since the start of the run, or since the end of `--warmup` once it has elapsed.
     pub fn get_current_stats(&self) -> StressStats {
-        self.counters.snapshot(self.stats.start_time)
+        self.warmup_baseline
+            .apply(self.counters.snapshot(self.stats.start_time))
+    }
+
+    /// Earlier runs from `--state-file` plus this one; `None` without a state file.
+    pub fn lifetime_stats(&self) -> Option<LifetimeStats> {
+        self.previous
+            .as_ref()
+            .map(|previous| previous.with_run(&self.get_current_stats()))
+    }
+
+    /// Rewrites `--state-file` with the current lifetime totals, logging instead of failing.
+    pub fn save_state(&self) {
+        if let (Some(path), Some(lifetime)) = (&self.config.state_file, self.lifetime_stats())
+            && let Err(e) = lifetime.save(path)
+        {
+            log::error!("{e:#}");
+        }
     }
 
     pub fn mode(&self) -> Mode {
         self.config.mode
     }
+
+    pub fn latency_summaries(&self) -> Vec<LatencySummary> {
+        self.counters.connect_latencies.summaries()
+    }
+
+    /// `--tls-timing` samples per `host:port`; empty unless the option is set.
+    pub fn tls_handshake_summaries(&self) -> Vec<LatencySummary> {
+        self.counters.tls_handshakes.summaries()
+    }
+
+    pub fn target_summaries(&self) -> Vec<TargetSummary> {
+        self.counters.targets.summaries()
+    }
+
+    pub fn status_counts(&self) -> Vec<(u16, u64)> {
+        self.counters.status_codes.counts()
+    }
+
+    /// Min/avg/max/stddev of the per-interval packet rate; flood modes only.
+    pub fn pps_summary(&self) -> Option<PpsSummary> {
+        self.counters.pps_history.summary()
+    }
+
+    /// Highest workers-per-instance count that finished an interval under the
+    /// `--auto-concurrency` failure threshold; `None` when auto-scaling is off.
+    pub fn peak_stable_concurrency(&self) -> Option<usize> {
+        self.config
+            .auto_concurrency
+            .map(|_| self.peak_stable.load(Ordering::Relaxed))
+    }
+
+    /// Resolves each time the stats reporter detects a `--stall-restart` stall.
+    pub async fn stalled(&self) {
+        self.stalled.notified().await;
+    }
+
+    /// How long `--connect-only-first` held the load back, once it has finished.
+    pub fn connect_warmup_duration(&self) -> Option<Duration> {
+        self.connect_warmup.as_ref().and_then(ConnectWarmup::took)
+    }
+
+    /// How many times `--stall-restart` asked for an instance recycle.
+    pub fn stall_recycles(&self) -> u64 {
+        self.stall_recycles.load(Ordering::Relaxed)
+    }
+
+    /// Connections or requests in progress right now.
+    pub fn active_connections(&self) -> u64 {
+        self.counters.active_connections.active()
+    }
+
+    /// Most connections or requests that were in progress at the same time.
+    pub fn peak_active_connections(&self) -> u64 {
+        self.counters.active_connections.peak()
+    }
+
+    /// In-flight workers at the end of `--duration` and how `--ramp-down` resolved them.
+    pub fn ramp_down_summary(&self) -> Option<RampDownSummary> {
+        self.counters.ramp_down.lock().ok().and_then(|s| *s)
+    }
+}
+
+/// Starts the reported totals over from the current counters.
+fn reset_baseline(counters: &SharedCounters, baseline: &WarmupBaseline) {
+    baseline.mark(counters.snapshot(Instant::now()));
+    counters.connect_latencies.clear();
+    counters.tls_handshakes.clear();
+    counters.targets.clear();
+    counters.status_codes.clear();
+    counters.pps_history.clear();
+}
+
+/// Ramp-down only follows the end of `--duration` and never runs past the `--deadline` hard stop.
+fn ramp_down_window(config: &StressConfig) -> Option<Duration> {
+    let window = config.ramp_down.filter(|w| !w.is_zero())?;
+    let duration = config.duration?;
+    match config.deadline {
+        Some(deadline) if deadline <= duration => None,
+        Some(deadline) => Some(window.min(deadline - duration)),
+        None => Some(window),
+    }
+}
+
+/// Workers added per interval (and the starting count) in `--auto-concurrency` mode.
+fn auto_concurrency_step(max: usize) -> usize {
+    (max / 20).max(1)
+}
+
+/// Additive increase while the interval stayed under the threshold, halve when it did not.
+fn next_concurrency(current: usize, max: usize, failure_rate: f64, threshold: f64) -> usize {
+    if failure_rate > threshold {
+        (current / 2).max(1)
+    } else {
+        (current + auto_concurrency_step(max)).min(max)
+    }
+}
+
+async fn auto_scale(
+    auto: AutoConcurrency,
+    max: usize,
+    gate: ConcurrencyGate,
+    counters: SharedCounters,
+    peak_stable: Arc<AtomicUsize>,
+) {
+    let mut ticker = interval_at((Instant::now() + auto.interval).into(), auto.interval);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+    let mut last_success = counters.success_events.load(Ordering::Relaxed);
+    let mut last_failure = counters.failure_events.load(Ordering::Relaxed);
+    loop {
+        ticker.tick().await;
+        let success = counters.success_events.load(Ordering::Relaxed);
+        let failure = counters.failure_events.load(Ordering::Relaxed);
+        let window = StressStats {
+            success_events: success - last_success,
+            failure_events: failure - last_failure,
+            ..StressStats::new()
+        };
+        last_success = success;
+        last_failure = failure;
+
+        // Nothing finished this interval, so there is no signal to scale on.
+        if window.success_events + window.failure_events == 0 {
+            continue;
+        }
+
+        let current = gate.active();
+        let failure_rate = window.failure_rate();
+        if failure_rate <= auto.max_failure_rate {
+            peak_stable.fetch_max(current, Ordering::Relaxed);
+        }
+
+        let next = next_concurrency(current, max, failure_rate, auto.max_failure_rate);
+        if next != current {
+            log::info!(
+                "Auto-concurrency: {:.1}% failures at {} workers per instance, switching to {}",
+                failure_rate,
+                current,
+                next
+            );
+            gate.set_active(next);
+        }
+    }
+}
+
+fn build_progress_bar(duration: Duration) -> ProgressBar {
+    let bar = ProgressBar::with_draw_target(Some(duration.as_secs()), ProgressDrawTarget::stdout());
+    let style = ProgressStyle::with_template(
+        "{elapsed_precise} [{wide_bar:.cyan/blue}] {pos}/{len}s | {msg}",
+    )
+    .unwrap_or_else(|_| ProgressStyle::default_bar())
+    .progress_chars("=> ");
+    bar.set_style(style);
+    bar
 }
 
 pub fn resolve_targets(mode: Mode, raw: Option<&str>) -> Result<Vec<Target>> {
@@ -274,7 +1669,8 @@ pub fn resolve_targets(mode: Mode, raw: Option<&str>) -> Result<Vec<Target>> {
             .iter()
             .map(|url| Target::Http((*url).to_string()))
             .collect()),
-        Mode::TcpFlood | Mode::UdpFlood => Err(anyhow!(
+        Mode::Replay => Ok(Vec::new()),
+        Mode::TcpFlood | Mode::UdpFlood | Mode::TcpConnect | Mode::Connect => Err(anyhow!(
             "Mode {mode:?} requires --targets with host:port entries"
         )),
     }
@@ -289,8 +1685,10 @@ pub fn parse_target_list(raw: &str, mode: Mode) -> Result<Vec<Target>> {
         }
 
         let target = match mode {
-            Mode::Download => parse_http_target(token)?,
-            Mode::TcpFlood | Mode::UdpFlood => parse_socket_target(token)?,
+            Mode::Download | Mode::Replay => parse_http_target(token)?,
+            Mode::TcpFlood | Mode::UdpFlood | Mode::TcpConnect | Mode::Connect => {
+                parse_socket_target(token)?
+            }
         };
         targets.push(target);
     }
@@ -302,7 +1700,40 @@ pub fn parse_target_list(raw: &str, mode: Mode) -> Result<Vec<Target>> {
     Ok(targets)
 }
 
+/// `--targets-file`: one target per line, blank lines and `#` comments ignored.
+pub fn load_targets_file(path: &Path, mode: Mode) -> Result<Vec<Target>> {
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read targets file {}", path.display()))?;
+    parse_target_lines(&content, mode)
+}
+
+fn parse_target_lines(content: &str, mode: Mode) -> Result<Vec<Target>> {
+    let mut targets = Vec::new();
+    for (line_num, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let parsed = parse_target_list(line, mode)
+            .with_context(|| format!("Invalid target on line {}", line_num + 1))?;
+        targets.extend(parsed);
+    }
+
+    if targets.is_empty() {
+        return Err(anyhow!("No targets found in targets file"));
+    }
+
+    Ok(targets)
+}
+
 fn parse_http_target(token: &str) -> Result<Target> {
+    // Bare `host:port` (or `host/path`) tokens default to plain HTTP.
+    if !token.contains("://") {
+        let url = Url::parse(&format!("http://{token}"))
+            .map_err(|e| anyhow!("Invalid HTTP target {token}: {e}"))?;
+        return Ok(Target::Http(url.to_string()));
+    }
+
     let url = Url::parse(token).map_err(|e| anyhow!("Invalid HTTP target {token}: {e}"))?;
     match url.scheme() {
         "http" | "https" => Ok(Target::Http(token.to_string())),
@@ -347,14 +1778,102 @@ fn parse_socket_target(token: &str) -> Result<Target> {
     }))
 }
 
-pub(crate) fn build_payload(size: usize) -> Vec<u8> {
+/// How long a flood target's host name may take to resolve for the private range check.
+const RESOLVE_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Names the range `ip` falls in if a flood must not hit it without `--allow-private`.
+fn restricted_range(ip: IpAddr) -> Option<&'static str> {
+    match ip.to_canonical() {
+        IpAddr::V4(v4) if v4.is_loopback() => Some("loopback"),
+        IpAddr::V4(v4) if v4.is_private() => Some("private"),
+        IpAddr::V4(v4) if v4.is_link_local() => Some("link-local"),
+        IpAddr::V4(v4) if v4.is_unspecified() => Some("unspecified"),
+        IpAddr::V6(v6) if v6.is_loopback() => Some("loopback"),
+        IpAddr::V6(v6) if v6.is_unique_local() => Some("private"),
+        IpAddr::V6(v6) if v6.is_unicast_link_local() => Some("link-local"),
+        IpAddr::V6(v6) if v6.is_unspecified() => Some("unspecified"),
+        _ => None,
+    }
+}
+
+/// Addresses `host` stands for; names that do not resolve locally are skipped with a warning,
+/// since the private range check cannot cover them.
+async fn resolve_host(host: &str, port: u16) -> Vec<IpAddr> {
+    if let Ok(ip) = host.parse::<IpAddr>() {
+        return vec![ip];
+    }
+    let host_lower = host.to_ascii_lowercase();
+    if host_lower == "localhost" || host_lower.ends_with(".localhost") {
+        return vec![IpAddr::V4(Ipv4Addr::LOCALHOST)];
+    }
+    match timeout(RESOLVE_TIMEOUT, lookup_host((host, port))).await {
+        Ok(Ok(addrs)) => addrs.map(|addr| addr.ip()).collect(),
+        _ => {
+            log::warn!(
+                "Flood target {host}:{port} does not resolve locally and was NOT checked against \
+                 private ranges; the proxy may still reach a non-public address"
+            );
+            Vec::new()
+        }
+    }
+}
+
+/// Warns about every socket target on a loopback, private, link-local or unspecified address
+/// and refuses the run unless `allow_private` is set.
+pub async fn check_target_ranges(targets: &[Target], allow_private: bool) -> Result<()> {
+    let mut restricted = Vec::new();
+    for target in targets {
+        let Target::Socket(socket) = target else {
+            continue;
+        };
+        for ip in resolve_host(&socket.host, socket.port).await {
+            if let Some(range) = restricted_range(ip) {
+                log::warn!(
+                    "Flood target {} is a {} address ({})",
+                    socket.display(),
+                    range,
+                    ip
+                );
+                restricted.push(socket.display());
+                break;
+            }
+        }
+    }
+
+    if restricted.is_empty() {
+        return Ok(());
+    }
+    if allow_private {
+        log::warn!(
+            "Flooding {} non-public target(s) because --allow-private is set",
+            restricted.len()
+        );
+        return Ok(());
+    }
+    Err(anyhow!(
+        "Refusing to flood non-public targets {}; pass --allow-private if this is intended",
+        restricted.join(", ")
+    ))
+}
+
+const TEXT_PAYLOAD: &[u8] = b"herscat payload 0123456789 abcdefghijklmnopqrstuvwxyz\n";
+
+pub(crate) fn build_payload(size: usize, pattern: PayloadPattern, rng: &mut StdRng) -> Vec<u8> {
     use rand::Rng;
-    let mut payload = vec![0u8; size.max(1)];
-    rand::rng().fill(payload.as_mut_slice());
-    payload
+    let size = size.max(1);
+    match pattern {
+        PayloadPattern::Random => {
+            let mut payload = vec![0u8; size];
+            rng.fill(payload.as_mut_slice());
+            payload
+        }
+        PayloadPattern::Zero => vec![0u8; size],
+        PayloadPattern::Incrementing => (0..size).map(|i| i as u8).collect(),
+        PayloadPattern::Text => TEXT_PAYLOAD.iter().copied().cycle().take(size).collect(),
+    }
 }
 
-pub(crate) fn packet_interval(rate: Option<u32>) -> Option<Duration> {
+fn packet_interval(rate: Option<u32>) -> Option<Duration> {
     rate.and_then(|pps| {
         if pps == 0 {
             None
@@ -364,30 +1883,599 @@ pub(crate) fn packet_interval(rate: Option<u32>) -> Option<Duration> {
     })
 }
 
+/// Builds the think-time range from optional `--jitter-min`/`--jitter-max` values.
+///
+/// A missing bound falls back to the other one (or zero for the minimum).
+pub fn jitter_range(min_ms: Option<u64>, max_ms: Option<u64>) -> Option<(Duration, Duration)> {
+    let (min, max) = match (min_ms, max_ms) {
+        (None, None) => return None,
+        (Some(min), None) => (min, min),
+        (None, Some(max)) => (0, max),
+        (Some(min), Some(max)) => (min.min(max), max.max(min)),
+    };
+    Some((Duration::from_millis(min), Duration::from_millis(max)))
+}
+
+pub(crate) async fn apply_jitter(jitter: Option<(Duration, Duration)>, rng: &mut StdRng) {
+    use rand::Rng;
+    if let Some((min, max)) = jitter {
+        let pause = if max > min {
+            rng.random_range(min..=max)
+        } else {
+            min
+        };
+        if !pause.is_zero() {
+            sleep(pause).await;
+        }
+    }
+}
+
+/// Random source seeded with `seed`, or from the thread-local generator when no `--seed` is set.
+pub(crate) fn seeded_rng(seed: Option<u64>) -> StdRng {
+    match seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_rng(&mut rand::rng()),
+    }
+}
+
+/// Per-worker random source for target picks, User-Agents and jitter.
+///
+/// With a `--seed` every worker draws its own sequence, and the same seed, worker id and target
+/// set reproduce it exactly on the next run.
+pub(crate) fn worker_rng(seed: Option<u64>, worker_id: usize) -> StdRng {
+    const MIX: u64 = 0x9E37_79B9_7F4A_7C15;
+    seeded_rng(seed.map(|seed| seed ^ (worker_id as u64 + 1).wrapping_mul(MIX)))
+}
+
+/// Hands each worker its own, per-worker seeded ordering of `targets` when `shuffle` is set.
+pub(crate) fn worker_targets<T: Clone>(
+    targets: &Arc<Vec<T>>,
+    worker_id: usize,
+    shuffle: bool,
+    seed: Option<u64>,
+) -> Arc<Vec<T>> {
+    if !shuffle {
+        return Arc::clone(targets);
+    }
+    let mut order = targets.as_ref().clone();
+    order.shuffle(&mut worker_rng(seed, worker_id));
+    Arc::new(order)
+}
+
+/// How a worker picks its next target: at random, or in order through its `--shuffle-targets`
+/// list, so the per-worker shuffle decides what gets hit when.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct TargetCursor {
+    next: Option<usize>,
+}
+
+impl TargetCursor {
+    pub(crate) fn new(shuffle: bool) -> Self {
+        Self {
+            next: shuffle.then_some(0),
+        }
+    }
+
+    /// Index of the next target in a list of `len` entries.
+    pub(crate) fn pick(&mut self, len: usize, rng: &mut StdRng) -> usize {
+        use rand::Rng;
+
+        match &mut self.next {
+            Some(next) => {
+                let idx = *next % len;
+                *next = idx + 1;
+                idx
+            }
+            None => rng.random_range(0..len),
+        }
+    }
+}
+
+/// Opens a SOCKS5 connection to `target` through the local inbound at `proxy`.
+pub(crate) async fn socks_connect(
+    proxy: SocketAddr,
+    target: &SocketTarget,
+    auth: Option<&InboundAuth>,
+) -> std::result::Result<Socks5Stream<TcpStream>, tokio_socks::Error> {
+    let destination = (target.host.as_str(), target.port);
+    match auth {
+        Some(auth) => {
+            Socks5Stream::connect_with_password(proxy, destination, &auth.user, &auth.pass).await
+        }
+        None => Socks5Stream::connect(proxy, destination).await,
+    }
+}
+
 pub(crate) async fn supervise_workers(
     handles: Vec<JoinHandle<()>>,
     end_time: Option<Instant>,
+    ramp_down: Option<Duration>,
+    counters: &SharedCounters,
+    limits: RunLimits,
+    shutdown: &ShutdownSignal,
 ) -> Result<()> {
     if handles.is_empty() {
         return Err(anyhow!("No worker tasks spawned"));
     }
 
-    if let Some(end) = end_time {
-        let now = Instant::now();
-        if end > now {
-            sleep(end - now).await;
+    loop {
+        if let Some(end) = end_time
+            && Instant::now() >= end
+        {
+            if let Some(window) = ramp_down {
+                let summary = ramp_down_workers(&handles, window, shutdown).await;
+                if let Ok(mut slot) = counters.ramp_down.lock() {
+                    *slot = Some(summary);
+                }
+            }
+            break;
+        }
+        if counters.limit_reached(limits) {
+            log::info!("Request or byte limit reached, stopping workers");
+            break;
         }
-        for handle in &handles {
-            handle.abort();
+        if shutdown.is_triggered() || handles.iter().all(|handle| handle.is_finished()) {
+            break;
         }
+        sleep(SUPERVISOR_TICK).await;
     }
+    shutdown.trigger();
+
+    let abort_handles: Vec<AbortHandle> = handles.iter().map(|h| h.abort_handle()).collect();
+    let results = match timeout(SHUTDOWN_GRACE, join_all(handles)).await {
+        Ok(results) => results,
+        Err(_) => {
+            let pending = abort_handles.iter().filter(|h| !h.is_finished()).count();
+            log::debug!("{pending} workers still busy after shutdown grace period, aborting");
+            for handle in &abort_handles {
+                handle.abort();
+            }
+            return Ok(());
+        }
+    };
 
-    let results = join_all(handles).await;
     for (idx, result) in results.into_iter().enumerate() {
-        if let Err(e) = result {
+        if let Err(e) = result
+            && e.is_panic()
+        {
             log::error!("Worker {idx} panicked: {e}");
         }
     }
 
     Ok(())
 }
+
+/// Stops new work and waits up to `window` for the workers that are still busy to finish.
+async fn ramp_down_workers(
+    handles: &[JoinHandle<()>],
+    window: Duration,
+    shutdown: &ShutdownSignal,
+) -> RampDownSummary {
+    shutdown.drain();
+    let busy = || handles.iter().filter(|h| !h.is_finished()).count();
+    let in_flight = busy();
+    log::info!(
+        "Duration reached, ramping down {} busy workers for up to {}s",
+        in_flight,
+        window.as_secs_f64()
+    );
+
+    let ramp_end = Instant::now() + window;
+    while busy() > 0 && Instant::now() < ramp_end && !shutdown.is_triggered() {
+        sleep(SUPERVISOR_TICK).await;
+    }
+
+    let aborted = busy();
+    RampDownSummary {
+        in_flight,
+        completed: in_flight - aborted,
+        aborted,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_codes_are_counted_in_order() {
+        let tracker = StatusTracker::default();
+        for status in [502, 200, 407, 200, 200] {
+            tracker.record(status);
+        }
+        assert_eq!(tracker.counts(), [(200, 3), (407, 1), (502, 1)]);
+
+        tracker.clear();
+        assert!(tracker.counts().is_empty());
+    }
+
+    #[test]
+    fn test_pps_history_summary_and_ring_buffer() {
+        let history = PpsHistory::default();
+        assert!(history.summary().is_none());
+        for pps in [100.0, 200.0, 300.0] {
+            history.record(pps);
+        }
+        let summary = history.summary().unwrap();
+        assert_eq!(summary.samples, 3);
+        assert_eq!(
+            (summary.min, summary.avg, summary.max),
+            (100.0, 200.0, 300.0)
+        );
+        assert!((summary.stddev - 81.65).abs() < 0.01);
+
+        history.clear();
+        history.record(0.0);
+        for _ in 0..PPS_HISTORY_LEN {
+            history.record(50.0);
+        }
+        let summary = history.summary().unwrap();
+        assert_eq!(summary.samples, PPS_HISTORY_LEN);
+        assert_eq!((summary.min, summary.stddev), (50.0, 0.0));
+    }
+
+    #[test]
+    fn test_latency_summary_percentiles() {
+        let tracker = LatencyTracker::default();
+        for ms in (1..=100).rev() {
+            tracker.record("example.com:443", Duration::from_millis(ms));
+        }
+        let summaries = tracker.summaries();
+        assert_eq!(summaries.len(), 1);
+        assert_eq!(summaries[0].samples, 100);
+        assert_eq!(summaries[0].p50, Duration::from_millis(50));
+        assert_eq!(summaries[0].p99, Duration::from_millis(99));
+    }
+
+    #[test]
+    fn test_latency_tracker_memory_is_bounded() {
+        let tracker = LatencyTracker::default();
+        let count = LATENCY_RESERVOIR_LEN * 3;
+        for _ in 0..count {
+            tracker.record("example.com:443", Duration::from_millis(20));
+        }
+        let samples = tracker.samples.lock().unwrap();
+        assert_eq!(
+            samples["example.com:443"].reservoir.len(),
+            LATENCY_RESERVOIR_LEN
+        );
+        drop(samples);
+
+        let summary = &tracker.summaries()[0];
+        assert_eq!(summary.samples, count);
+        assert_eq!(summary.average, Duration::from_millis(20));
+        assert_eq!(summary.p99, Duration::from_millis(20));
+    }
+
+    #[test]
+    fn test_result_line_is_single_key_value_line() {
+        let stats = StressStats {
+            success_events: 4210,
+            failure_events: 12,
+            bytes_transferred: 100_000_000,
+            start_time: Instant::now() - Duration::from_secs(10),
+            ..StressStats::new()
+        };
+        assert_eq!(
+            stats.result_line(Mode::Download),
+            "RESULT mode=download bytes=100000000 mbps=80.0 success=4210 failure=12 duration=10.0"
+        );
+    }
+
+    #[test]
+    fn test_auto_concurrency_ramps_up_and_halves() {
+        assert_eq!(auto_concurrency_step(200), 10);
+        assert_eq!(auto_concurrency_step(5), 1);
+
+        assert_eq!(next_concurrency(10, 200, 1.0, 5.0), 20);
+        assert_eq!(next_concurrency(195, 200, 0.0, 5.0), 200);
+        assert_eq!(next_concurrency(80, 200, 12.5, 5.0), 40);
+        assert_eq!(next_concurrency(1, 200, 50.0, 5.0), 1);
+    }
+
+    #[test]
+    fn test_build_payload_patterns() {
+        let rng = &mut seeded_rng(None);
+        assert_eq!(build_payload(4, PayloadPattern::Zero, rng), vec![0; 4]);
+        assert_eq!(build_payload(0, PayloadPattern::Random, rng).len(), 1);
+
+        let seeded = build_payload(64, PayloadPattern::Random, &mut seeded_rng(Some(7)));
+        let again = build_payload(64, PayloadPattern::Random, &mut seeded_rng(Some(7)));
+        assert_eq!(seeded, again);
+
+        let incrementing = build_payload(300, PayloadPattern::Incrementing, rng);
+        assert_eq!(&incrementing[..3], &[0, 1, 2]);
+        assert_eq!(incrementing[255], 255);
+        assert_eq!(incrementing[256], 0);
+
+        let text = build_payload(TEXT_PAYLOAD.len() + 7, PayloadPattern::Text, rng);
+        assert!(text.iter().all(|b| b.is_ascii()));
+        assert_eq!(&text[TEXT_PAYLOAD.len()..], &TEXT_PAYLOAD[..7]);
+    }
+
+    #[test]
+    fn test_download_targets_default_to_http() {
+        let targets = parse_target_list(
+            "example.com:8080, https://example.org/1GB.bin, 10.0.0.1:81/file.bin",
+            Mode::Download,
+        )
+        .unwrap();
+        let urls: Vec<&str> = targets
+            .iter()
+            .map(|t| match t {
+                Target::Http(url) => url.as_str(),
+                Target::Socket(_) => panic!("expected HTTP target"),
+            })
+            .collect();
+        assert_eq!(
+            urls,
+            [
+                "http://example.com:8080/",
+                "https://example.org/1GB.bin",
+                "http://10.0.0.1:81/file.bin"
+            ]
+        );
+
+        assert!(parse_target_list("ftp://example.com/x", Mode::Download).is_err());
+    }
+
+    #[test]
+    fn test_target_lines_skip_blanks_and_comments() {
+        let content = "# lab targets\n\n10.0.0.1:80\n  10.0.0.2:443\n";
+        let targets = parse_target_lines(content, Mode::TcpFlood).unwrap();
+        assert_eq!(targets.len(), 2);
+
+        let err = parse_target_lines("10.0.0.1:80\nnope\n", Mode::TcpFlood).unwrap_err();
+        assert_eq!(err.to_string(), "Invalid target on line 2");
+        assert!(parse_target_lines("# only comments\n", Mode::TcpFlood).is_err());
+    }
+
+    #[test]
+    fn test_total_concurrency_is_split_across_ports() {
+        let config = StressConfig {
+            concurrency: 10,
+            concurrency_mode: ConcurrencyMode::Total,
+            proxy_ports: vec![10801, 10802, 10803],
+            ..Default::default()
+        };
+        let split: Vec<usize> = (0..3).map(|idx| config.instance_workers(idx)).collect();
+        assert_eq!(split, [4, 3, 3]);
+        assert_eq!(config.total_workers(), 10);
+        assert_eq!(config.max_instance_workers(), 4);
+
+        let per_instance = StressConfig {
+            concurrency_mode: ConcurrencyMode::PerInstance,
+            ..config
+        };
+        assert_eq!(per_instance.total_workers(), 30);
+    }
+
+    #[test]
+    fn test_total_pps_is_split_across_workers() {
+        let config = StressConfig {
+            proxy_ports: vec![10800, 10801],
+            concurrency: 50,
+            total_pps: Some(1000),
+            ..StressConfig::default()
+        };
+        assert_eq!(config.send_interval(), Some(Duration::from_millis(100)));
+
+        let per_worker = StressConfig {
+            packet_rate: Some(200),
+            total_pps: None,
+            ..config
+        };
+        assert_eq!(per_worker.send_interval(), Some(Duration::from_millis(5)));
+        assert_eq!(StressConfig::default().send_interval(), None);
+    }
+
+    #[test]
+    fn test_restricted_ranges() {
+        let range = |ip: &str| restricted_range(ip.parse().unwrap());
+        assert_eq!(range("127.0.0.1"), Some("loopback"));
+        assert_eq!(range("192.168.1.10"), Some("private"));
+        assert_eq!(range("169.254.0.1"), Some("link-local"));
+        assert_eq!(range("::ffff:10.1.2.3"), Some("private"));
+        assert_eq!(range("fe80::1"), Some("link-local"));
+        assert_eq!(range("8.8.8.8"), None);
+        assert_eq!(range("2606:4700::1111"), None);
+    }
+
+    #[tokio::test]
+    async fn test_check_target_ranges_refuses_private_targets() {
+        let targets = |raw| parse_target_list(raw, Mode::UdpFlood).unwrap();
+
+        let err = check_target_ranges(&targets("127.0.0.1:80"), false)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("127.0.0.1:80"));
+        assert!(err.to_string().contains("--allow-private"));
+
+        let private = targets("1.1.1.1:53,10.0.0.1:80");
+        assert!(check_target_ranges(&private, false).await.is_err());
+        assert!(check_target_ranges(&private, true).await.is_ok());
+
+        let public = targets("1.1.1.1:80");
+        assert!(check_target_ranges(&public, false).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_check_target_ranges_lets_unresolvable_targets_through() {
+        // `.invalid` never resolves (RFC 6761), so the check cannot tell where it points.
+        assert!(resolve_host("herscat.invalid", 80).await.is_empty());
+        let targets = parse_target_list("herscat.invalid:80", Mode::TcpFlood).unwrap();
+        assert!(check_target_ranges(&targets, false).await.is_ok());
+    }
+
+    #[test]
+    fn test_worker_targets_shuffle_per_worker() {
+        let targets = Arc::new((0..32).collect::<Vec<u32>>());
+
+        let shared = worker_targets(&targets, 3, false, None);
+        assert!(Arc::ptr_eq(&shared, &targets));
+
+        let first = worker_targets(&targets, 1, true, Some(0));
+        let second = worker_targets(&targets, 2, true, Some(0));
+        assert_ne!(first, second);
+        assert_eq!(first, worker_targets(&targets, 1, true, Some(0)));
+        assert_ne!(first, worker_targets(&targets, 1, true, Some(42)));
+
+        let mut sorted = first.as_ref().clone();
+        sorted.sort_unstable();
+        assert_eq!(sorted, *targets);
+    }
+
+    #[test]
+    fn test_target_cursor_walks_shuffled_list_in_order() {
+        let rng = &mut seeded_rng(Some(1));
+        let mut cursor = TargetCursor::new(true);
+        let picks: Vec<usize> = (0..7).map(|_| cursor.pick(3, rng)).collect();
+        assert_eq!(picks, [0, 1, 2, 0, 1, 2, 0]);
+
+        let mut random = TargetCursor::new(false);
+        assert!((0..50).all(|_| random.pick(3, rng) < 3));
+    }
+
+    #[test]
+    fn test_worker_rng_repeats_target_picks_for_a_seed() {
+        use rand::Rng;
+        let picks = |seed, worker_id| {
+            let mut rng = worker_rng(seed, worker_id);
+            let picks: Vec<u32> = (0..64).map(|_| rng.random_range(0..10)).collect();
+            picks
+        };
+
+        assert_eq!(picks(Some(42), 3), picks(Some(42), 3));
+        assert_ne!(picks(Some(42), 3), picks(Some(42), 4));
+        assert_ne!(picks(Some(42), 3), picks(Some(43), 3));
+    }
+
+    #[test]
+    fn test_concurrency_gate_admits_lower_slots() {
+        let gate = ConcurrencyGate::new(2, ShutdownSignal::default());
+        assert!(gate.admits(0) && gate.admits(1));
+        assert!(!gate.admits(2));
+
+        gate.set_active(3);
+        assert!(gate.admits(2));
+    }
+
+    #[tokio::test]
+    async fn test_connect_warmup_holds_workers_until_all_arrive() {
+        let warmup = ConnectWarmup::new(3, Instant::now());
+        let gate = ConcurrencyGate::new(3, ShutdownSignal::default()).with_warmup(warmup.clone());
+        assert!(!gate.warmup_ticket(3).is_pending());
+
+        let mut first = gate.warmup_ticket(0);
+        let waiting = tokio::spawn(async move { first.arrive(true).await });
+        let mut second = gate.warmup_ticket(1);
+        let _ = timeout(Duration::from_millis(300), second.arrive(false)).await;
+        assert!(!waiting.is_finished() && !warmup.is_finished());
+
+        // A worker that stops before connecting still counts as arrived.
+        drop(gate.warmup_ticket(2));
+        timeout(Duration::from_secs(1), waiting)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(warmup.took().is_some());
+        assert_eq!(warmup.connected.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn test_connection_gauge_tracks_active_and_peaks() {
+        let gauge = ConnectionGauge::default();
+        let first = gauge.open();
+        let second = gauge.open();
+        assert_eq!((gauge.active(), gauge.peak()), (2, 2));
+
+        drop(first);
+        assert_eq!(gauge.take_interval_peak(), 2);
+        drop(second);
+        assert_eq!(gauge.active(), 0);
+        assert_eq!(gauge.take_interval_peak(), 1);
+        assert_eq!(gauge.take_interval_peak(), 0);
+        assert_eq!(gauge.peak(), 2);
+    }
+
+    #[test]
+    fn test_rate_units() {
+        let stats = StressStats {
+            bytes_transferred: 125_000_000,
+            start_time: Instant::now() - Duration::from_secs(10),
+            ..StressStats::new()
+        };
+        assert!((stats.megabytes_per_second() - 12.5).abs() < 0.01);
+        assert!((stats.mebibytes_per_second() - 11.92).abs() < 0.01);
+        assert_eq!(
+            format_rate(12_500_000.0),
+            "12.50 MB/s (11.92 MiB/s, 100 Mbps)"
+        );
+    }
+
+    #[test]
+    fn test_ema_smooths_unless_alpha_is_one() {
+        let mut smoothed = Ema::new(0.5);
+        assert_eq!(smoothed.update(100.0), 100.0);
+        assert_eq!(smoothed.update(0.0), 50.0);
+        assert_eq!(smoothed.update(0.0), 25.0);
+
+        let mut raw = Ema::new(1.0);
+        raw.update(100.0);
+        assert_eq!(raw.update(7.0), 7.0);
+    }
+
+    #[test]
+    fn test_stall_detector_needs_consecutive_slow_intervals() {
+        let mut detector = StallDetector::new(StallRestart {
+            min_mbps: 10.0,
+            intervals: 2,
+        });
+
+        assert!(!detector.observe(1.0));
+        assert!(!detector.observe(50.0));
+        assert!(!detector.observe(1.0));
+        assert!(detector.observe(0.0));
+        assert!(!detector.observe(0.0));
+        assert!(detector.observe(9.9));
+    }
+
+    #[test]
+    fn test_ramp_down_window_respects_deadline() {
+        let mut config = StressConfig {
+            ramp_down: Some(Duration::from_secs(10)),
+            ..StressConfig::default()
+        };
+        assert_eq!(ramp_down_window(&config), None);
+
+        config.duration = Some(Duration::from_secs(30));
+        assert_eq!(ramp_down_window(&config), Some(Duration::from_secs(10)));
+
+        config.deadline = Some(Duration::from_secs(35));
+        assert_eq!(ramp_down_window(&config), Some(Duration::from_secs(5)));
+
+        config.deadline = Some(Duration::from_secs(30));
+        assert_eq!(ramp_down_window(&config), None);
+    }
+
+    #[tokio::test]
+    async fn test_ramp_down_counts_finished_and_aborted_workers() {
+        let shutdown = ShutdownSignal::default();
+        let handles = vec![
+            tokio::spawn(sleep(Duration::from_millis(50))),
+            tokio::spawn(sleep(Duration::from_secs(60))),
+        ];
+
+        let summary = ramp_down_workers(&handles, Duration::from_millis(500), &shutdown).await;
+        assert!(shutdown.is_draining() && !shutdown.is_triggered());
+        assert_eq!(
+            summary,
+            RampDownSummary {
+                in_flight: 2,
+                completed: 1,
+                aborted: 1,
+            }
+        );
+        handles.iter().for_each(JoinHandle::abort);
+    }
+}
@@ -0,0 +1,320 @@
+use super::download::{build_clients, warm_up};
+use super::failure::{FailureKind, classify_reqwest};
+use super::{
+    ConcurrencyGate, RunLimits, SharedCounters, ShutdownSignal, StressConfig, WarmupTicket,
+    apply_jitter, supervise_workers, worker_rng,
+};
+use anyhow::{Context, Result, anyhow};
+use futures::StreamExt;
+use rand::rngs::StdRng;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::{Client, Method};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+use url::Url;
+
+/// One line of a `--replay-file`, e.g.
+/// `{"method": "POST", "url": "https://api.example.com/v1/items", "headers": {"Content-Type": "application/json"}, "body": "{}"}`.
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct ReplaySpec {
+    #[serde(default)]
+    method: Option<String>,
+    url: String,
+    #[serde(default)]
+    headers: BTreeMap<String, String>,
+    #[serde(default)]
+    body: Option<String>,
+}
+
+/// A validated request from the replay file, rebuilt for every execution.
+#[derive(Debug, Clone)]
+pub struct ReplayRequest {
+    pub method: Method,
+    pub url: Url,
+    pub headers: HeaderMap,
+    pub body: Option<String>,
+}
+
+impl ReplayRequest {
+    fn from_spec(spec: ReplaySpec) -> Result<Self> {
+        let method = match spec.method {
+            Some(method) => Method::from_bytes(method.to_ascii_uppercase().as_bytes())
+                .with_context(|| format!("Invalid HTTP method {method}"))?,
+            None => Method::GET,
+        };
+
+        let url = Url::parse(&spec.url).with_context(|| format!("Invalid URL {}", spec.url))?;
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err(anyhow!(
+                "Unsupported scheme in {url}, expected http or https"
+            ));
+        }
+
+        let mut headers = HeaderMap::new();
+        for (name, value) in &spec.headers {
+            let name = HeaderName::from_bytes(name.as_bytes())
+                .with_context(|| format!("Invalid header name {name}"))?;
+            let value = HeaderValue::from_str(value)
+                .with_context(|| format!("Invalid value for header {name}"))?;
+            headers.append(name, value);
+        }
+
+        Ok(Self {
+            method,
+            url,
+            headers,
+            body: spec.body,
+        })
+    }
+
+    /// Key used for the per-endpoint summary, e.g. `POST https://api.example.com/v1/items`.
+    fn label(&self) -> String {
+        format!("{} {}", self.method, self.url)
+    }
+
+    fn build(&self, client: &Client) -> reqwest::Result<reqwest::Request> {
+        let mut builder = client
+            .request(self.method.clone(), self.url.clone())
+            .headers(self.headers.clone());
+        if let Some(body) = &self.body {
+            builder = builder.body(body.clone());
+        }
+        builder.build()
+    }
+}
+
+/// `--replay-file`: one JSON request per line, blank lines and `#` comments ignored.
+pub fn load_replay_file(path: &Path) -> Result<Vec<ReplayRequest>> {
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read replay file {}", path.display()))?;
+    parse_replay_lines(&content)
+}
+
+fn parse_replay_lines(content: &str) -> Result<Vec<ReplayRequest>> {
+    let mut requests = Vec::new();
+    for (line_num, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let request = serde_json::from_str(line)
+            .map_err(anyhow::Error::from)
+            .and_then(ReplayRequest::from_spec)
+            .with_context(|| format!("Invalid replay request on line {}", line_num + 1))?;
+        requests.push(request);
+    }
+
+    if requests.is_empty() {
+        return Err(anyhow!("No requests found in replay file"));
+    }
+
+    Ok(requests)
+}
+
+pub async fn run(
+    config: &StressConfig,
+    counters: SharedCounters,
+    shutdown: ShutdownSignal,
+    gate: ConcurrencyGate,
+    end_time: Option<Instant>,
+) -> Result<()> {
+    if config.replay.is_empty() {
+        return Err(anyhow!("No requests configured for replay mode"));
+    }
+
+    let clients = build_clients(config)?;
+    let requests = Arc::new(config.replay.clone());
+    let mut handles: Vec<JoinHandle<()>> = Vec::new();
+
+    for (idx, client) in clients.into_iter().enumerate() {
+        for worker in 0..config.instance_workers(idx) {
+            let worker_id = idx * 10_000 + worker;
+            let params = ReplayWorkerParams {
+                worker_id,
+                slot: worker,
+                proxy_port: config.proxy_ports[idx],
+                client: client.clone(),
+                requests: Arc::clone(&requests),
+                end_time,
+                limits: config.limits(),
+                jitter: config.jitter,
+                rng: worker_rng(config.seed, worker_id),
+                fail_on_status: config.fail_on_status,
+                counters: counters.clone(),
+                shutdown: shutdown.clone(),
+                warmup: gate.warmup_ticket(worker),
+                gate: gate.clone(),
+            };
+            let handle = tokio::spawn(async move {
+                replay_worker_loop(params).await;
+            });
+            handles.push(handle);
+        }
+    }
+
+    supervise_workers(
+        handles,
+        end_time,
+        config.ramp_down,
+        &counters,
+        config.limits(),
+        &shutdown,
+    )
+    .await
+}
+
+struct ReplayWorkerParams {
+    worker_id: usize,
+    slot: usize,
+    proxy_port: u16,
+    client: Client,
+    requests: Arc<Vec<ReplayRequest>>,
+    end_time: Option<Instant>,
+    limits: RunLimits,
+    jitter: Option<(Duration, Duration)>,
+    rng: StdRng,
+    fail_on_status: bool,
+    counters: SharedCounters,
+    shutdown: ShutdownSignal,
+    warmup: WarmupTicket,
+    gate: ConcurrencyGate,
+}
+
+/// Plays the request sequence in file order, starting over after the last request.
+async fn replay_worker_loop(mut params: ReplayWorkerParams) {
+    let worker_id = params.worker_id;
+    if params.warmup.is_pending() {
+        let connected = warm_up(&params.client, &params.requests[0].url, 1).await;
+        params.warmup.arrive(connected).await;
+    }
+
+    for request in params.requests.iter().cycle() {
+        if let Some(end) = params.end_time
+            && Instant::now() >= end
+        {
+            log::debug!("Replay worker {worker_id} stopping due to duration limit");
+            break;
+        }
+
+        if params.counters.limit_reached(params.limits) {
+            log::debug!("Replay worker {worker_id} stopping due to request or byte limit");
+            break;
+        }
+
+        if params.shutdown.is_triggered() {
+            log::debug!("Replay worker {worker_id} stopping due to shutdown");
+            break;
+        }
+
+        if params.counters.down_ports.contains(params.proxy_port) {
+            log::debug!(
+                "Replay worker {worker_id} stopping, xray-core on port {} is down",
+                params.proxy_port
+            );
+            break;
+        }
+
+        if !params.gate.wait_for_slot(params.slot).await {
+            break;
+        }
+
+        let label = request.label();
+        if let Err(kind) = execute_request(&params, request, &label).await {
+            params.counters.record_failure(kind);
+            params.counters.targets.record_failure(&label);
+        }
+        apply_jitter(params.jitter, &mut params.rng).await;
+    }
+
+    log::debug!("Replay worker {worker_id} completed");
+}
+
+async fn execute_request(
+    params: &ReplayWorkerParams,
+    request: &ReplayRequest,
+    label: &str,
+) -> Result<(), FailureKind> {
+    let counters = &params.counters;
+    let _connection = counters.active_connections.open();
+    let built = request.build(&params.client).map_err(|err| {
+        log::warn!("Failed to build request {label}: {err}");
+        FailureKind::Other
+    })?;
+
+    let response = params.client.execute(built).await.map_err(|err| {
+        log::debug!("Request {label} failed: {err}");
+        classify_reqwest(&err)
+    })?;
+
+    let status = response.status();
+    counters.status_codes.record(status.as_u16());
+    if params.fail_on_status && !status.is_success() {
+        log::debug!("Request {label} answered {status}");
+        return Err(FailureKind::Status);
+    }
+    counters.record_success();
+
+    let mut stream = response.bytes_stream();
+    let mut total_bytes = 0u64;
+    while let Some(chunk_result) = stream.next().await {
+        if params.shutdown.is_triggered() {
+            break;
+        }
+        match chunk_result {
+            Ok(chunk) => {
+                total_bytes += chunk.len() as u64;
+                counters.record_bytes(chunk.len() as u64);
+            }
+            Err(err) => {
+                log::debug!("Stream error from {label} after {total_bytes} bytes: {err}");
+                counters.targets.record_bytes(label, total_bytes);
+                return Err(classify_reqwest(&err));
+            }
+        }
+    }
+
+    counters.targets.record_success(label, total_bytes);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_replay_lines() {
+        let content = r#"
+# login, then list items
+{"method": "post", "url": "https://api.example.com/login", "headers": {"Content-Type": "application/json"}, "body": "{\"user\": \"cat\"}"}
+
+{"url": "https://api.example.com/v1/items?page=1"}
+"#;
+        let requests = parse_replay_lines(content).unwrap();
+        assert_eq!(requests.len(), 2);
+
+        assert_eq!(requests[0].method, Method::POST);
+        assert_eq!(requests[0].headers["content-type"], "application/json");
+        assert_eq!(requests[0].body.as_deref(), Some(r#"{"user": "cat"}"#));
+        assert_eq!(requests[1].method, Method::GET);
+        assert_eq!(
+            requests[1].label(),
+            "GET https://api.example.com/v1/items?page=1"
+        );
+    }
+
+    #[test]
+    fn test_parse_replay_lines_rejects_bad_entries() {
+        let err = parse_replay_lines("{\"url\": \"https://a.example\"}\n{\"url\": \"ftp://b\"}")
+            .unwrap_err();
+        assert_eq!(err.to_string(), "Invalid replay request on line 2");
+
+        assert!(parse_replay_lines("{\"url\": \"https://a.example\", \"verb\": \"GET\"}").is_err());
+        assert!(parse_replay_lines("# nothing to replay\n").is_err());
+    }
+}
@@ -0,0 +1,117 @@
+use crate::stressor::StressStats;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+
+/// `--state-file` contents: counters summed over every run that used the file.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LifetimeStats {
+    pub runs: u64,
+    pub elapsed_secs: f64,
+    pub success_events: u64,
+    pub failure_events: u64,
+    pub bytes_transferred: u64,
+    pub packets_sent: u64,
+    pub retries: u64,
+    pub partial_downloads: u64,
+    /// Failure counts by category, only categories that occurred
+    pub failures: BTreeMap<String, u64>,
+}
+
+impl LifetimeStats {
+    /// Reads the totals of earlier runs; a missing file means this is the first one.
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Self::default()),
+            Err(err) => {
+                return Err(err)
+                    .with_context(|| format!("Failed to read state file {}", path.display()));
+            }
+        };
+        serde_json::from_str(&contents)
+            .with_context(|| format!("Failed to parse state file {}", path.display()))
+    }
+
+    /// Writes through a temporary file and a rename so a kill mid-write keeps the old totals.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(self).context("Failed to serialize state")?;
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        fs::write(&tmp, json + "\n")
+            .with_context(|| format!("Failed to write state file {}", path.display()))?;
+        fs::rename(&tmp, path)
+            .with_context(|| format!("Failed to replace state file {}", path.display()))
+    }
+
+    /// These totals with `run` added as one more run.
+    pub fn with_run(&self, run: &StressStats) -> Self {
+        let mut failures = self.failures.clone();
+        for (kind, count) in run.failure_breakdown() {
+            *failures.entry(kind.as_str().to_string()).or_default() += count;
+        }
+        Self {
+            runs: self.runs + 1,
+            elapsed_secs: self.elapsed_secs + run.elapsed().as_secs_f64(),
+            success_events: self.success_events + run.success_events,
+            failure_events: self.failure_events + run.failure_events,
+            bytes_transferred: self.bytes_transferred + run.bytes_transferred,
+            packets_sent: self.packets_sent + run.packets_sent,
+            retries: self.retries + run.retries,
+            partial_downloads: self.partial_downloads + run.partial_downloads,
+            failures,
+        }
+    }
+
+    /// Average over the summed run time, so idle gaps between restarts do not count.
+    pub fn mbps(&self) -> f64 {
+        if self.elapsed_secs.is_normal() {
+            self.bytes_transferred as f64 * 8.0 / self.elapsed_secs / (1000.0 * 1000.0)
+        } else {
+            0.0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stressor::FailureKind;
+
+    #[test]
+    fn test_state_round_trips_and_accumulates() {
+        let path = std::env::temp_dir().join(format!("herscat-state-{}.json", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let fresh = LifetimeStats::load(&path).unwrap();
+        assert_eq!(fresh, LifetimeStats::default());
+
+        let mut run = StressStats::new();
+        run.bytes_transferred = 1000;
+        run.success_events = 3;
+        run.failure_events = 2;
+        run.failures_by_kind[FailureKind::Timeout.index()] = 2;
+
+        let first = LifetimeStats::default().with_run(&run);
+        first.save(&path).unwrap();
+        let second = LifetimeStats::load(&path).unwrap().with_run(&run);
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(second.runs, 2);
+        assert_eq!(second.bytes_transferred, 2000);
+        assert_eq!(second.success_events, 6);
+        assert_eq!(second.failures["timeout"], 4);
+    }
+
+    #[test]
+    fn test_state_rejects_garbage() {
+        let path = std::env::temp_dir().join(format!("herscat-bad-{}.json", std::process::id()));
+        fs::write(&path, "not json").unwrap();
+        let err = LifetimeStats::load(&path).unwrap_err();
+        fs::remove_file(&path).unwrap();
+        assert!(format!("{err:#}").contains("Failed to parse state file"));
+    }
+}
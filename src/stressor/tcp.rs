@@ -1,45 +1,157 @@
+use super::failure::{FailureKind, classify_anyhow, classify_socks};
 use super::{
-    SharedCounters, SocketTarget, StressConfig, build_payload, packet_interval, supervise_workers,
+    ConcurrencyGate, RunLimits, SharedCounters, ShutdownSignal, SocketTarget, StressConfig,
+    TargetCursor, WarmupTicket, apply_jitter, build_payload, seeded_rng, socks_connect,
+    supervise_workers, worker_rng, worker_targets,
 };
-use anyhow::{Result, anyhow};
-use rand::{Rng, rng};
+use crate::cli::Mode;
+use crate::config::InboundAuth;
+use anyhow::{Context, Result, anyhow};
+use rand::rngs::StdRng;
+use std::fs;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::path::Path;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
-use tokio::io::AsyncWriteExt;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
 use tokio::net::TcpStream;
 use tokio::task::JoinHandle;
-use tokio::time::sleep;
+use tokio::time::{sleep, timeout};
 use tokio_socks::tcp::Socks5Stream;
 
+/// How long one `--tcp-template` answer may take before the connection counts as timed out.
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// `--tcp-template`: the bytes tcp-flood sends per iteration and how much of each answer it reads.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TcpTemplate {
+    pub request: Vec<u8>,
+    pub response_end: ResponseEnd,
+}
+
+/// Where one answer to a template ends.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ResponseEnd {
+    /// Whatever the first read returns
+    FirstRead,
+    /// The first occurrence of this byte sequence (`--tcp-read-until`)
+    Delimiter(Vec<u8>),
+    /// This many bytes (`--tcp-read-bytes`)
+    Bytes(usize),
+}
+
+impl TcpTemplate {
+    /// Reads the request bytes from `path` verbatim; `delimiter` may use the escapes `\r`, `\n`,
+    /// `\t`, `\0`, `\\` and `\xHH`.
+    pub fn load(path: &Path, delimiter: Option<&str>, bytes: Option<u64>) -> Result<Self> {
+        let request = fs::read(path)
+            .with_context(|| format!("Failed to read TCP template {}", path.display()))?;
+        if request.is_empty() {
+            return Err(anyhow!("TCP template {} is empty", path.display()));
+        }
+        let response_end = match (delimiter, bytes) {
+            (Some(delimiter), _) => ResponseEnd::Delimiter(unescape(delimiter)?),
+            (None, Some(bytes)) => ResponseEnd::Bytes(bytes as usize),
+            (None, None) => ResponseEnd::FirstRead,
+        };
+        Ok(Self {
+            request,
+            response_end,
+        })
+    }
+}
+
+fn unescape(text: &str) -> Result<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            let mut utf8 = [0u8; 4];
+            bytes.extend_from_slice(c.encode_utf8(&mut utf8).as_bytes());
+            continue;
+        }
+        match chars.next() {
+            Some('r') => bytes.push(b'\r'),
+            Some('n') => bytes.push(b'\n'),
+            Some('t') => bytes.push(b'\t'),
+            Some('0') => bytes.push(0),
+            Some('\\') => bytes.push(b'\\'),
+            Some('x') => {
+                let hex: String = chars.by_ref().take(2).collect();
+                let byte = u8::from_str_radix(&hex, 16)
+                    .ok()
+                    .filter(|_| hex.len() == 2)
+                    .ok_or_else(|| anyhow!("Invalid escape \\x{hex} in {text:?}"))?;
+                bytes.push(byte);
+            }
+            other => {
+                let escape = other.map(String::from).unwrap_or_default();
+                return Err(anyhow!("Invalid escape \\{escape} in {text:?}"));
+            }
+        }
+    }
+    if bytes.is_empty() {
+        return Err(anyhow!("The response delimiter must not be empty"));
+    }
+    Ok(bytes)
+}
+
 pub async fn run(
     config: &StressConfig,
     counters: SharedCounters,
-    start_time: Instant,
+    shutdown: ShutdownSignal,
+    gate: ConcurrencyGate,
+    end_time: Option<Instant>,
 ) -> Result<()> {
     let targets = config.socket_targets();
     if targets.is_empty() {
         return Err(anyhow!(
-            "No host:port targets configured for TCP flood mode"
+            "No host:port targets configured for {:?} mode",
+            config.mode
         ));
     }
     let targets = Arc::new(targets);
 
-    let payload = Arc::new(build_payload(config.packet_size));
-    let packet_interval = packet_interval(config.packet_rate);
-    let end_time = config.duration.map(|d| start_time + d);
+    let connect_only = matches!(config.mode, Mode::TcpConnect);
+    let response_end = config.tcp_template.as_ref().map(|t| t.response_end.clone());
+    let payload = Arc::new(if connect_only {
+        Vec::new()
+    } else if let Some(template) = &config.tcp_template {
+        template.request.clone()
+    } else {
+        let rng = &mut seeded_rng(config.seed);
+        build_payload(config.packet_size, config.payload_pattern, rng)
+    });
+    let packet_interval = config.send_interval();
 
     let mut handles: Vec<JoinHandle<()>> = Vec::new();
     for (idx, port) in config.proxy_ports.iter().enumerate() {
-        for worker in 0..config.concurrency {
+        for worker in 0..config.instance_workers(idx) {
+            let worker_id = idx * 10_000 + worker;
             let params = TcpWorkerParams {
-                worker_id: idx * 10_000 + worker,
+                worker_id,
+                slot: worker,
+                proxy_host: config.proxy_host,
                 proxy_port: *port,
-                targets: Arc::clone(&targets),
+                targets: worker_targets(&targets, worker_id, config.shuffle_targets, config.seed),
                 payload: Arc::clone(&payload),
+                response_end: response_end.clone(),
+                connect_only,
+                hold: config.hold,
                 packet_interval,
                 end_time,
+                limits: config.limits(),
+                jitter: config.jitter,
+                rng: worker_rng(config.seed, worker_id),
+                cursor: TargetCursor::new(config.shuffle_targets),
                 packets_per_connection: config.packets_per_connection,
+                conn_lifetime: config.conn_lifetime,
                 counters: counters.clone(),
+                auth: config.inbound_auth.clone(),
+                shutdown: shutdown.clone(),
+                warmup: gate.warmup_ticket(worker),
+                gate: gate.clone(),
             };
             let handle = tokio::spawn(async move {
                 tcp_worker_loop(params).await;
@@ -48,21 +160,44 @@ pub async fn run(
         }
     }
 
-    supervise_workers(handles, end_time).await
+    supervise_workers(
+        handles,
+        end_time,
+        config.ramp_down,
+        &counters,
+        config.limits(),
+        &shutdown,
+    )
+    .await
 }
 
 struct TcpWorkerParams {
     worker_id: usize,
+    slot: usize,
+    proxy_host: IpAddr,
     proxy_port: u16,
     targets: Arc<Vec<SocketTarget>>,
     payload: Arc<Vec<u8>>,
+    /// Set with `--tcp-template`: read an answer after every send
+    response_end: Option<ResponseEnd>,
+    connect_only: bool,
+    hold: Option<Duration>,
     packet_interval: Option<Duration>,
     end_time: Option<Instant>,
+    limits: RunLimits,
+    jitter: Option<(Duration, Duration)>,
+    rng: StdRng,
+    cursor: TargetCursor,
     packets_per_connection: Option<u32>,
+    conn_lifetime: Option<Duration>,
     counters: SharedCounters,
+    auth: Option<InboundAuth>,
+    shutdown: ShutdownSignal,
+    warmup: WarmupTicket,
+    gate: ConcurrencyGate,
 }
 
-async fn tcp_worker_loop(params: TcpWorkerParams) {
+async fn tcp_worker_loop(mut params: TcpWorkerParams) {
     loop {
         if let Some(end) = params.end_time
             && Instant::now() >= end
@@ -74,24 +209,65 @@ async fn tcp_worker_loop(params: TcpWorkerParams) {
             break;
         }
 
-        let idx = rng().random_range(0..params.targets.len());
+        if params.counters.limit_reached(params.limits) {
+            log::debug!(
+                "TCP worker {} finished due to request or byte limit",
+                params.worker_id
+            );
+            break;
+        }
+
+        if params.shutdown.is_triggered() {
+            log::debug!("TCP worker {} finished due to shutdown", params.worker_id);
+            break;
+        }
+
+        if params.counters.down_ports.contains(params.proxy_port) {
+            log::debug!(
+                "TCP worker {} finished, xray-core on port {} is down",
+                params.worker_id,
+                params.proxy_port
+            );
+            break;
+        }
+
+        if !params.gate.wait_for_slot(params.slot).await {
+            break;
+        }
+
+        let idx = params.cursor.pick(params.targets.len(), &mut params.rng);
         let target = &params.targets[idx];
+        let proxy = SocketAddr::new(params.proxy_host, params.proxy_port);
 
-        match Socks5Stream::connect(
-            ("127.0.0.1", params.proxy_port),
-            (target.host.as_str(), target.port),
-        )
-        .await
-        {
+        let connected = socks_connect(proxy, target, params.auth.as_ref()).await;
+        params.warmup.arrive(connected.is_ok()).await;
+        match connected {
+            Ok(stream) if params.connect_only => {
+                let connection = params.counters.active_connections.open();
+                params.counters.record_success();
+                if let Some(hold) = params.hold {
+                    sleep(hold).await;
+                }
+                drop(stream);
+                drop(connection);
+
+                if let Some(interval) = params.packet_interval {
+                    sleep(interval).await;
+                }
+                apply_jitter(params.jitter, &mut params.rng).await;
+            }
             Ok(mut stream) => {
-                if let Err(err) = send_loop(&mut stream, &params).await {
+                let _connection = params.counters.active_connections.open();
+                if let Err(err) = send_loop(&mut stream, &mut params).await {
                     log::debug!(
                         "TCP worker {} stream error towards {}: {}",
                         params.worker_id,
-                        target.display(),
+                        params.targets[idx].display(),
                         err
                     );
-                    params.counters.record_failure();
+                    params
+                        .counters
+                        .record_failure(classify_anyhow(&err, FailureKind::Stream));
                 }
             }
             Err(err) => {
@@ -102,35 +278,234 @@ async fn tcp_worker_loop(params: TcpWorkerParams) {
                     target.display(),
                     err
                 );
-                params.counters.record_failure();
+                params.counters.record_failure(classify_socks(&err));
                 sleep(Duration::from_millis(200)).await;
             }
         }
     }
 }
 
-async fn send_loop(stream: &mut Socks5Stream<TcpStream>, params: &TcpWorkerParams) -> Result<()> {
+async fn send_loop(
+    stream: &mut Socks5Stream<TcpStream>,
+    params: &mut TcpWorkerParams,
+) -> Result<()> {
     let mut packets_this_connection = 0u32;
+    let opened = Instant::now();
 
     loop {
         stream.write_all(&params.payload).await?;
-        params.counters.record_packet(params.payload.len());
+        match &params.response_end {
+            Some(end) => {
+                let received = timeout(RESPONSE_TIMEOUT, read_response(stream, end))
+                    .await
+                    .map_err(|_| {
+                        io::Error::new(io::ErrorKind::TimedOut, "no response in time")
+                    })??;
+                params
+                    .counters
+                    .record_exchange(params.payload.len(), received);
+            }
+            None => params.counters.record_packet(params.payload.len()),
+        }
         packets_this_connection = packets_this_connection.saturating_add(1);
 
         if let Some(interval) = params.packet_interval {
             sleep(interval).await;
         }
+        apply_jitter(params.jitter, &mut params.rng).await;
 
         if let Some(limit) = params.packets_per_connection && packets_this_connection >= limit {
             break;
         }
 
+        if let Some(lifetime) = params.conn_lifetime
+            && opened.elapsed() >= lifetime
+        {
+            break;
+        }
+
         if let Some(end) = params.end_time
             && Instant::now() >= end
         {
             break;
         }
+
+        if params.counters.limit_reached(params.limits) || params.shutdown.is_triggered() {
+            break;
+        }
+
+        // Retired by --auto-concurrency: close the connection and park.
+        if !params.gate.admits(params.slot) {
+            break;
+        }
     }
 
     Ok(())
 }
+
+/// Reads one answer to a template and returns its size. Bytes that arrive in the same read as
+/// the delimiter are counted with this answer.
+async fn read_response<S: AsyncRead + Unpin>(stream: &mut S, end: &ResponseEnd) -> Result<usize> {
+    let mut buf = [0u8; 16 * 1024];
+    let mut tail = Vec::new();
+    let mut total = 0usize;
+    loop {
+        let want = match end {
+            ResponseEnd::Bytes(limit) => (limit - total).min(buf.len()),
+            _ => buf.len(),
+        };
+        let read = stream.read(&mut buf[..want]).await?;
+        if read == 0 {
+            return Err(anyhow!("Connection closed after {total} response bytes"));
+        }
+        total += read;
+
+        match end {
+            ResponseEnd::FirstRead => return Ok(total),
+            ResponseEnd::Bytes(limit) if total >= *limit => return Ok(total),
+            ResponseEnd::Bytes(_) => {}
+            ResponseEnd::Delimiter(delimiter) => {
+                tail.extend_from_slice(&buf[..read]);
+                if tail
+                    .windows(delimiter.len())
+                    .any(|w| w == delimiter.as_slice())
+                {
+                    return Ok(total);
+                }
+                // Keep just enough to catch a delimiter split across reads.
+                tail.drain(..tail.len().saturating_sub(delimiter.len() - 1));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::io::AsyncReadExt;
+    use tokio::net::TcpListener;
+
+    /// No-auth SOCKS5 server that grants every CONNECT and discards the payload. Returns its
+    /// port and a count of accepted connections.
+    async fn socks_sink() -> (u16, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let accepted = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&accepted);
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                counter.fetch_add(1, Ordering::Relaxed);
+                tokio::spawn(async move {
+                    let mut greeting = [0u8; 2];
+                    socket.read_exact(&mut greeting).await?;
+                    let mut methods = vec![0u8; greeting[1] as usize];
+                    socket.read_exact(&mut methods).await?;
+                    socket.write_all(&[5, 0]).await?;
+
+                    let mut request = [0u8; 4];
+                    socket.read_exact(&mut request).await?;
+                    let addr_len = match request[3] {
+                        1 => 4,
+                        4 => 16,
+                        _ => socket.read_u8().await? as usize,
+                    };
+                    let mut rest = vec![0u8; addr_len + 2];
+                    socket.read_exact(&mut rest).await?;
+                    socket.write_all(&[5, 0, 0, 1, 0, 0, 0, 0, 0, 0]).await?;
+
+                    let mut sink = [0u8; 1024];
+                    while socket.read(&mut sink).await? > 0 {}
+                    Ok::<_, std::io::Error>(())
+                });
+            }
+        });
+        (port, accepted)
+    }
+
+    async fn run_worker(proxy_port: u16, conn_lifetime: Option<Duration>) {
+        let shutdown = ShutdownSignal::default();
+        let gate = ConcurrencyGate::new(1, shutdown.clone());
+        let params = TcpWorkerParams {
+            worker_id: 0,
+            slot: 0,
+            proxy_host: IpAddr::from([127, 0, 0, 1]),
+            proxy_port,
+            targets: Arc::new(vec![SocketTarget {
+                host: "example.com".to_string(),
+                port: 80,
+            }]),
+            payload: Arc::new(vec![0; 16]),
+            response_end: None,
+            connect_only: false,
+            hold: None,
+            packet_interval: Some(Duration::from_millis(10)),
+            end_time: Some(Instant::now() + Duration::from_millis(300)),
+            limits: RunLimits::default(),
+            jitter: None,
+            rng: StdRng::seed_from_u64(0),
+            cursor: TargetCursor::new(false),
+            packets_per_connection: None,
+            conn_lifetime,
+            counters: SharedCounters::new(),
+            auth: None,
+            shutdown,
+            warmup: gate.warmup_ticket(0),
+            gate,
+        };
+        tcp_worker_loop(params).await;
+    }
+
+    #[tokio::test]
+    async fn test_conn_lifetime_forces_reconnects() {
+        let (port, accepted) = socks_sink().await;
+        run_worker(port, None).await;
+        assert_eq!(accepted.load(Ordering::Relaxed), 1);
+
+        let (port, accepted) = socks_sink().await;
+        run_worker(port, Some(Duration::from_millis(50))).await;
+        let connections = accepted.load(Ordering::Relaxed);
+        assert!(connections >= 3, "only {connections} connections");
+    }
+
+    #[test]
+    fn test_response_delimiter_escapes() {
+        assert_eq!(unescape(r"\r\n\r\n").unwrap(), b"\r\n\r\n");
+        assert_eq!(unescape(r"END\x00\\").unwrap(), b"END\0\\");
+        assert!(unescape(r"\xZ1").is_err());
+        assert!(unescape(r"\q").is_err());
+        assert!(unescape("").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_read_response_stops_at_delimiter_or_size() {
+        let (mut client, mut server) = tokio::io::duplex(64);
+        let delimiter = ResponseEnd::Delimiter(b"\r\n\r\n".to_vec());
+        server.write_all(b"HTTP/1.1 200 OK\r\n\r").await.unwrap();
+        let reading = tokio::spawn(async move {
+            let received = read_response(&mut client, &delimiter).await.unwrap();
+            (client, received)
+        });
+        sleep(Duration::from_millis(50)).await;
+        server.write_all(b"\n").await.unwrap();
+        let (mut client, received) = reading.await.unwrap();
+        assert_eq!(received, 19);
+
+        server.write_all(b"0123456789").await.unwrap();
+        let received = read_response(&mut client, &ResponseEnd::Bytes(4))
+            .await
+            .unwrap();
+        assert_eq!(received, 4);
+        let received = read_response(&mut client, &ResponseEnd::FirstRead)
+            .await
+            .unwrap();
+        assert_eq!(received, 6);
+
+        drop(server);
+        let err = read_response(&mut client, &ResponseEnd::FirstRead)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("Connection closed"));
+    }
+}
@@ -0,0 +1,130 @@
+//! `--tls-timing`: a bare TLS handshake to each https download target, timed on its own.
+//!
+//! reqwest folds the handshake into its connect phase, so the probe dials the target through the
+//! same SOCKS5 inbound and runs the handshake itself, leaving the download untouched.
+
+use super::{SocketTarget, socks_connect};
+use crate::config::InboundAuth;
+use anyhow::{Context, Result, anyhow};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::time::timeout;
+use tokio_rustls::TlsConnector;
+use tokio_rustls::rustls::client::danger::{
+    HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier,
+};
+use tokio_rustls::rustls::crypto::{self, CryptoProvider};
+use tokio_rustls::rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use tokio_rustls::rustls::{ClientConfig, DigitallySignedStruct, RootCertStore, SignatureScheme};
+use url::Url;
+
+/// Same bound as the download client's connect timeout.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Clone)]
+pub(super) struct TlsProbe {
+    proxy: SocketAddr,
+    auth: Option<InboundAuth>,
+    connector: TlsConnector,
+}
+
+impl TlsProbe {
+    /// Certificates are only checked with `verify_certs`, matching the download client.
+    pub(super) fn new(
+        proxy: SocketAddr,
+        auth: Option<InboundAuth>,
+        verify_certs: bool,
+    ) -> Result<Self> {
+        let provider = Arc::new(crypto::ring::default_provider());
+        let builder = ClientConfig::builder_with_provider(Arc::clone(&provider))
+            .with_safe_default_protocol_versions()
+            .context("Failed to set up TLS for --tls-timing")?;
+        let config = if verify_certs {
+            let roots = RootCertStore {
+                roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+            };
+            builder.with_root_certificates(roots).with_no_client_auth()
+        } else {
+            builder
+                .dangerous()
+                .with_custom_certificate_verifier(Arc::new(AcceptAnyCert(provider)))
+                .with_no_client_auth()
+        };
+        Ok(Self {
+            proxy,
+            auth,
+            connector: TlsConnector::from(Arc::new(config)),
+        })
+    }
+
+    /// Returns the `host:port` the sample belongs to and the handshake time, which starts once
+    /// the SOCKS5 tunnel is up so proxy connect time is left out.
+    pub(super) async fn handshake(&self, url: &Url) -> Result<(String, Duration)> {
+        let host = url
+            .host_str()
+            .ok_or_else(|| anyhow!("{url} has no host"))?
+            .trim_start_matches('[')
+            .trim_end_matches(']');
+        let target = SocketTarget {
+            host: host.to_string(),
+            port: url.port_or_known_default().unwrap_or(443),
+        };
+        let name = ServerName::try_from(target.host.clone())
+            .with_context(|| format!("Invalid TLS server name {}", target.host))?;
+
+        let stream = timeout(
+            HANDSHAKE_TIMEOUT,
+            socks_connect(self.proxy, &target, self.auth.as_ref()),
+        )
+        .await
+        .context("SOCKS5 connect timed out")??;
+        let started = Instant::now();
+        timeout(HANDSHAKE_TIMEOUT, self.connector.connect(name, stream))
+            .await
+            .context("TLS handshake timed out")??;
+        Ok((target.display(), started.elapsed()))
+    }
+}
+
+/// Accepts any certificate but still checks the handshake signatures, like
+/// `danger_accept_invalid_certs` on the download client.
+#[derive(Debug)]
+struct AcceptAnyCert(Arc<CryptoProvider>);
+
+impl ServerCertVerifier for AcceptAnyCert {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, tokio_rustls::rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, tokio_rustls::rustls::Error> {
+        let algorithms = &self.0.signature_verification_algorithms;
+        crypto::verify_tls12_signature(message, cert, dss, algorithms)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, tokio_rustls::rustls::Error> {
+        let algorithms = &self.0.signature_verification_algorithms;
+        crypto::verify_tls13_signature(message, cert, dss, algorithms)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.signature_verification_algorithms.supported_schemes()
+    }
+}
@@ -1,8 +1,13 @@
+use super::failure::{FailureKind, classify_anyhow};
 use super::{
-    SharedCounters, SocketTarget, StressConfig, build_payload, packet_interval, supervise_workers,
+    ConcurrencyGate, ConnectionGauge, ConnectionGuard, RunLimits, SharedCounters, ShutdownSignal,
+    SocketTarget, StressConfig, TargetCursor, WarmupTicket, apply_jitter, build_payload,
+    seeded_rng, supervise_workers, worker_rng, worker_targets,
 };
+use crate::config::InboundAuth;
 use anyhow::{Result, anyhow};
-use rand::{Rng, rng};
+use rand::rngs::StdRng;
+use socket2::{Domain, Protocol, Socket, Type};
 use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
@@ -11,10 +16,21 @@ use tokio::net::{TcpStream, UdpSocket};
 use tokio::task::JoinHandle;
 use tokio::time::sleep;
 
+/// Largest UDP payload that fits in one IPv4 datagram (65535 - 20 IP - 8 UDP header bytes).
+const MAX_UDP_DATAGRAM: u32 = 65_507;
+
+/// Worst-case SOCKS5 UDP request header: RSV, FRAG, ATYP, a 255-byte domain with its length, port.
+const MAX_SOCKS5_UDP_HEADER: u32 = 2 + 1 + 1 + 1 + 255 + 2;
+
+/// Largest `--packet-size` that still fits in a single datagram to the SOCKS5 relay.
+pub const MAX_UDP_PACKET_SIZE: u32 = MAX_UDP_DATAGRAM - MAX_SOCKS5_UDP_HEADER;
+
 pub async fn run(
     config: &StressConfig,
     counters: SharedCounters,
-    start_time: Instant,
+    shutdown: ShutdownSignal,
+    gate: ConcurrencyGate,
+    end_time: Option<Instant>,
 ) -> Result<()> {
     let targets = config.socket_targets();
     if targets.is_empty() {
@@ -24,22 +40,39 @@ pub async fn run(
     }
     let targets = Arc::new(targets);
 
-    let payload = Arc::new(build_payload(config.packet_size));
-    let packet_interval = packet_interval(config.packet_rate);
-    let end_time = config.duration.map(|d| start_time + d);
+    let rng = &mut seeded_rng(config.seed);
+    let payload = build_payload(config.packet_size, config.payload_pattern, rng);
+    let payload = Arc::new(payload);
+    let packet_interval = config.send_interval();
+    let unspecified = IpAddr::V4(Ipv4Addr::UNSPECIFIED);
+    let local_ip = config.local_address.unwrap_or(unspecified);
+    let local_addr = SocketAddr::new(local_ip, config.udp_local_port.unwrap_or(0));
 
     let mut handles: Vec<JoinHandle<()>> = Vec::new();
     for (idx, port) in config.proxy_ports.iter().enumerate() {
-        for worker in 0..config.concurrency {
+        for worker in 0..config.instance_workers(idx) {
+            let worker_id = idx * 10_000 + worker;
             let params = UdpWorkerParams {
-                worker_id: idx * 10_000 + worker,
+                worker_id,
+                slot: worker,
+                proxy_host: config.proxy_host,
                 proxy_port: *port,
-                targets: Arc::clone(&targets),
+                targets: worker_targets(&targets, worker_id, config.shuffle_targets, config.seed),
                 payload: Arc::clone(&payload),
                 packet_interval,
                 end_time,
+                limits: config.limits(),
+                jitter: config.jitter,
+                rng: worker_rng(config.seed, worker_id),
+                cursor: TargetCursor::new(config.shuffle_targets),
                 packets_per_connection: config.packets_per_connection,
+                reassociate: config.udp_reassociate,
+                local_addr,
+                auth: config.inbound_auth.clone(),
                 counters: counters.clone(),
+                shutdown: shutdown.clone(),
+                warmup: gate.warmup_ticket(worker),
+                gate: gate.clone(),
             };
             let handle = tokio::spawn(async move {
                 udp_worker_loop(params).await;
@@ -48,21 +81,41 @@ pub async fn run(
         }
     }
 
-    supervise_workers(handles, end_time).await
+    supervise_workers(
+        handles,
+        end_time,
+        config.ramp_down,
+        &counters,
+        config.limits(),
+        &shutdown,
+    )
+    .await
 }
 
 struct UdpWorkerParams {
     worker_id: usize,
+    slot: usize,
+    proxy_host: IpAddr,
     proxy_port: u16,
     targets: Arc<Vec<SocketTarget>>,
     payload: Arc<Vec<u8>>,
     packet_interval: Option<Duration>,
     end_time: Option<Instant>,
+    limits: RunLimits,
+    jitter: Option<(Duration, Duration)>,
+    rng: StdRng,
+    cursor: TargetCursor,
     packets_per_connection: Option<u32>,
+    reassociate: bool,
+    local_addr: SocketAddr,
+    auth: Option<InboundAuth>,
     counters: SharedCounters,
+    shutdown: ShutdownSignal,
+    warmup: WarmupTicket,
+    gate: ConcurrencyGate,
 }
 
-async fn udp_worker_loop(params: UdpWorkerParams) {
+async fn udp_worker_loop(mut params: UdpWorkerParams) {
     let mut association: Option<UdpAssociation> = None;
     let mut packets_this_connection = 0u32;
 
@@ -77,8 +130,42 @@ async fn udp_worker_loop(params: UdpWorkerParams) {
             break;
         }
 
+        if params.counters.limit_reached(params.limits) {
+            log::debug!(
+                "UDP worker {} finished due to request or byte limit",
+                params.worker_id
+            );
+            break;
+        }
+
+        if params.shutdown.is_triggered() {
+            log::debug!("UDP worker {} finished due to shutdown", params.worker_id);
+            break;
+        }
+
+        if params.counters.down_ports.contains(params.proxy_port) {
+            log::debug!(
+                "UDP worker {} finished, xray-core on port {} is down",
+                params.worker_id,
+                params.proxy_port
+            );
+            break;
+        }
+
+        if !params.gate.admits(params.slot) {
+            association = None;
+            if !params.gate.wait_for_slot(params.slot).await {
+                break;
+            }
+        }
+
         if association.is_none() {
-            match UdpAssociation::connect(params.proxy_port).await {
+            let proxy = SocketAddr::new(params.proxy_host, params.proxy_port);
+            let gauge = &params.counters.active_connections;
+            let auth = params.auth.as_ref();
+            let connected = UdpAssociation::connect(proxy, params.local_addr, auth, gauge).await;
+            params.warmup.arrive(connected.is_ok()).await;
+            match connected {
                 Ok(assoc) => association = Some(assoc),
                 Err(err) => {
                     log::debug!(
@@ -87,7 +174,9 @@ async fn udp_worker_loop(params: UdpWorkerParams) {
                         params.proxy_port,
                         err
                     );
-                    params.counters.record_failure();
+                    params
+                        .counters
+                        .record_failure(classify_anyhow(&err, FailureKind::Connect));
                     sleep(Duration::from_millis(250)).await;
                     continue;
                 }
@@ -97,10 +186,12 @@ async fn udp_worker_loop(params: UdpWorkerParams) {
 
         let mut reset_association = false;
         if let Some(assoc) = association.as_mut() {
-            match send_udp_packet(assoc, &params).await {
+            match send_udp_packet(assoc, &mut params).await {
                 Ok(()) => {
                     packets_this_connection = packets_this_connection.saturating_add(1);
-                    if let Some(limit) = params.packets_per_connection
+                    if params.reassociate {
+                        reset_association = true;
+                    } else if let Some(limit) = params.packets_per_connection
                         && packets_this_connection >= limit
                     {
                         reset_association = true;
@@ -113,7 +204,9 @@ async fn udp_worker_loop(params: UdpWorkerParams) {
                         params.proxy_port,
                         err
                     );
-                    params.counters.record_failure();
+                    params
+                        .counters
+                        .record_failure(classify_anyhow(&err, FailureKind::Stream));
                     reset_association = true;
                     sleep(Duration::from_millis(200)).await;
                 }
@@ -132,35 +225,90 @@ struct UdpAssociation {
     tcp_guard: TcpStream,
     udp_socket: UdpSocket,
     relay_addr: SocketAddr,
+    _connection: ConnectionGuard,
 }
 
 impl UdpAssociation {
-    async fn connect(proxy_port: u16) -> Result<Self> {
-        let mut stream = TcpStream::connect(("127.0.0.1", proxy_port)).await?;
-        perform_greeting(&mut stream).await?;
+    async fn connect(
+        proxy: SocketAddr,
+        local_addr: SocketAddr,
+        auth: Option<&InboundAuth>,
+        gauge: &ConnectionGauge,
+    ) -> Result<Self> {
+        let mut stream = TcpStream::connect(proxy).await?;
+        perform_greeting(&mut stream, auth).await?;
         let relay_addr = request_udp_associate(&mut stream).await?;
-        let udp_socket = UdpSocket::bind(SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0))).await?;
+        let udp_socket = bind_relay_socket(local_addr)?;
 
         Ok(Self {
             tcp_guard: stream,
             udp_socket,
             relay_addr,
+            _connection: gauge.open(),
         })
     }
 }
 
-async fn perform_greeting(stream: &mut TcpStream) -> Result<()> {
-    let request = [0x05, 0x01, 0x00];
-    stream.write_all(&request).await?;
+/// Binds the local UDP socket with address/port reuse so many associations can share `local_addr`
+/// (port 0 still picks an ephemeral port).
+fn bind_relay_socket(local_addr: SocketAddr) -> Result<UdpSocket> {
+    let domain = Domain::for_address(local_addr);
+    let socket = Socket::new(domain, Type::DGRAM, Some(Protocol::UDP))?;
+    socket.set_reuse_address(true)?;
+    #[cfg(all(unix, not(any(target_os = "solaris", target_os = "illumos"))))]
+    socket.set_reuse_port(true)?;
+    socket.set_nonblocking(true)?;
+    socket.bind(&local_addr.into())?;
+    Ok(UdpSocket::from_std(socket.into())?)
+}
+
+/// Method negotiation: no-auth, or username/password (RFC 1929) when `auth` is set.
+pub(crate) async fn perform_greeting(
+    stream: &mut TcpStream,
+    auth: Option<&InboundAuth>,
+) -> Result<()> {
+    let method = if auth.is_some() { 0x02 } else { 0x00 };
+    stream.write_all(&[0x05, 0x01, method]).await?;
 
     let mut response = [0u8; 2];
     stream.read_exact(&mut response).await?;
-    if response != [0x05, 0x00] {
+    if response != [0x05, method] {
         return Err(anyhow!(
-            "SOCKS5 server rejected authentication method (got {:?})",
+            "SOCKS5 server rejected authentication method {method:#04x} (got {:?})",
             response
         ));
     }
+
+    match auth {
+        Some(auth) => authenticate(stream, auth).await,
+        None => Ok(()),
+    }
+}
+
+async fn authenticate(stream: &mut TcpStream, auth: &InboundAuth) -> Result<()> {
+    let (user, pass) = (auth.user.as_bytes(), auth.pass.as_bytes());
+    if user.len() > u8::MAX as usize || pass.len() > u8::MAX as usize {
+        return Err(anyhow!(
+            "SOCKS5 username and password must be at most 255 bytes each"
+        ));
+    }
+
+    let mut request = Vec::with_capacity(3 + user.len() + pass.len());
+    request.push(0x01);
+    request.push(user.len() as u8);
+    request.extend_from_slice(user);
+    request.push(pass.len() as u8);
+    request.extend_from_slice(pass);
+    stream.write_all(&request).await?;
+
+    let mut response = [0u8; 2];
+    stream.read_exact(&mut response).await?;
+    if response[1] != 0x00 {
+        return Err(anyhow!(
+            "SOCKS5 server rejected username/password (status {})",
+            response[1]
+        ));
+    }
     Ok(())
 }
 
@@ -217,8 +365,8 @@ async fn read_socks_address(stream: &mut TcpStream, atyp: u8) -> Result<SocketAd
     Ok(SocketAddr::new(addr, port))
 }
 
-async fn send_udp_packet(assoc: &mut UdpAssociation, params: &UdpWorkerParams) -> Result<()> {
-    let idx = rng().random_range(0..params.targets.len());
+async fn send_udp_packet(assoc: &mut UdpAssociation, params: &mut UdpWorkerParams) -> Result<()> {
+    let idx = params.cursor.pick(params.targets.len(), &mut params.rng);
     let target = &params.targets[idx];
     let packet = build_udp_packet(target, &params.payload)?;
 
@@ -232,6 +380,7 @@ async fn send_udp_packet(assoc: &mut UdpAssociation, params: &UdpWorkerParams) -
     if let Some(interval) = params.packet_interval {
         sleep(interval).await;
     }
+    apply_jitter(params.jitter, &mut params.rng).await;
 
     Ok(())
 }
@@ -268,3 +417,75 @@ fn build_udp_packet(target: &SocketTarget, payload: &[u8]) -> Result<Vec<u8>> {
     packet.extend_from_slice(payload);
     Ok(packet)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    /// Serves one greeting as an inbound that only accepts user `cat` with password `meow`.
+    async fn password_socks_server() -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut header = [0u8; 2];
+            socket.read_exact(&mut header).await.unwrap();
+            let mut methods = vec![0u8; header[1] as usize];
+            socket.read_exact(&mut methods).await.unwrap();
+            if !methods.contains(&0x02) {
+                socket.write_all(&[0x05, 0xff]).await.unwrap();
+                return;
+            }
+            socket.write_all(&[0x05, 0x02]).await.unwrap();
+
+            assert_eq!(socket.read_u8().await.unwrap(), 0x01);
+            let mut user = vec![0u8; socket.read_u8().await.unwrap() as usize];
+            socket.read_exact(&mut user).await.unwrap();
+            let mut pass = vec![0u8; socket.read_u8().await.unwrap() as usize];
+            socket.read_exact(&mut pass).await.unwrap();
+            let status = u8::from(user != b"cat" || pass != b"meow");
+            socket.write_all(&[0x01, status]).await.unwrap();
+        });
+        addr
+    }
+
+    async fn greet(auth: Option<&InboundAuth>) -> Result<()> {
+        let mut stream = TcpStream::connect(password_socks_server().await)
+            .await
+            .unwrap();
+        perform_greeting(&mut stream, auth).await
+    }
+
+    #[tokio::test]
+    async fn test_greeting_authenticates_with_password() {
+        let auth = |pass: &str| InboundAuth {
+            user: "cat".to_string(),
+            pass: pass.to_string(),
+        };
+
+        greet(Some(&auth("meow"))).await.unwrap();
+
+        let err = greet(Some(&auth("purr"))).await.unwrap_err().to_string();
+        assert!(err.contains("rejected username/password"), "{err}");
+
+        let err = greet(None).await.unwrap_err().to_string();
+        assert!(err.contains("rejected authentication method"), "{err}");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_relay_sockets_share_local_port() {
+        let any = SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0));
+        let first = bind_relay_socket(any).unwrap();
+        let port = first.local_addr().unwrap().port();
+        let second = bind_relay_socket(SocketAddr::from((Ipv4Addr::UNSPECIFIED, port))).unwrap();
+        assert_eq!(second.local_addr().unwrap().port(), port);
+    }
+
+    #[tokio::test]
+    async fn test_relay_socket_binds_local_address() {
+        let socket = bind_relay_socket(SocketAddr::from((Ipv4Addr::LOCALHOST, 0))).unwrap();
+        assert_eq!(socket.local_addr().unwrap().ip(), Ipv4Addr::LOCALHOST);
+    }
+}
@@ -0,0 +1,385 @@
+//! `--tui`: a full-screen live dashboard over the same counters the stats reporter reads.
+
+use crate::process::{InstanceStatus, ProcessManager};
+use crate::stressor::{GIBIBYTE, MEBIBYTE, StressRunner, StressStats, TargetSummary};
+use anyhow::{Context, Result};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Style, Stylize};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Paragraph, Row, Sparkline, Table};
+use ratatui::{DefaultTerminal, Frame};
+use std::collections::VecDeque;
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::runtime::Handle;
+use tokio::task::JoinHandle;
+
+/// How often the dashboard redraws and polls the keyboard.
+const REFRESH_INTERVAL: Duration = Duration::from_millis(250);
+/// Throughput samples kept for the graph, one per refresh.
+const HISTORY_LEN: usize = 512;
+/// Log lines kept for the log panel.
+const LOG_LINES: usize = 200;
+
+/// Log sink for `env_logger`: lines go to stderr as usual, except while a [`Dashboard`] owns
+/// the terminal, when they are kept for its log panel instead.
+#[derive(Clone, Default)]
+pub struct LogBuffer {
+    lines: Arc<Mutex<LogLines>>,
+    capturing: Arc<AtomicBool>,
+}
+
+#[derive(Default)]
+struct LogLines {
+    lines: VecDeque<String>,
+    pending: String,
+}
+
+impl LogBuffer {
+    fn set_capturing(&self, capturing: bool) {
+        self.capturing.store(capturing, Ordering::Relaxed);
+    }
+
+    /// The last `count` captured lines, oldest first.
+    fn tail(&self, count: usize) -> Vec<String> {
+        let Ok(log) = self.lines.lock() else {
+            return Vec::new();
+        };
+        let skip = log.lines.len().saturating_sub(count);
+        log.lines.iter().skip(skip).cloned().collect()
+    }
+}
+
+impl Write for LogBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if !self.capturing.load(Ordering::Relaxed) {
+            return io::stderr().write(buf);
+        }
+        if let Ok(mut log) = self.lines.lock() {
+            log.pending.push_str(&String::from_utf8_lossy(buf));
+            while let Some(end) = log.pending.find('\n') {
+                let line: String = log.pending.drain(..=end).collect();
+                log.lines.push_back(line.trim_end().to_string());
+                if log.lines.len() > LOG_LINES {
+                    log.lines.pop_front();
+                }
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        io::stderr().flush()
+    }
+}
+
+/// Aggregate rates derived from consecutive snapshots.
+struct Rates {
+    last_sample: Instant,
+    last_bytes: u64,
+    last_events: u64,
+    mbps: f64,
+    events_per_sec: f64,
+    /// Throughput per refresh in kbit/s, newest last
+    history: VecDeque<u64>,
+}
+
+impl Rates {
+    fn new(now: Instant) -> Self {
+        Self {
+            last_sample: now,
+            last_bytes: 0,
+            last_events: 0,
+            mbps: 0.0,
+            events_per_sec: 0.0,
+            history: VecDeque::with_capacity(HISTORY_LEN),
+        }
+    }
+
+    /// Counters drop back when `--warmup` resets the baseline, which reads as one idle sample.
+    fn update(&mut self, stats: &StressStats, now: Instant) {
+        let seconds = now.duration_since(self.last_sample).as_secs_f64();
+        if seconds <= 0.0 {
+            return;
+        }
+        let events = stats.success_events + stats.failure_events;
+        let bytes_delta = stats.bytes_transferred.saturating_sub(self.last_bytes);
+        let events_delta = events.saturating_sub(self.last_events);
+
+        self.mbps = (bytes_delta as f64 * 8.0) / (seconds * 1_000_000.0);
+        self.events_per_sec = events_delta as f64 / seconds;
+        self.last_sample = now;
+        self.last_bytes = stats.bytes_transferred;
+        self.last_events = events;
+
+        if self.history.len() == HISTORY_LEN {
+            self.history.pop_front();
+        }
+        self.history.push_back((self.mbps * 1000.0) as u64);
+    }
+}
+
+/// Everything one frame shows, gathered before drawing.
+struct Snapshot {
+    stats: StressStats,
+    instances: Vec<InstanceStatus>,
+    /// Whether a dead instance is waiting for the monitor or left down (`--no-restart`)
+    restarts: bool,
+    targets: Vec<TargetSummary>,
+    logs: Vec<String>,
+}
+
+/// Handle of a running `--tui` dashboard.
+pub struct Dashboard {
+    stop: Arc<AtomicBool>,
+    task: JoinHandle<Result<()>>,
+}
+
+impl Dashboard {
+    /// Takes over the terminal and redraws until [`Dashboard::close`] is called.
+    ///
+    /// `q`, `Esc` or Ctrl+C inside the dashboard trigger the runner's normal shutdown, so the
+    /// run ends as if it had been interrupted.
+    pub fn spawn(runner: StressRunner, processes: ProcessManager, logs: LogBuffer) -> Result<Self> {
+        let terminal = ratatui::try_init().context("Failed to initialize the terminal")?;
+        logs.set_capturing(true);
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let handle = Handle::current();
+        let task = tokio::task::spawn_blocking({
+            let stop = Arc::clone(&stop);
+            move || {
+                let result = draw_loop(terminal, &runner, &processes, &logs, &stop, &handle);
+                ratatui::restore();
+                logs.set_capturing(false);
+                result
+            }
+        });
+
+        Ok(Self { stop, task })
+    }
+
+    /// Stops redrawing and gives the terminal back.
+    pub async fn close(self) -> Result<()> {
+        self.stop.store(true, Ordering::Relaxed);
+        self.task.await.context("Dashboard task failed")?
+    }
+}
+
+fn draw_loop(
+    mut terminal: DefaultTerminal,
+    runner: &StressRunner,
+    processes: &ProcessManager,
+    logs: &LogBuffer,
+    stop: &AtomicBool,
+    handle: &Handle,
+) -> Result<()> {
+    let mut rates = Rates::new(Instant::now());
+
+    while !stop.load(Ordering::Relaxed) {
+        let stats = runner.get_current_stats();
+        rates.update(&stats, Instant::now());
+        let snapshot = Snapshot {
+            stats,
+            instances: handle.block_on(processes.instance_statuses()),
+            restarts: processes.restarts_enabled(),
+            targets: runner.target_summaries(),
+            logs: logs.tail(LOG_LINES),
+        };
+        terminal
+            .draw(|frame| draw(frame, runner, &rates, &snapshot))
+            .context("Failed to draw the dashboard")?;
+
+        if event::poll(REFRESH_INTERVAL).context("Failed to poll terminal events")?
+            && let Event::Key(key) = event::read().context("Failed to read terminal events")?
+            && is_quit_key(key)
+        {
+            log::warn!("Stopping the test from the dashboard");
+            runner.shutdown();
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+fn is_quit_key(key: KeyEvent) -> bool {
+    if key.kind != KeyEventKind::Press {
+        return false;
+    }
+    match key.code {
+        KeyCode::Char('q') | KeyCode::Esc => true,
+        KeyCode::Char('c') => key.modifiers.contains(KeyModifiers::CONTROL),
+        _ => false,
+    }
+}
+
+fn draw(frame: &mut Frame, runner: &StressRunner, rates: &Rates, snapshot: &Snapshot) {
+    let [summary_area, graph_area, tables_area, log_area, help_area] = Layout::vertical([
+        Constraint::Length(4),
+        Constraint::Length(8),
+        Constraint::Min(6),
+        Constraint::Length(8),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+    let [instances_area, targets_area] =
+        Layout::horizontal([Constraint::Percentage(35), Constraint::Percentage(65)])
+            .areas(tables_area);
+
+    let stats = &snapshot.stats;
+    let elapsed = stats.elapsed().as_secs();
+    let summary = vec![
+        Line::from(format!(
+            "Mode: {} | Elapsed: {:02}:{:02}:{:02} | Workers: {} | Active: {} (peak {})",
+            runner.mode().as_str(),
+            elapsed / 3600,
+            elapsed / 60 % 60,
+            elapsed % 60,
+            runner.active_worker_count(),
+            runner.active_connections(),
+            runner.peak_active_connections()
+        )),
+        Line::from(vec![
+            "Success: ".into(),
+            stats.success_events.to_string().green(),
+            " | Failed: ".into(),
+            stats.failure_events.to_string().red(),
+            format!(" ({:.2}%)", stats.failure_rate()).into(),
+            format!(
+                " | Total: {:.2} GiB | Events/s: {:.1}",
+                stats.bytes_transferred as f64 / GIBIBYTE,
+                rates.events_per_sec
+            )
+            .into(),
+        ]),
+    ];
+    frame.render_widget(
+        Paragraph::new(summary).block(Block::bordered().title(" herscat ")),
+        summary_area,
+    );
+
+    let width = graph_area.width.saturating_sub(2) as usize;
+    let skip = rates.history.len().saturating_sub(width);
+    let graph = Sparkline::default()
+        .block(Block::bordered().title(format!(" Throughput: {:.2} Mbps ", rates.mbps)))
+        .data(rates.history.iter().skip(skip))
+        .style(Style::default().fg(Color::Cyan));
+    frame.render_widget(graph, graph_area);
+
+    let instance_rows = snapshot.instances.iter().map(|inst| {
+        let state = if inst.alive {
+            "alive".green()
+        } else if snapshot.restarts {
+            "restarting".yellow()
+        } else {
+            "down".red()
+        };
+        Row::new(vec![
+            inst.port.to_string().into(),
+            inst.pid.to_string().into(),
+            Line::from(state),
+            inst.restarts.to_string().into(),
+            inst.proxy.clone().unwrap_or_default().into(),
+        ])
+    });
+    let instances = Table::new(
+        instance_rows,
+        [
+            Constraint::Length(6),
+            Constraint::Length(8),
+            Constraint::Length(10),
+            Constraint::Length(8),
+            Constraint::Fill(1),
+        ],
+    )
+    .header(Row::new(["Port", "PID", "State", "Restarts", "Proxy"]).bold())
+    .block(Block::bordered().title(format!(" Instances ({}) ", snapshot.instances.len())));
+    frame.render_widget(instances, instances_area);
+
+    // Per-target bytes only grow once a download finishes, so show the average since the start.
+    let seconds = stats.elapsed().as_secs_f64().max(1.0);
+    let target_rows = snapshot.targets.iter().map(|target| {
+        Row::new(vec![
+            format!("{:.2}", target.bytes as f64 * 8.0 / (seconds * 1_000_000.0)),
+            format!("{:.2}", target.bytes as f64 / MEBIBYTE),
+            target.success.to_string(),
+            target.failure.to_string(),
+            target.target.clone(),
+        ])
+    });
+    let targets = Table::new(
+        target_rows,
+        [
+            Constraint::Length(9),
+            Constraint::Length(10),
+            Constraint::Length(8),
+            Constraint::Length(8),
+            Constraint::Min(10),
+        ],
+    )
+    .header(Row::new(["Avg Mbps", "Total MiB", "OK", "Failed", "Target"]).bold())
+    .block(Block::bordered().title(" Targets "));
+    frame.render_widget(targets, targets_area);
+
+    let visible = log_area.height.saturating_sub(2) as usize;
+    let skip = snapshot.logs.len().saturating_sub(visible);
+    let log_lines: Vec<Line> = snapshot.logs[skip..]
+        .iter()
+        .map(|line| Line::from(line.as_str()))
+        .collect();
+    frame.render_widget(
+        Paragraph::new(log_lines).block(Block::bordered().title(" Log ")),
+        log_area,
+    );
+
+    frame.render_widget(
+        Line::from(" q / Esc / Ctrl+C: stop the test").dark_gray(),
+        help_area,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rates_follow_counter_deltas() {
+        let start = Instant::now();
+        let mut rates = Rates::new(start);
+        let mut stats = StressStats::new();
+
+        stats.bytes_transferred = 1_250_000;
+        stats.success_events = 8;
+        stats.failure_events = 2;
+        rates.update(&stats, start + Duration::from_secs(1));
+        assert!((rates.mbps - 10.0).abs() < 1e-9);
+        assert!((rates.events_per_sec - 10.0).abs() < 1e-9);
+        assert_eq!(rates.history, [10_000]);
+
+        // A warm-up baseline reset makes the totals drop instead of failing the subtraction.
+        stats.bytes_transferred = 0;
+        rates.update(&stats, start + Duration::from_secs(2));
+        assert_eq!(rates.mbps, 0.0);
+        assert_eq!(rates.history.len(), 2);
+    }
+
+    #[test]
+    fn test_log_buffer_keeps_complete_lines_while_capturing() {
+        let mut logs = LogBuffer::default();
+        logs.set_capturing(true);
+
+        logs.write_all(b"first line\nsecond ").unwrap();
+        assert_eq!(logs.tail(10), ["first line"]);
+        logs.write_all(b"line\n").unwrap();
+        assert_eq!(logs.tail(1), ["second line"]);
+
+        for i in 0..LOG_LINES {
+            writeln!(logs, "line {i}").unwrap();
+        }
+        assert_eq!(logs.tail(usize::MAX).len(), LOG_LINES);
+    }
+}